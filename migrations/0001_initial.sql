@@ -1,3 +1,7 @@
+-- Schema of the first release. Databases created from the old init.sql
+-- already have most of it, so every statement can run again.
+-- The chat tables are created by 0003_chat.sql.
+
 CREATE TABLE IF NOT EXISTS users (
   id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
   email VARCHAR(255) NOT NULL UNIQUE,
@@ -18,8 +22,15 @@ CREATE TABLE IF NOT EXISTS courses (
   FOREIGN KEY (preceptor_id) REFERENCES users(id) ON DELETE SET NULL
 );
 
-ALTER TABLE users ADD CONSTRAINT fk_course FOREIGN KEY (course_id) REFERENCES courses(id) ON DELETE SET NULL;
-
+SET @stmt = IF(
+  EXISTS(SELECT 1 FROM information_schema.TABLE_CONSTRAINTS
+         WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = 'users' AND CONSTRAINT_NAME = 'fk_course'),
+  'DO 0',
+  'ALTER TABLE users ADD CONSTRAINT fk_course FOREIGN KEY (course_id) REFERENCES courses(id) ON DELETE SET NULL'
+);
+PREPARE stmt FROM @stmt;
+EXECUTE stmt;
+DEALLOCATE PREPARE stmt;
 
 CREATE TABLE IF NOT EXISTS roles (
   id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
@@ -28,7 +39,6 @@ CREATE TABLE IF NOT EXISTS roles (
   FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
 );
 
-
 CREATE TABLE IF NOT EXISTS personal_data (
   id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
   user_id BIGINT UNSIGNED NOT NULL,
@@ -154,7 +164,6 @@ CREATE TABLE IF NOT EXISTS disciplinary_sanctions (
   FOREIGN KEY (student_id) REFERENCES users(id) ON DELETE CASCADE
 );
 
-
 CREATE TABLE IF NOT EXISTS messages (
   id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
   sender_id BIGINT UNSIGNED NOT NULL,
@@ -183,39 +192,3 @@ CREATE TABLE IF NOT EXISTS subject_messages (
   FOREIGN KEY (sender_id) REFERENCES users(id) ON DELETE CASCADE,
   FOREIGN KEY (subject_id) REFERENCES subjects(id) ON DELETE CASCADE
 );
-
-CREATE TABLE IF NOT EXISTS chats (
-  id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
-  name TEXT NOT NULL,
-  photo TEXT,
-  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-  description TEXT
-);
-
-CREATE TABLE IF NOT EXISTS chat_parciticipants (
-  id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
-  user_id BIGINT UNSIGNED NOT NULL,
-  chat_id BIGINT UNSIGNED NOT NULL,
-  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
-  FOREIGN KEY (chat_id) REFERENCES chats(id) ON DELETE CASCADE
-);
-
-CREATE TABLE IF NOT EXISTS chat_messages (
-  id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
-  chat_id BIGINT UNSIGNED NOT NULL,
-  sender_id BIGINT UNSIGNED NOT NULL,
-  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-  type_message ENUM('text','file') DEFAULT 'text',
-  message TEXT NOT NULL,
-  FOREIGN KEY (sender_id) REFERENCES users(id) ON DELETE CASCADE,
-  FOREIGN KEY (chat_id) REFERENCES chats(id) ON DELETE CASCADE
-);
-
-CREATE TABLE IF NOT EXISTS reads (
-  id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
-  message_id BIGINT UNSIGNED NOT NULL,
-  reader_id BIGINT UNSIGNED NOT NULL,
-  when TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-  FOREIGN KEY (reader_id) REFERENCES users(id) ON DELETE CASCADE,
-  FOREIGN KEY (message_id) REFERENCES chat_messages(id) ON DELETE CASCADE
-)
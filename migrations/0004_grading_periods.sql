@@ -0,0 +1,39 @@
+-- Grading periods (trimesters, cuatrimestres...) of the academic year. Every
+-- year has its own database, so these are the periods of that year.
+CREATE TABLE IF NOT EXISTS grading_periods (
+  id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+  name VARCHAR(100) NOT NULL,
+  start_date DATE NOT NULL,
+  end_date DATE NOT NULL
+);
+
+SET @stmt = IF(
+  EXISTS(SELECT 1 FROM information_schema.COLUMNS
+         WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = 'grades' AND COLUMN_NAME = 'period_id'),
+  'DO 0',
+  'ALTER TABLE grades ADD COLUMN period_id BIGINT UNSIGNED NULL'
+);
+PREPARE stmt FROM @stmt;
+EXECUTE stmt;
+DEALLOCATE PREPARE stmt;
+
+SET @stmt = IF(
+  EXISTS(SELECT 1 FROM information_schema.TABLE_CONSTRAINTS
+         WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = 'grades' AND CONSTRAINT_NAME = 'fk_grades_period'),
+  'DO 0',
+  'ALTER TABLE grades ADD CONSTRAINT fk_grades_period FOREIGN KEY (period_id) REFERENCES grading_periods(id) ON DELETE SET NULL'
+);
+PREPARE stmt FROM @stmt;
+EXECUTE stmt;
+DEALLOCATE PREPARE stmt;
+
+-- grades inserted without a period, by any path, go to the period of their date
+DROP TRIGGER IF EXISTS grades_assign_period;
+
+CREATE TRIGGER grades_assign_period BEFORE INSERT ON grades
+  FOR EACH ROW SET NEW.period_id = COALESCE(
+    NEW.period_id,
+    (SELECT id FROM grading_periods
+     WHERE DATE(COALESCE(NEW.created_at, NOW())) BETWEEN start_date AND end_date
+     ORDER BY start_date LIMIT 1)
+  );
@@ -0,0 +1,14 @@
+-- Weight of each assessment type in the averages, per subject or, without
+-- a subject, for the whole school. Types without a weight count 1.
+CREATE TABLE IF NOT EXISTS assessment_type_weights (
+  id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+  subject_id BIGINT UNSIGNED NULL,
+  -- NULL subjects are never equal in a unique key, this one makes the school default unique
+  subject_key BIGINT UNSIGNED AS (COALESCE(subject_id, 0)) STORED,
+  assessment_type ENUM('exam','homework','project','oral','remedial','selfassessable') NOT NULL,
+  weight DECIMAL(5,2) NOT NULL,
+  UNIQUE KEY uq_assessment_type_weight (subject_key, assessment_type),
+  -- a stored generated column forbids cascading from its base column,
+  -- deleting a subject deletes its weights first
+  FOREIGN KEY (subject_id) REFERENCES subjects(id)
+);
@@ -0,0 +1,29 @@
+-- Range of the numerical and percentage grades, and the grade a student
+-- passes with.
+CREATE TABLE IF NOT EXISTS grading_scales (
+  grade_type ENUM('numerical','percentage') PRIMARY KEY,
+  min_value DECIMAL(5,2) NOT NULL,
+  max_value DECIMAL(5,2) NOT NULL,
+  passing_value DECIMAL(5,2) NOT NULL
+);
+
+INSERT IGNORE INTO grading_scales (grade_type, min_value, max_value, passing_value) VALUES
+  ('numerical', 1, 10, 6),
+  ('percentage', 0, 100, 60);
+
+-- Labels of the conceptual grades. A conceptual grade stores the value of
+-- its label, a number of the numerical scale, so it counts in the averages.
+CREATE TABLE IF NOT EXISTS grading_scale_labels (
+  id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+  label VARCHAR(50) NOT NULL,
+  value DECIMAL(5,2) NOT NULL,
+  UNIQUE KEY uq_grading_scale_label (label),
+  UNIQUE KEY uq_grading_scale_value (value)
+);
+
+INSERT IGNORE INTO grading_scale_labels (label, value) VALUES
+  ('Excelente', 10),
+  ('Muy bueno', 8.5),
+  ('Bueno', 7),
+  ('Regular', 5),
+  ('Insuficiente', 3);
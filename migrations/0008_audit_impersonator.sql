@@ -0,0 +1,10 @@
+-- Admin behind an impersonated change, the actor is the impersonated user.
+SET @stmt = IF(
+  EXISTS(SELECT 1 FROM information_schema.COLUMNS
+         WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = 'audit_log' AND COLUMN_NAME = 'impersonator_id'),
+  'DO 0',
+  'ALTER TABLE audit_log ADD COLUMN impersonator_id BIGINT UNSIGNED NULL AFTER actor_role'
+);
+PREPARE stmt FROM @stmt;
+EXECUTE stmt;
+DEALLOCATE PREPARE stmt;
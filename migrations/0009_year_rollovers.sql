@@ -0,0 +1,6 @@
+-- Rollovers that led to this year's database, carried over by each one.
+-- User ids only match a past year reached through an unbroken chain of them.
+CREATE TABLE IF NOT EXISTS year_rollovers (
+  from_year INT NOT NULL PRIMARY KEY,
+  rolled_over_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,166 @@
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, MySqlPool};
+
+use crate::sessions::{generate_token, hash_token};
+use crate::structs::{ApiToken, MySelf, Role};
+use crate::traits::New;
+
+/// Lets the auth extractor tell API tokens apart from JWTs.
+pub const TOKEN_PREFIX: &str = "gs_pat_";
+
+#[derive(FromRow)]
+struct ApiTokenRow {
+    id: u64,
+    user_id: u64,
+    name: String,
+    token_hint: String,
+    role: Role,
+    allowed_endpoints: Option<String>,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+pub enum ApiTokenAuth {
+    Valid(MySelf),
+    Invalid,
+    EndpointNotAllowed,
+}
+
+pub fn is_api_token(token: &str) -> bool {
+    token.starts_with(TOKEN_PREFIX)
+}
+
+/// An allowed endpoint is `"<METHOD> <path prefix>"` or just a path prefix,
+/// which allows every method.
+pub fn check_endpoint_pattern(pattern: &str) -> Result<(), String> {
+    let path = match pattern.split_once(' ') {
+        Some((method, path)) => {
+            if !matches!(method, "GET" | "POST" | "PUT" | "PATCH" | "DELETE") {
+                return Err(format!("Invalid method in '{}'", pattern));
+            }
+            path
+        }
+        None => pattern,
+    };
+    if !path.starts_with("/api/v1/") {
+        return Err(format!("Endpoint '{}' must start with /api/v1/", pattern));
+    }
+    Ok(())
+}
+
+fn endpoint_allowed(patterns: &[String], method: &str, path: &str) -> bool {
+    patterns.iter().any(|pattern| match pattern.split_once(' ') {
+        Some((m, prefix)) => m == method && path.starts_with(prefix),
+        None => path.starts_with(pattern.as_str()),
+    })
+}
+
+/// Creates a token for a service account and returns its id and the
+/// plaintext token, which is not stored.
+pub async fn create_token(
+    pool: &MySqlPool,
+    user_id: u64,
+    name: &str,
+    role: &Role,
+    allowed_endpoints: Option<&[String]>,
+    expires_in_days: Option<i64>,
+    created_by: u64,
+) -> Result<(u64, String), sqlx::Error> {
+    let token = format!("{}{}", TOKEN_PREFIX, generate_token());
+    let allowed = allowed_endpoints.map(|a| serde_json::to_string(a).expect("strings serialize"));
+
+    let res = sqlx::query(
+        "INSERT INTO api_tokens (user_id, name, token_hash, token_hint, role, allowed_endpoints, created_by, expires_at) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, IF(? IS NULL, NULL, DATE_ADD(NOW(), INTERVAL ? DAY)))",
+    )
+    .bind(user_id)
+    .bind(name)
+    .bind(hash_token(&token))
+    .bind(&token[..TOKEN_PREFIX.len() + 6])
+    .bind(role)
+    .bind(allowed)
+    .bind(created_by)
+    .bind(expires_in_days)
+    .bind(expires_in_days)
+    .execute(pool)
+    .await?;
+
+    Ok((res.last_insert_id(), token))
+}
+
+pub async fn authenticate(
+    pool: &MySqlPool,
+    token: &str,
+    method: &str,
+    path: &str,
+) -> Result<ApiTokenAuth, sqlx::Error> {
+    let row: Option<(u64, u64, Role, Option<String>)> = sqlx::query_as(
+        "SELECT t.id, t.user_id, t.role, t.allowed_endpoints FROM api_tokens t \
+         JOIN users u ON u.id = t.user_id \
+         JOIN roles r ON r.user_id = t.user_id AND r.role = t.role \
+         WHERE t.token_hash = ? AND t.revoked_at IS NULL \
+         AND (t.expires_at IS NULL OR t.expires_at > NOW()) AND u.is_service_account = TRUE",
+    )
+    .bind(hash_token(token))
+    .fetch_optional(pool)
+    .await?;
+
+    let (token_id, user_id, role, allowed_endpoints) = match row {
+        Some(r) => r,
+        None => return Ok(ApiTokenAuth::Invalid),
+    };
+
+    match allowed_endpoints {
+        Some(allowed) => {
+            let patterns: Vec<String> = serde_json::from_str(&allowed).unwrap_or_default();
+            if !endpoint_allowed(&patterns, method, path) {
+                return Ok(ApiTokenAuth::EndpointNotAllowed);
+            }
+        }
+        // admin tokens created before they had to list their endpoints
+        None if role == Role::admin => return Ok(ApiTokenAuth::EndpointNotAllowed),
+        None => {}
+    }
+
+    sqlx::query("UPDATE api_tokens SET last_used_at = NOW() WHERE id = ?")
+        .bind(token_id)
+        .execute(pool)
+        .await?;
+
+    Ok(ApiTokenAuth::Valid(MySelf::new(user_id, role)))
+}
+
+pub async fn list_tokens(pool: &MySqlPool) -> Result<Vec<ApiToken>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, ApiTokenRow>(
+        "SELECT id, user_id, name, token_hint, role, allowed_endpoints, created_at, last_used_at, expires_at, revoked_at \
+         FROM api_tokens ORDER BY created_at DESC",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| ApiToken {
+            id: r.id,
+            user_id: r.user_id,
+            name: r.name,
+            token_hint: r.token_hint,
+            role: r.role,
+            allowed_endpoints: r.allowed_endpoints.and_then(|a| serde_json::from_str(&a).ok()),
+            created_at: r.created_at,
+            last_used_at: r.last_used_at,
+            expires_at: r.expires_at,
+            revoked_at: r.revoked_at,
+        })
+        .collect())
+}
+
+pub async fn revoke_token(pool: &MySqlPool, token_id: u64) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query("UPDATE api_tokens SET revoked_at = NOW() WHERE id = ? AND revoked_at IS NULL")
+        .bind(token_id)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
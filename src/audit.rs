@@ -0,0 +1,109 @@
+use serde::Serialize;
+use sqlx::mysql::MySqlRow;
+use sqlx::{FromRow, MySqlConnection};
+
+use crate::structs::{AuditAction, Grade, MySelf};
+
+// Audited entities, named after their tables.
+pub const GRADES: &str = "grades";
+pub const ASSISTANCE: &str = "assistance";
+pub const DISCIPLINARY_SANCTIONS: &str = "disciplinary_sanctions";
+// Not a table: the entity id is the impersonated user.
+pub const IMPERSONATIONS: &str = "impersonations";
+
+/// Current state of a row as JSON, for the before/after columns. `table` is
+/// always one of the constants above, never user input. Takes the connection
+/// of the change's transaction so the snapshot sees exactly what is written.
+pub async fn snapshot<T>(
+    conn: &mut MySqlConnection,
+    table: &str,
+    id: u64,
+) -> Result<Option<serde_json::Value>, sqlx::Error>
+where
+    T: for<'r> FromRow<'r, MySqlRow> + Serialize + Send + Unpin,
+{
+    let row = sqlx::query_as::<_, T>(&format!("SELECT * FROM {} WHERE id = ?", table))
+        .bind(id)
+        .fetch_optional(conn)
+        .await?;
+    Ok(row.and_then(|r| serde_json::to_value(r).ok()))
+}
+
+/// Appends an entry to the audit log. Call it on the transaction of the
+/// change it describes: if the entry can't be written the change is rolled
+/// back with it. Changes made while impersonating keep the admin behind them.
+pub async fn record(
+    conn: &mut MySqlConnection,
+    actor: &MySelf,
+    entity: &str,
+    entity_id: u64,
+    action: AuditAction,
+    before: Option<serde_json::Value>,
+    after: Option<serde_json::Value>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO audit_log (actor_id, actor_role, impersonator_id, entity, entity_id, action, before_data, after_data) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(actor.id)
+    .bind(&actor.role)
+    .bind(actor.impersonator_id)
+    .bind(entity)
+    .bind(entity_id)
+    .bind(action)
+    .bind(before)
+    .bind(after)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Snapshots of the grades a bulk change is about to touch, for
+/// `record_grade_updates`.
+pub async fn snapshot_grades(
+    conn: &mut MySqlConnection,
+    ids: &[u64],
+) -> Result<Vec<Option<serde_json::Value>>, sqlx::Error> {
+    let mut snapshots = Vec::with_capacity(ids.len());
+    for id in ids {
+        snapshots.push(snapshot::<Grade>(&mut *conn, GRADES, *id).await?);
+    }
+    Ok(snapshots)
+}
+
+/// Records an update entry for every grade of a bulk change that actually
+/// changed, given their snapshots from before it.
+pub async fn record_grade_updates(
+    conn: &mut MySqlConnection,
+    actor: &MySelf,
+    ids: &[u64],
+    before: Vec<Option<serde_json::Value>>,
+) -> Result<(), sqlx::Error> {
+    for (id, before) in ids.iter().zip(before) {
+        let after = snapshot::<Grade>(&mut *conn, GRADES, *id).await?;
+        if after != before {
+            record(&mut *conn, actor, GRADES, *id, AuditAction::Update, before, after).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Records a delete entry for every grade whose `column` is `id`, before a
+/// delete that cascades into them (an assessment or subject). `column` is a
+/// fixed column name, never user input.
+pub async fn record_grade_cascade(
+    conn: &mut MySqlConnection,
+    actor: &MySelf,
+    column: &str,
+    id: u64,
+) -> Result<(), sqlx::Error> {
+    let grades = sqlx::query_as::<_, Grade>(&format!("SELECT * FROM grades WHERE {} = ?", column))
+        .bind(id)
+        .fetch_all(&mut *conn)
+        .await?;
+    for grade in grades {
+        let before = serde_json::to_value(&grade).ok();
+        record(&mut *conn, actor, GRADES, grade.id, AuditAction::Delete, before, None).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use actix_web::dev::Payload;
+use actix_web::error::{ErrorInternalServerError, InternalError};
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::http::Method;
+use actix_web::{web, Error, FromRequest, HttpRequest, HttpResponse};
+use futures::future::LocalBoxFuture;
+use sqlx::MySqlPool;
+
+use crate::api_tokens::{self, is_api_token, ApiTokenAuth};
+use crate::jwt::{validate, Claims};
+use crate::schools::{School, DEFAULT_SCHOOL};
+use crate::sessions::ACCESS_COOKIE;
+use crate::structs::MySelf;
+
+/// Access token of the request: an `Authorization: Bearer` header takes
+/// precedence over the `jwt` cookie.
+pub fn access_token(req: &HttpRequest) -> Option<String> {
+    let bearer = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| {
+            let (scheme, token) = h.split_once(' ')?;
+            scheme.eq_ignore_ascii_case("bearer").then(|| token.trim().to_string())
+        });
+
+    bearer.or_else(|| req.cookie(ACCESS_COOKIE).map(|c| c.value().to_string()))
+}
+
+fn unauthorized(message: &str) -> Error {
+    InternalError::from_response(message.to_string(), HttpResponse::Unauthorized().json(message)).into()
+}
+
+/// Impersonation tokens never reach these, even with writes allowed: they
+/// act on the credentials and sessions of the impersonated user, and the
+/// chat websocket sends messages with plain GET upgrades.
+const IMPERSONATION_BLOCKED_PATHS: &[&str] = &[
+    "/api/v1/account/",
+    "/api/v1/sessions/",
+    "/api/v1/switch_role/",
+    "/api/v1/ws/",
+];
+
+/// Ending the impersonation is always allowed, also for read-only tokens.
+pub const IMPERSONATION_PATH: &str = "/api/v1/impersonation/";
+
+fn forbidden(message: &'static str) -> Error {
+    InternalError::from_response(message, HttpResponse::Forbidden().json(message)).into()
+}
+
+/// Restrictions for tokens issued to an admin acting as another user. Every
+/// request made with one is logged.
+fn check_impersonation(claims: &Claims, method: &Method, path: &str) -> Result<(), Error> {
+    let Some(impersonation) = &claims.impersonation else {
+        return Ok(());
+    };
+
+    log::info!(
+        "Admin {} as user {} ({:?}): {} {}",
+        impersonation.admin_id,
+        claims.user.id,
+        claims.user.role,
+        method,
+        path
+    );
+
+    if path == IMPERSONATION_PATH {
+        return Ok(());
+    }
+    if IMPERSONATION_BLOCKED_PATHS.iter().any(|p| path.starts_with(p)) {
+        return Err(forbidden("Endpoint not allowed while impersonating"));
+    }
+    if impersonation.read_only && !matches!(*method, Method::GET | Method::HEAD) {
+        return Err(forbidden("Impersonation is read-only"));
+    }
+    Ok(())
+}
+
+/// Validated claims of the caller, only from a JWT: endpoints that work on
+/// the login session itself take this. Handlers that only need the user take
+/// `MySelf` instead.
+impl FromRequest for Claims {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let token = access_token(req);
+        let pool = req.app_data::<web::Data<MySqlPool>>().cloned();
+        let school = req.app_data::<web::Data<School>>().map(|s| s.id.clone());
+        let method = req.method().clone();
+        let path = req.path().to_string();
+
+        Box::pin(async move {
+            let token = token.ok_or_else(|| unauthorized("Missing authentication token"))?;
+            let pool = pool.ok_or_else(|| ErrorInternalServerError("Database pool not configured"))?;
+
+            let mut claims = validate(&token, &pool)
+                .await
+                .map(|t| t.claims)
+                .map_err(|_| unauthorized("Invalid JWT token"))?;
+            // user ids repeat across schools, a token only works in its own
+            let token_school = claims.school.as_deref().unwrap_or(DEFAULT_SCHOOL);
+            if school.is_some_and(|s| s != token_school) {
+                return Err(unauthorized("Token issued by another school"));
+            }
+            check_impersonation(&claims, &method, &path)?;
+            claims.user.impersonator_id = claims.impersonation.as_ref().map(|i| i.admin_id);
+            Ok(claims)
+        })
+    }
+}
+
+/// Unlike `Claims`, the user can also come from an API token of a service
+/// account, limited to the endpoints allowed for that token.
+impl FromRequest for MySelf {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let token = access_token(req);
+        if !token.as_deref().is_some_and(is_api_token) {
+            let claims = Claims::from_request(req, payload);
+            return Box::pin(async move { Ok(claims.await?.user) });
+        }
+
+        let pool = req.app_data::<web::Data<MySqlPool>>().cloned();
+        let method = req.method().to_string();
+        let path = req.path().to_string();
+
+        Box::pin(async move {
+            let token = token.unwrap_or_default();
+            let pool = pool.ok_or_else(|| ErrorInternalServerError("Database pool not configured"))?;
+
+            match api_tokens::authenticate(&pool, &token, &method, &path).await {
+                Ok(ApiTokenAuth::Valid(user)) => Ok(user),
+                Ok(ApiTokenAuth::Invalid) => Err(unauthorized("Invalid API token")),
+                Ok(ApiTokenAuth::EndpointNotAllowed) => Err(forbidden("Endpoint not allowed for this token")),
+                Err(e) => Err(ErrorInternalServerError(e.to_string())),
+            }
+        })
+    }
+}
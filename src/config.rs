@@ -0,0 +1,217 @@
+use std::env;
+use std::fmt::Display;
+use std::fs::read_to_string;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use lettre::message::Mailbox;
+use serde::Deserialize;
+
+/// Every setting of the service. Read once at startup from the JSON file at
+/// `CONFIG_FILE`, if any, then from the environment, which wins. Field names
+/// are the lowercase environment variable names.
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub database_url: String,
+    pub academic_year: Option<i32>,
+    pub auto_migrate: bool,
+    pub schools_file: Option<String>,
+
+    pub bind_address: String,
+    pub base_url: String,
+    pub frontend_url: Option<String>,
+    pub debug: bool,
+
+    /// Root of `uploads/` and `email_templates/` unless they are set.
+    pub base_path: String,
+    pub uploads_dir: Option<String>,
+    pub email_templates_dir: Option<String>,
+    pub max_upload_bytes: usize,
+    pub json_limit_bytes: usize,
+
+    pub jwt_keys_dir: String,
+    /// Where create_database.py left the key pair used before the keyring.
+    pub legacy_keys_dir: String,
+    pub totp_issuer: String,
+
+    pub smtp_host: String,
+    pub email_from: Option<String>,
+    pub email_username: Option<String>,
+    pub email_password: Option<String>,
+
+    pub school_name: String,
+    pub school_logo_url: Option<String>,
+    pub school_primary_color: Option<String>,
+
+    /// Seconds, minutes, hours, day of month, month, day of week and year.
+    pub cron_schedule: String,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            database_url: String::new(),
+            academic_year: None,
+            auto_migrate: true,
+            schools_file: None,
+            bind_address: "0.0.0.0:80".to_string(),
+            base_url: String::new(),
+            frontend_url: None,
+            debug: false,
+            base_path: ".".to_string(),
+            uploads_dir: None,
+            email_templates_dir: None,
+            max_upload_bytes: 10 * 1024 * 1024,
+            json_limit_bytes: 4096,
+            jwt_keys_dir: "/shared/jwt_keys".to_string(),
+            legacy_keys_dir: "/shared".to_string(),
+            totp_issuer: "GoSchool".to_string(),
+            smtp_host: "smtp.gmail.com".to_string(),
+            email_from: None,
+            email_username: None,
+            email_password: None,
+            school_name: "GoSchool".to_string(),
+            school_logo_url: None,
+            school_primary_color: None,
+            cron_schedule: "0 0/15 * * * * *".to_string(),
+        }
+    }
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Configuration loaded by `load`. Handlers take it as `web::Data<Config>`,
+/// this is for code that runs outside a request.
+pub fn get() -> &'static Config {
+    CONFIG.get().expect("Configuration not loaded")
+}
+
+/// Loads and validates the configuration, reporting every problem at once.
+pub fn load() -> anyhow::Result<&'static Config> {
+    let mut config = match env::var("CONFIG_FILE") {
+        Ok(path) => {
+            let content = read_to_string(&path).map_err(|e| anyhow::anyhow!("Cannot read {}: {}", path, e))?;
+            serde_json::from_str(&content).map_err(|e| anyhow::anyhow!("Invalid {}: {}", path, e))?
+        }
+        Err(_) => Config::default(),
+    };
+
+    let mut errors = Vec::new();
+    config.apply_env(&mut errors);
+    config.validate(&mut errors);
+    if !errors.is_empty() {
+        anyhow::bail!("Invalid configuration:\n  {}", errors.join("\n  "));
+    }
+
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+fn from_env<T: FromStr>(target: &mut T, var: &str, errors: &mut Vec<String>)
+where
+    T::Err: Display,
+{
+    if let Ok(value) = env::var(var) {
+        match value.trim().parse() {
+            Ok(v) => *target = v,
+            Err(e) => errors.push(format!("{}: {}", var, e)),
+        }
+    }
+}
+
+fn optional_from_env<T: FromStr>(target: &mut Option<T>, var: &str, errors: &mut Vec<String>)
+where
+    T::Err: Display,
+{
+    if let Ok(value) = env::var(var) {
+        match value.trim().parse() {
+            Ok(v) => *target = Some(v),
+            Err(e) => errors.push(format!("{}: {}", var, e)),
+        }
+    }
+}
+
+impl Config {
+    fn apply_env(&mut self, errors: &mut Vec<String>) {
+        from_env(&mut self.database_url, "DATABASE_URL", errors);
+        optional_from_env(&mut self.academic_year, "ACADEMIC_YEAR", errors);
+        from_env(&mut self.auto_migrate, "AUTO_MIGRATE", errors);
+        optional_from_env(&mut self.schools_file, "SCHOOLS_FILE", errors);
+        from_env(&mut self.bind_address, "BIND_ADDRESS", errors);
+        from_env(&mut self.base_url, "BASE_URL", errors);
+        optional_from_env(&mut self.frontend_url, "FRONTEND_URL", errors);
+        from_env(&mut self.debug, "DEBUG", errors);
+        from_env(&mut self.base_path, "BASE_PATH", errors);
+        optional_from_env(&mut self.uploads_dir, "UPLOADS_DIR", errors);
+        optional_from_env(&mut self.email_templates_dir, "EMAIL_TEMPLATES_DIR", errors);
+        from_env(&mut self.max_upload_bytes, "MAX_UPLOAD_BYTES", errors);
+        from_env(&mut self.json_limit_bytes, "JSON_LIMIT_BYTES", errors);
+        from_env(&mut self.jwt_keys_dir, "JWT_KEYS_DIR", errors);
+        from_env(&mut self.legacy_keys_dir, "LEGACY_KEYS_DIR", errors);
+        from_env(&mut self.totp_issuer, "TOTP_ISSUER", errors);
+        from_env(&mut self.smtp_host, "SMTP_HOST", errors);
+        optional_from_env(&mut self.email_from, "EMAIL_FROM", errors);
+        optional_from_env(&mut self.email_username, "EMAIL_USERNAME", errors);
+        optional_from_env(&mut self.email_password, "EMAIL_PASSWORD", errors);
+        from_env(&mut self.school_name, "SCHOOL_NAME", errors);
+        optional_from_env(&mut self.school_logo_url, "SCHOOL_LOGO_URL", errors);
+        optional_from_env(&mut self.school_primary_color, "SCHOOL_PRIMARY_COLOR", errors);
+        from_env(&mut self.cron_schedule, "CRON_SCHEDULE", errors);
+    }
+
+    fn validate(&mut self, errors: &mut Vec<String>) {
+        if self.database_url.is_empty() {
+            errors.push("DATABASE_URL must be set".to_string());
+        }
+        if self.base_url.is_empty() {
+            errors.push("BASE_URL must be set".to_string());
+        }
+        // links are built as `{base_url}path`
+        for url in [Some(&mut self.base_url), self.frontend_url.as_mut()].into_iter().flatten() {
+            if !url.is_empty() && !url.ends_with('/') {
+                url.push('/');
+            }
+        }
+        if self.bind_address.parse::<SocketAddr>().is_err() {
+            errors.push(format!("BIND_ADDRESS '{}' must be an address and port like 0.0.0.0:80", self.bind_address));
+        }
+        if self.max_upload_bytes == 0 {
+            errors.push("MAX_UPLOAD_BYTES must be greater than 0".to_string());
+        }
+        if self.json_limit_bytes == 0 {
+            errors.push("JSON_LIMIT_BYTES must be greater than 0".to_string());
+        }
+        if let Err(e) = cron::Schedule::from_str(&self.cron_schedule) {
+            errors.push(format!("CRON_SCHEDULE '{}': {}", self.cron_schedule, e));
+        }
+
+        let email = [&self.email_from, &self.email_username, &self.email_password];
+        if email.iter().any(|v| v.is_some()) && !email.iter().all(|v| v.is_some()) {
+            errors.push("EMAIL_FROM, EMAIL_USERNAME and EMAIL_PASSWORD must be set together".to_string());
+        }
+        if let Some(from) = &self.email_from
+            && from.parse::<Mailbox>().is_err()
+        {
+            errors.push(format!("EMAIL_FROM '{}' is not a valid address", from));
+        }
+
+        let templates = self.email_templates_dir();
+        if !Path::new(&templates).is_dir() {
+            errors.push(format!("Email templates directory {} not found, set BASE_PATH or EMAIL_TEMPLATES_DIR", templates));
+        }
+    }
+
+    pub fn uploads_dir(&self) -> String {
+        self.uploads_dir
+            .clone()
+            .unwrap_or_else(|| format!("{}/uploads", self.base_path))
+    }
+
+    pub fn email_templates_dir(&self) -> String {
+        self.email_templates_dir
+            .clone()
+            .unwrap_or_else(|| format!("{}/email_templates", self.base_path))
+    }
+}
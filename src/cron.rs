@@ -4,6 +4,11 @@ use sqlx::MySqlPool;
 use std::{str::FromStr, time::Duration};
 use tokio::time::sleep;
 
+use crate::config;
+use crate::keyring;
+use crate::login_guard::delete_old_attempts;
+use crate::sessions::delete_expired_sessions;
+
 async fn migrate_selfassessable_pending_grades(pool: &MySqlPool) -> Result<(), sqlx::Error> {
     let insert_result = sqlx::query(
         r#"
@@ -49,7 +54,8 @@ async fn migrate_selfassessable_pending_grades(pool: &MySqlPool) -> Result<(), s
 }
 
 pub async fn start_cron_task(pool: MySqlPool) {
-    let schedule = Schedule::from_str("0 0/15 * * * * *").unwrap();
+    // validated when the configuration is loaded
+    let schedule = Schedule::from_str(&config::get().cron_schedule).unwrap();
     let mut upcoming = schedule.upcoming(Utc);
 
     loop {
@@ -63,6 +69,42 @@ pub async fn start_cron_task(pool: MySqlPool) {
             if let Err(e) = migrate_selfassessable_pending_grades(&pool).await {
                 eprintln!("Error migrating pending grades: {}", e);
             }
+
+            match delete_expired_sessions(&pool).await {
+                Ok(n) if n > 0 => println!("Deleted {} expired sessions", n),
+                Ok(_) => {}
+                Err(e) => eprintln!("Error deleting expired sessions: {}", e),
+            }
+
+            if let Err(e) = sqlx::query("DELETE FROM password_resets WHERE expires_at < NOW()")
+                .execute(&pool)
+                .await
+            {
+                eprintln!("Error deleting expired password resets: {}", e);
+            }
+
+            if let Err(e) = sqlx::query("DELETE FROM email_changes WHERE expires_at < NOW()")
+                .execute(&pool)
+                .await
+            {
+                eprintln!("Error deleting expired email changes: {}", e);
+            }
+
+            if let Err(e) = sqlx::query("DELETE FROM login_challenges WHERE expires_at < NOW()")
+                .execute(&pool)
+                .await
+            {
+                eprintln!("Error deleting expired login challenges: {}", e);
+            }
+
+            if let Err(e) = delete_old_attempts(&pool).await {
+                eprintln!("Error deleting old login attempts: {}", e);
+            }
+
+            // picks up keys rotated by another instance or the CLI
+            if let Err(e) = keyring::reload() {
+                eprintln!("Error reloading JWT keys: {}", e);
+            }
         }
     }
 }
@@ -1,11 +1,70 @@
-use std::env;
 use lettre::message::{header::ContentType, Mailbox, Message};
 use lettre::transport::smtp::authentication::Credentials;
 use lettre::{SmtpTransport, Transport};
 use std::fs::read_to_string;
+use std::path::Path;
 use tera::{Context, Tera};
 use tokio::task;
 
+use crate::config;
+use crate::schools::{self, School};
+
+/// Sender address of the school, `email_from` unless it has its own. Both
+/// are checked at startup.
+fn email_from(school: &School) -> Mailbox {
+    let from = match &school.email {
+        Some(sender) => &sender.from,
+        None => config::get().email_from.as_ref().expect("School without email sender"),
+    };
+    from.parse().expect("Invalid email sender")
+}
+
+fn mailer(school: &School) -> SmtpTransport {
+    let config = config::get();
+    let (host, credentials) = match &school.email {
+        Some(sender) => (
+            sender.smtp_host.as_deref().unwrap_or(&config.smtp_host),
+            Credentials::new(sender.username.clone(), sender.password.clone()),
+        ),
+        None => (
+            config.smtp_host.as_str(),
+            Credentials::new(
+                config.email_username.clone().expect("School without email sender"),
+                config.email_password.clone().expect("School without email sender"),
+            ),
+        ),
+    };
+
+    SmtpTransport::relay(host)
+        .unwrap()
+        .credentials(credentials)
+        .build()
+}
+
+/// `{school id}/{name}.html` of the templates directory when the school
+/// overrides it, otherwise the shared `{name}.html`.
+fn read_template(school: &School, name: &str) -> std::io::Result<String> {
+    let dir = config::get().email_templates_dir();
+    let school_path = format!("{}/{}/{}.html", dir, school.id, name);
+    if Path::new(&school_path).exists() {
+        return read_to_string(school_path);
+    }
+    read_to_string(format!("{}/{}.html", dir, name))
+}
+
+/// Name and branding of the school, available to every template.
+fn school_context(school: &School) -> Context {
+    let mut context = Context::new();
+    context.insert("school_name", &ammonia::clean(&school.name));
+    if let Some(logo_url) = &school.branding.logo_url {
+        context.insert("school_logo_url", &ammonia::clean(logo_url));
+    }
+    if let Some(color) = &school.branding.primary_color {
+        context.insert("school_primary_color", &ammonia::clean(color));
+    }
+    context
+}
+
 pub async fn send_grade_email(
     reply_to: Vec<String>,
     subject: &str,
@@ -13,20 +72,17 @@ pub async fn send_grade_email(
     student_name: &str,
     grade: &str,
 ) {
-    let from_str = env::var("EMAIL_FROM").expect("EMAIL_FROM must be set");
-    let from: Mailbox = from_str.parse().expect("Invalid EMAIL_FROM format");
-    let base_dir = env::var("BASE_PATH").expect("BASE_PATH must be set");
+    let school = schools::current();
+    let from = email_from(&school);
 
-    let template_path = format!("{}/email_templates/grade_submitted.html", base_dir);
-    let template_str = match read_to_string(&template_path) {
+    let template_str = match read_template(&school, "grade_submitted") {
         Ok(t) => t,
         Err(e) => {
             eprintln!("Error cargando template: {}", e);
             return;
         }
     };
-    let footer_template_path = format!("{}/email_templates/footer.html", base_dir);
-    let footer_template_str = match read_to_string(&footer_template_path) {
+    let footer_template_str = match read_template(&school, "footer") {
         Ok(t) => t,
         Err(e) => {
             eprintln!("Error cargando template: {}", e);
@@ -36,15 +92,8 @@ pub async fn send_grade_email(
 
     let email_subject = "Grade Submitted";
 
-    let credentials = Credentials::new(
-        env::var("EMAIL_USERNAME").expect("EMAIL_USERNAME must be set"),
-        env::var("EMAIL_PASSWORD").expect("EMAIL_PASSWORD must be set"),
-    );
-
-    let mailer = SmtpTransport::relay("smtp.gmail.com")
-        .unwrap()
-        .credentials(credentials)
-        .build();
+    let mailer = mailer(&school);
+    let base_context = school_context(&school);
 
     let header = ContentType::TEXT_HTML;
 
@@ -61,6 +110,7 @@ pub async fn send_grade_email(
         let to_str_clone = to_str.clone();
 
         let template_clone = template_str.clone();
+        let base_context = base_context.clone();
         let footer_template_clone = footer_template_str.clone();
 
         let sender_name = sender_name.clone();
@@ -71,7 +121,7 @@ pub async fn send_grade_email(
         task::spawn_blocking(move || {
             match to_str_clone.parse::<Mailbox>() {
                 Ok(to) => {
-                    let mut context = Context::new();
+                    let mut context = base_context;
                     context.insert("sender_name", &ammonia::clean(&sender_name));
                     context.insert("student_name", &ammonia::clean(&student_name));
                     context.insert("subject", &ammonia::clean(&subject));
@@ -115,20 +165,17 @@ pub async fn send_message_email(
     sender_name: &str,
     message: &str,
 ) {
-    let from_str = env::var("EMAIL_FROM").expect("EMAIL_FROM must be set");
-    let from: Mailbox = from_str.parse().expect("Invalid EMAIL_FROM format");
-    let base_dir = env::var("BASE_PATH").expect("BASE_PATH must be set");
+    let school = schools::current();
+    let from = email_from(&school);
 
-    let template_path = format!("{}/email_templates/message_sent.html", base_dir);
-    let template_str = match read_to_string(&template_path) {
+    let template_str = match read_template(&school, "message_sent") {
         Ok(t) => t,
         Err(e) => {
             eprintln!("Error cargando template: {}", e);
             return;
         }
     };
-    let footer_template_path = format!("{}/email_templates/footer.html", base_dir);
-    let footer_template_str = match read_to_string(&footer_template_path) {
+    let footer_template_str = match read_template(&school, "footer") {
         Ok(t) => t,
         Err(e) => {
             eprintln!("Error cargando template: {}", e);
@@ -138,15 +185,8 @@ pub async fn send_message_email(
 
     let email_subject = "Nuevo mensaje recibido";
 
-    let credentials = Credentials::new(
-        env::var("EMAIL_USERNAME").expect("EMAIL_USERNAME must be set"),
-        env::var("EMAIL_PASSWORD").expect("EMAIL_PASSWORD must be set"),
-    );
-
-    let mailer = SmtpTransport::relay("smtp.gmail.com")
-        .unwrap()
-        .credentials(credentials)
-        .build();
+    let mailer = mailer(&school);
+    let base_context = school_context(&school);
 
     let header = ContentType::TEXT_HTML;
 
@@ -160,12 +200,13 @@ pub async fn send_message_email(
         let student_name = student_name.to_string();
 
         let template_clone = template_str.clone();
+        let base_context = base_context.clone();
         let footer_template_clone = footer_template_str.clone();
 
         task::spawn_blocking(move || {
             match to_str.parse::<Mailbox>() {
                 Ok(to) => {
-                    let mut context = Context::new();
+                    let mut context = base_context;
                     context.insert("sender_name", &ammonia::clean(&sender_name));
                     context.insert("student_name", &ammonia::clean(&student_name));
                     context.insert("message", &ammonia::clean(&message));
@@ -210,20 +251,17 @@ pub async fn send_subject_message_email(
     subject_name: &str,
     message: &str,
 ) {
-    let from_str = env::var("EMAIL_FROM").expect("EMAIL_FROM must be set");
-    let from: Mailbox = from_str.parse().expect("Invalid EMAIL_FROM format");
-    let base_dir = env::var("BASE_PATH").expect("BASE_PATH must be set");
+    let school = schools::current();
+    let from = email_from(&school);
 
-    let template_path = format!("{}/email_templates/subject_message_sent.html", base_dir);
-    let template_str = match read_to_string(&template_path) {
+    let template_str = match read_template(&school, "subject_message_sent") {
         Ok(t) => t,
         Err(e) => {
             eprintln!("Error cargando template: {}", e);
             return;
         }
     };
-    let footer_template_path = format!("{}/email_templates/footer.html", base_dir);
-    let footer_template_str = match read_to_string(&footer_template_path) {
+    let footer_template_str = match read_template(&school, "footer") {
         Ok(t) => t,
         Err(e) => {
             eprintln!("Error cargando template: {}", e);
@@ -231,15 +269,8 @@ pub async fn send_subject_message_email(
         }
     };
 
-    let credentials = Credentials::new(
-        env::var("EMAIL_USERNAME").expect("EMAIL_USERNAME must be set"),
-        env::var("EMAIL_PASSWORD").expect("EMAIL_PASSWORD must be set"),
-    );
-
-    let mailer = SmtpTransport::relay("smtp.gmail.com")
-        .unwrap()
-        .credentials(credentials)
-        .build();
+    let mailer = mailer(&school);
+    let base_context = school_context(&school);
 
     let header = ContentType::TEXT_HTML;
 
@@ -252,12 +283,13 @@ pub async fn send_subject_message_email(
         let message = message.to_string();
 
         let template_clone = template_str.clone();
+        let base_context = base_context.clone();
         let footer_template_clone = footer_template_str.clone();
 
         task::spawn_blocking(move || {
             match to_str.parse::<Mailbox>() {
                 Ok(to) => {
-                    let mut context = Context::new();
+                    let mut context = base_context;
                     context.insert("sender_name", &ammonia::clean(&sender_name));
                     context.insert("student_name", &ammonia::clean(&student_name));
                     context.insert("subject_name", &ammonia::clean(&subject_name));
@@ -304,20 +336,17 @@ pub async fn send_assessment_email(
     assessment_title: &str,
     due_date: &str,
 ) {
-    let from_str = env::var("EMAIL_FROM").expect("EMAIL_FROM must be set");
-    let from: Mailbox = from_str.parse().expect("Invalid EMAIL_FROM format");
-    let base_dir = env::var("BASE_PATH").expect("BASE_PATH must be set");
+    let school = schools::current();
+    let from = email_from(&school);
 
-    let template_path = format!("{}/email_templates/assessment_created.html", base_dir);
-    let template_str = match read_to_string(&template_path) {
+    let template_str = match read_template(&school, "assessment_created") {
         Ok(t) => t,
         Err(e) => {
             eprintln!("Error cargando template: {}", e);
             return;
         }
     };
-    let footer_template_path = format!("{}/email_templates/footer.html", base_dir);
-    let footer_template_str = match read_to_string(&footer_template_path) {
+    let footer_template_str = match read_template(&school, "footer") {
         Ok(t) => t,
         Err(e) => {
             eprintln!("Error cargando template: {}", e);
@@ -325,15 +354,8 @@ pub async fn send_assessment_email(
         }
     };
 
-    let credentials = Credentials::new(
-        env::var("EMAIL_USERNAME").expect("EMAIL_USERNAME must be set"),
-        env::var("EMAIL_PASSWORD").expect("EMAIL_PASSWORD must be set"),
-    );
-
-    let mailer = SmtpTransport::relay("smtp.gmail.com")
-        .unwrap()
-        .credentials(credentials)
-        .build();
+    let mailer = mailer(&school);
+    let base_context = school_context(&school);
 
     let header = ContentType::TEXT_HTML;
 
@@ -348,12 +370,13 @@ pub async fn send_assessment_email(
         let subject_name = subject_name.to_string();
 
         let template_clone = template_str.clone();
+        let base_context = base_context.clone();
         let footer_template_clone = footer_template_str.clone();
 
         task::spawn_blocking(move || {
             match to_str.parse::<Mailbox>() {
                 Ok(to) => {
-                    let mut context = Context::new();
+                    let mut context = base_context;
                     context.insert("sender_name", &ammonia::clean(&sender_name));
                     context.insert("student_name", &ammonia::clean(&student_name));
                     context.insert("subject_name", &ammonia::clean(&subject_name));
@@ -402,20 +425,17 @@ pub async fn send_assistance_email(
     presence: &str,
     date: &str,
 ) {
-    let from_str = env::var("EMAIL_FROM").expect("EMAIL_FROM must be set");
-    let from: Mailbox = from_str.parse().expect("Invalid EMAIL_FROM format");
-    let base_dir = env::var("BASE_PATH").expect("BASE_PATH must be set");
+    let school = schools::current();
+    let from = email_from(&school);
 
-    let template_path = format!("{}/email_templates/assistance_upload.html", base_dir);
-    let template_str = match read_to_string(&template_path) {
+    let template_str = match read_template(&school, "assistance_upload") {
         Ok(t) => t,
         Err(e) => {
             eprintln!("Error cargando template: {}", e);
             return;
         }
     };
-    let footer_template_path = format!("{}/email_templates/footer.html", base_dir);
-    let footer_template_str = match read_to_string(&footer_template_path) {
+    let footer_template_str = match read_template(&school, "footer") {
         Ok(t) => t,
         Err(e) => {
             eprintln!("Error cargando template: {}", e);
@@ -425,15 +445,8 @@ pub async fn send_assistance_email(
 
     let email_subject = "New non-attendance have been registered";
 
-    let credentials = Credentials::new(
-        env::var("EMAIL_USERNAME").expect("EMAIL_USERNAME must be set"),
-        env::var("EMAIL_PASSWORD").expect("EMAIL_PASSWORD must be set"),
-    );
-
-    let mailer = SmtpTransport::relay("smtp.gmail.com")
-        .unwrap()
-        .credentials(credentials)
-        .build();
+    let mailer = mailer(&school);
+    let base_context = school_context(&school);
 
     let header = ContentType::TEXT_HTML;
 
@@ -450,6 +463,7 @@ pub async fn send_assistance_email(
         let to_str_clone = to_str.clone();
 
         let template_clone = template_str.clone();
+        let base_context = base_context.clone();
         let footer_template_clone = footer_template_str.clone();
 
         let sender_name = sender_name.clone();
@@ -460,7 +474,7 @@ pub async fn send_assistance_email(
         task::spawn_blocking(move || {
             match to_str_clone.parse::<Mailbox>() {
                 Ok(to) => {
-                    let mut context = Context::new();
+                    let mut context = base_context;
                     context.insert("sender_name", &ammonia::clean(&sender_name));
                     context.insert("student_name", &ammonia::clean(&student_name));
                     context.insert("presence", &ammonia::clean(&presence));
@@ -508,20 +522,17 @@ pub async fn send_disciplinary_sanction_email(
     description: &str,
     date: &str,
 ) {
-    let from_str = env::var("EMAIL_FROM").expect("EMAIL_FROM must be set");
-    let from: Mailbox = from_str.parse().expect("Invalid EMAIL_FROM format");
-    let base_dir = env::var("BASE_PATH").expect("BASE_PATH must be set");
+    let school = schools::current();
+    let from = email_from(&school);
 
-    let template_path = format!("{}/email_templates/disciplinary_sanction_upload.html", base_dir);
-    let template_str = match read_to_string(&template_path) {
+    let template_str = match read_template(&school, "disciplinary_sanction_upload") {
         Ok(t) => t,
         Err(e) => {
             eprintln!("Error cargando template: {}", e);
             return;
         }
     };
-    let footer_template_path = format!("{}/email_templates/footer.html", base_dir);
-    let footer_template_str = match read_to_string(&footer_template_path) {
+    let footer_template_str = match read_template(&school, "footer") {
         Ok(t) => t,
         Err(e) => {
             eprintln!("Error cargando template: {}", e);
@@ -531,15 +542,8 @@ pub async fn send_disciplinary_sanction_email(
 
     let email_subject = "disciplinary_sanction_upload";
 
-    let credentials = Credentials::new(
-        env::var("EMAIL_USERNAME").expect("EMAIL_USERNAME must be set"),
-        env::var("EMAIL_PASSWORD").expect("EMAIL_PASSWORD must be set"),
-    );
-
-    let mailer = SmtpTransport::relay("smtp.gmail.com")
-        .unwrap()
-        .credentials(credentials)
-        .build();
+    let mailer = mailer(&school);
+    let base_context = school_context(&school);
 
     let header = ContentType::TEXT_HTML;
 
@@ -558,6 +562,7 @@ pub async fn send_disciplinary_sanction_email(
         let to_str_clone = to_str.clone();
 
         let template_clone = template_str.clone();
+        let base_context = base_context.clone();
         let footer_template_clone = footer_template_str.clone();
 
         let sender_name = sender_name.clone();
@@ -570,7 +575,7 @@ pub async fn send_disciplinary_sanction_email(
         task::spawn_blocking(move || {
             match to_str_clone.parse::<Mailbox>() {
                 Ok(to) => {
-                    let mut context = Context::new();
+                    let mut context = base_context;
                     context.insert("sender_name", &ammonia::clean(&sender_name));
                     context.insert("student_name", &ammonia::clean(&student_name));
                     context.insert("sanction_type", &ammonia::clean(&sanction_type));
@@ -611,3 +616,146 @@ pub async fn send_disciplinary_sanction_email(
     futures::future::join_all(tasks).await;
 }
 
+
+/// Renders `email_templates/{template}.html` together with the shared footer and
+/// sends it to every recipient. Values in `context` must already be sanitized.
+async fn send_template_email(
+    reply_to: Vec<String>,
+    email_subject: &str,
+    template: &str,
+    context: Context,
+) {
+    let school = schools::current();
+    let from = email_from(&school);
+
+    let template_str = match read_template(&school, template) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("Error cargando template: {}", e);
+            return;
+        }
+    };
+    let footer_template_str = match read_template(&school, "footer") {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("Error cargando template: {}", e);
+            return;
+        }
+    };
+
+    let mut tera = Tera::default();
+    if let Err(e) = tera.add_raw_template(template, &template_str) {
+        eprintln!("Template inválido: {}", e);
+        return;
+    }
+    if let Err(e) = tera.add_raw_template("footer", &footer_template_str) {
+        eprintln!("Template inválido: {}", e);
+        return;
+    }
+    let mut full_context = school_context(&school);
+    full_context.extend(context);
+    let body = match tera.render(template, &full_context) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Error renderizando template: {}", e);
+            return;
+        }
+    };
+
+    let mailer = mailer(&school);
+
+    let tasks = reply_to.into_iter().map(|to_str| {
+        let from = from.clone();
+        let mailer = mailer.clone();
+        let subject = email_subject.to_string();
+        let body = body.clone();
+
+        task::spawn_blocking(move || {
+            match to_str.parse::<Mailbox>() {
+                Ok(to) => {
+                    let email = Message::builder()
+                        .from(from)
+                        .to(to)
+                        .subject(subject)
+                        .header(ContentType::TEXT_HTML)
+                        .body(body)
+                        .unwrap();
+
+                    match mailer.send(&email) {
+                        Ok(_) => println!("✅ Email enviado a {}", to_str),
+                        Err(e) => eprintln!("❌ Error al enviar a {}: {:?}", to_str, e),
+                    }
+                }
+                Err(e) => eprintln!("❌ Dirección inválida '{}': {}", to_str, e),
+            }
+        })
+    });
+
+    futures::future::join_all(tasks).await;
+}
+
+pub async fn send_password_reset_email(
+    to: String,
+    user_name: &str,
+    reset_link: &str,
+    expires_minutes: i64,
+) {
+    let mut context = Context::new();
+    context.insert("user_name", &ammonia::clean(user_name));
+    context.insert("reset_link", &ammonia::clean(reset_link));
+    context.insert("expires_minutes", &expires_minutes);
+
+    send_template_email(vec![to], "Restablecer contraseña", "password_reset", context).await;
+}
+
+pub async fn send_invitation_email(
+    to: String,
+    invitation_link: &str,
+    expires_hours: i64,
+) {
+    let mut context = Context::new();
+    context.insert("invitation_link", &ammonia::clean(invitation_link));
+    context.insert("expires_hours", &expires_hours);
+
+    send_template_email(vec![to], "Te invitaron a GoSchool", "invitation", context).await;
+}
+
+pub async fn send_email_change_email(
+    to: String,
+    user_name: &str,
+    confirm_link: &str,
+    expires_hours: i64,
+) {
+    let mut context = Context::new();
+    context.insert("user_name", &ammonia::clean(user_name));
+    context.insert("confirm_link", &ammonia::clean(confirm_link));
+    context.insert("expires_hours", &expires_hours);
+
+    send_template_email(vec![to], "Confirmá tu nuevo correo", "email_change", context).await;
+}
+
+pub async fn send_email_changed_email(
+    to: String,
+    user_name: &str,
+    new_email: &str,
+) {
+    let mut context = Context::new();
+    context.insert("user_name", &ammonia::clean(user_name));
+    context.insert("new_email", &ammonia::clean(new_email));
+
+    send_template_email(vec![to], "Tu correo fue actualizado", "email_changed", context).await;
+}
+
+pub async fn send_account_locked_email(
+    to: String,
+    user_name: &str,
+    ip_address: &str,
+    lockout_minutes: i64,
+) {
+    let mut context = Context::new();
+    context.insert("user_name", &ammonia::clean(user_name));
+    context.insert("ip_address", &ammonia::clean(ip_address));
+    context.insert("lockout_minutes", &lockout_minutes);
+
+    send_template_email(vec![to], "Cuenta bloqueada temporalmente", "account_locked", context).await;
+}
@@ -13,6 +13,34 @@ pub struct GradeFilter {
     pub subject_id: Option<u64>,
     pub student_id: Option<u64>,
     pub description: Option<String>,
+    pub period_id: Option<u64>,
+    /// Adds the averages of the students and subjects listed.
+    pub with_averages: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GradeAverageFilter {
+    pub subject_id: Option<u64>,
+    pub student_id: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GradeStatisticsFilter {
+    pub assessment_id: Option<u64>,
+    pub subject_id: Option<u64>,
+    pub period_id: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SubjectStatisticsFilter {
+    pub course_id: u64,
+    pub period_id: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AssessmentWeightFilter {
+    /// Weights of the subject and the school defaults.
+    pub subject_id: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -78,9 +106,26 @@ pub struct AssistanceFilter {
     pub date: Option<NaiveDate>,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FamilyFilter {
+    pub student_id: Option<u64>,
+    pub guardian_id: Option<u64>,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct DisciplinarySanctionFilter {
     pub disciplinary_sanction_id: Option<u64>,
     pub student_id: Option<u64>,
     pub sanction_type: Option<String>,
 }
+
+#[derive(Serialize, Deserialize)]
+pub struct AuditLogFilter {
+    pub actor_id: Option<u64>,
+    pub entity: Option<String>,
+    pub entity_id: Option<u64>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
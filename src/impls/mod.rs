@@ -4,3 +4,164 @@ mod user_post;
 mod user_update;
 mod user_delete;
 mod chat_authorization;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use sqlx::{MySqlExecutor, MySqlPool};
+
+use crate::structs::{GradeType, Role};
+
+/// Whether the user holds the role, to validate users assigned to a role
+/// specific place (preceptor of a course, teacher of a subject...).
+pub(crate) async fn holds_role(pool: &MySqlPool, user_id: u64, role: Role) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM roles WHERE user_id = ? AND role = ?)")
+        .bind(user_id)
+        .bind(role)
+        .fetch_one(pool)
+        .await
+}
+
+/// Why a grading period cannot span `start_date..=end_date`, ignoring the
+/// period being updated.
+pub(crate) async fn invalid_period_dates(
+    pool: &MySqlPool,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    period_id: Option<u64>,
+) -> Result<Option<&'static str>, sqlx::Error> {
+    if start_date > end_date {
+        return Ok(Some("start_date must not be after end_date"));
+    }
+    let overlaps: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM grading_periods WHERE id <> ? AND start_date <= ? AND end_date >= ?)"
+    )
+    .bind(period_id.unwrap_or(0))
+    .bind(end_date)
+    .bind(start_date)
+    .fetch_one(pool)
+    .await?;
+    Ok(overlaps.then_some("Overlaps another grading period"))
+}
+
+/// Assigns the grades without a period that fall within it, like the ones
+/// posted before the period was defined.
+pub(crate) async fn assign_grades_to_period<'e, E: MySqlExecutor<'e>>(executor: E, period_id: u64) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE grades g JOIN grading_periods p ON p.id = ? \
+         SET g.period_id = p.id \
+         WHERE g.period_id IS NULL AND DATE(g.created_at) BETWEEN p.start_date AND p.end_date"
+    )
+    .bind(period_id)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Assigns every grade without a period to the period its date falls in,
+/// like the ones a period left out when its dates changed.
+pub(crate) async fn assign_grades_to_periods<'e, E: MySqlExecutor<'e>>(executor: E) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE grades g JOIN grading_periods p ON DATE(g.created_at) BETWEEN p.start_date AND p.end_date \
+         SET g.period_id = p.id \
+         WHERE g.period_id IS NULL"
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Why a conceptual grade cannot have `label` and `value`, ignoring the
+/// label being updated.
+pub(crate) async fn invalid_grade_label(
+    pool: &MySqlPool,
+    label: &str,
+    value: Decimal,
+    label_id: Option<u64>,
+) -> Result<Option<String>, sqlx::Error> {
+    if label.is_empty() || label.chars().count() > 50 {
+        return Ok(Some("label must have 1 to 50 characters".to_string()));
+    }
+    if value.normalize().scale() > 2 {
+        return Ok(Some("Grades have at most 2 decimals".to_string()));
+    }
+    let (min_value, max_value): (Decimal, Decimal) =
+        sqlx::query_as("SELECT min_value, max_value FROM grading_scales WHERE grade_type = 'numerical'")
+            .fetch_one(pool)
+            .await?;
+    if value < min_value || value > max_value {
+        return Ok(Some(format!(
+            "value must be a numerical grade, from {} to {}",
+            min_value.normalize(),
+            max_value.normalize()
+        )));
+    }
+    let taken: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM grading_scale_labels WHERE id <> ? AND (label = ? OR value = ?))"
+    )
+    .bind(label_id.unwrap_or(0))
+    .bind(label)
+    .bind(value)
+    .fetch_one(pool)
+    .await?;
+    Ok(taken.then(|| "Another label has this label or value".to_string()))
+}
+
+/// The value stored for a grade and its label, or why it is not valid. A
+/// conceptual grade is given by its label, or by the value of one, and
+/// stores that value; the others must be within their grading scale.
+pub(crate) async fn grade_value(
+    pool: &MySqlPool,
+    grade_type: GradeType,
+    grade: Option<Decimal>,
+    label: Option<&str>,
+) -> Result<Result<(Decimal, Option<String>), String>, sqlx::Error> {
+    if grade_type == GradeType::Conceptual {
+        let found: Option<(String, Decimal)> = match (label, grade) {
+            (Some(label), _) => {
+                sqlx::query_as("SELECT label, value FROM grading_scale_labels WHERE label = ?")
+                    .bind(label.trim())
+                    .fetch_optional(pool)
+                    .await?
+            }
+            (None, Some(grade)) => {
+                sqlx::query_as("SELECT label, value FROM grading_scale_labels WHERE value = ?")
+                    .bind(grade)
+                    .fetch_optional(pool)
+                    .await?
+            }
+            (None, None) => return Ok(Err("A conceptual grade needs a label".to_string())),
+        };
+        return Ok(match found {
+            Some((_, value)) if grade.is_some_and(|g| g != value) => {
+                Err("grade is not the value of label".to_string())
+            }
+            Some((label, value)) => Ok((value, Some(label))),
+            None => Err("Unknown conceptual grade".to_string()),
+        });
+    }
+
+    if label.is_some() {
+        return Ok(Err("Only conceptual grades have a label".to_string()));
+    }
+    let Some(grade) = grade else {
+        return Ok(Err("grade is required".to_string()));
+    };
+    if grade.normalize().scale() > 2 {
+        return Ok(Err("Grades have at most 2 decimals".to_string()));
+    }
+    let (min_value, max_value): (Decimal, Decimal) =
+        sqlx::query_as("SELECT min_value, max_value FROM grading_scales WHERE grade_type = ?")
+            .bind(grade_type)
+            .fetch_one(pool)
+            .await?;
+    if grade < min_value || grade > max_value {
+        let kind = if grade_type == GradeType::Percentage { "Percentage" } else { "Numerical" };
+        return Ok(Err(format!(
+            "{} grades go from {} to {}",
+            kind,
+            min_value.normalize(),
+            max_value.normalize()
+        )));
+    }
+    Ok(Ok((grade, None)))
+}
@@ -1,5 +1,7 @@
 use actix_web::HttpResponse;
 use sqlx::MySqlPool;
+use crate::audit;
+use crate::parse_multipart::stored_path;
 use crate::structs::*;
 use crate::traits::{Delete, Get};
 
@@ -25,10 +27,17 @@ impl Delete for MySelf {
             return HttpResponse::Unauthorized().finish();
         }
         
-        let res = sqlx::query("DELETE FROM assessments WHERE id = ?")
-            .bind(assessment_id)
-            .execute(pool)
-            .await;
+        // its grades go with it, so they are audited first
+        let res: Result<(), sqlx::Error> = async {
+            let mut tx = pool.begin().await?;
+            audit::record_grade_cascade(&mut tx, self, "assessment_id", assessment_id).await?;
+            sqlx::query("DELETE FROM assessments WHERE id = ?")
+                .bind(assessment_id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await
+        }
+        .await;
         match res {
             Ok(_) => HttpResponse::Ok().finish(),
             Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
@@ -56,10 +65,17 @@ impl Delete for MySelf {
             return HttpResponse::Unauthorized().finish();
         }
 
-        let res = sqlx::query("DELETE FROM grades WHERE id = ?")
-            .bind(grade_id)
-            .execute(pool)
-            .await;
+        let res: Result<(), sqlx::Error> = async {
+            let mut tx = pool.begin().await?;
+            let before = audit::snapshot::<Grade>(&mut tx, audit::GRADES, grade_id).await?;
+            sqlx::query("DELETE FROM grades WHERE id = ?")
+                .bind(grade_id)
+                .execute(&mut *tx)
+                .await?;
+            audit::record(&mut tx, self, audit::GRADES, grade_id, AuditAction::Delete, before, None).await?;
+            tx.commit().await
+        }
+        .await;
         match res {
             Ok(_) => HttpResponse::Ok().finish(),
             Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
@@ -161,7 +177,9 @@ impl Delete for MySelf {
                     Ok(file) => file,
                     Err(e) => return HttpResponse::InternalServerError().json(e.to_string())
             };
-            std::fs::remove_file(res).unwrap();
+            if let Some(path) = stored_path(&res) {
+                let _ = std::fs::remove_file(path);
+            }
         }
                     
 
@@ -210,7 +228,9 @@ impl Delete for MySelf {
                     Ok(file) => file,
                     Err(e) => return HttpResponse::InternalServerError().json(e.to_string())
             };
-            std::fs::remove_file(res).unwrap();
+            if let Some(path) = stored_path(&res) {
+                let _ = std::fs::remove_file(path);
+            }
         }
 
         let res = sqlx::query("DELETE FROM homework_submissions WHERE id = ?")
@@ -253,12 +273,19 @@ impl Delete for MySelf {
                 return HttpResponse::Unauthorized().finish();
             }
         }
-        let result = sqlx::query("DELETE FROM assistance WHERE id = ?")
-            .bind(assistance_id)
-            .execute(pool)
-            .await;
+        let result: Result<(), sqlx::Error> = async {
+            let mut tx = pool.begin().await?;
+            let before = audit::snapshot::<Assistance>(&mut tx, audit::ASSISTANCE, assistance_id).await?;
+            sqlx::query("DELETE FROM assistance WHERE id = ?")
+                .bind(assistance_id)
+                .execute(&mut *tx)
+                .await?;
+            audit::record(&mut tx, self, audit::ASSISTANCE, assistance_id, AuditAction::Delete, before, None).await?;
+            tx.commit().await
+        }
+        .await;
         match result {
-            Ok(_) => return HttpResponse::Ok().finish(),
+            Ok(_) => HttpResponse::Ok().finish(),
             Err(e) => return HttpResponse::InternalServerError().json(e.to_string())
         }
     }
@@ -293,12 +320,19 @@ impl Delete for MySelf {
                 return HttpResponse::Unauthorized().finish();
             }
         }
-        let result = sqlx::query("DELETE FROM disciplinary_sanctions WHERE id = ?")
-            .bind(disciplinary_sanction_id)
-            .execute(pool)
-            .await;
+        let result: Result<(), sqlx::Error> = async {
+            let mut tx = pool.begin().await?;
+            let before = audit::snapshot::<DisciplinarySanction>(&mut tx, audit::DISCIPLINARY_SANCTIONS, disciplinary_sanction_id).await?;
+            sqlx::query("DELETE FROM disciplinary_sanctions WHERE id = ?")
+                .bind(disciplinary_sanction_id)
+                .execute(&mut *tx)
+                .await?;
+            audit::record(&mut tx, self, audit::DISCIPLINARY_SANCTIONS, disciplinary_sanction_id, AuditAction::Delete, before, None).await?;
+            tx.commit().await
+        }
+        .await;
         match result {
-            Ok(_) => return HttpResponse::Ok().finish(),
+            Ok(_) => HttpResponse::Ok().finish(),
             Err(e) => return HttpResponse::InternalServerError().json(e.to_string())
         }
 
@@ -324,4 +358,226 @@ impl Delete for MySelf {
             Err(e) => HttpResponse::InternalServerError().json(e.to_string())
         }
     }
+
+    async fn delete_family(
+        &self,
+        pool: &MySqlPool,
+        family_id: u64
+    ) -> HttpResponse {
+        match self.role {
+            Role::admin => {}
+            Role::preceptor => {
+                let has_access: bool = match sqlx::query_scalar(
+                    "SELECT EXISTS(SELECT 1 FROM families f JOIN users u ON f.student_id = u.id JOIN courses c ON u.course_id = c.id WHERE f.id = ? AND c.preceptor_id = ?)"
+                )
+                .bind(family_id)
+                .bind(self.id)
+                .fetch_one(pool)
+                .await
+                {
+                    Ok(v) => v,
+                    Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+                };
+                if !has_access {
+                    return HttpResponse::Unauthorized().finish();
+                }
+            }
+            _ => return HttpResponse::Unauthorized().finish(),
+        }
+
+        let result = sqlx::query("DELETE FROM families WHERE id = ?")
+            .bind(family_id)
+            .execute(pool)
+            .await;
+
+        match result {
+            Ok(_) => HttpResponse::Ok().finish(),
+            Err(e) => HttpResponse::InternalServerError().json(e.to_string())
+        }
+    }
+
+    async fn delete_course(
+        &self,
+        pool: &MySqlPool,
+        course_id: u64
+    ) -> HttpResponse {
+        if self.role != Role::admin {
+            return HttpResponse::Unauthorized().finish();
+        }
+
+        let mut tx = match pool.begin().await {
+            Ok(tx) => tx,
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        };
+        // locking the course holds back students and subjects being added
+        // to it, their foreign key check waits for the delete
+        match sqlx::query_scalar::<_, u64>("SELECT id FROM courses WHERE id = ? FOR UPDATE")
+            .bind(course_id)
+            .fetch_optional(&mut *tx)
+            .await
+        {
+            Ok(Some(_)) => {}
+            Ok(None) => return HttpResponse::NotFound().json("Course not found"),
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        }
+
+        // deleting would cascade into subjects, grades and timetables
+        let in_use: bool = match sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM users WHERE course_id = ?) OR EXISTS(SELECT 1 FROM subjects WHERE course_id = ?)"
+        )
+        .bind(course_id)
+        .bind(course_id)
+        .fetch_one(&mut *tx)
+        .await
+        {
+            Ok(v) => v,
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        };
+        if in_use {
+            return HttpResponse::Conflict().json("Course still has students or subjects");
+        }
+
+        let result: Result<(), sqlx::Error> = async {
+            sqlx::query("DELETE FROM courses WHERE id = ?")
+                .bind(course_id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await
+        }
+        .await;
+
+        match result {
+            Ok(_) => HttpResponse::Ok().finish(),
+            Err(e) => HttpResponse::InternalServerError().json(e.to_string())
+        }
+    }
+
+    async fn delete_subject(
+        &self,
+        pool: &MySqlPool,
+        subject_id: u64
+    ) -> HttpResponse {
+        if self.role != Role::admin {
+            return HttpResponse::Unauthorized().finish();
+        }
+
+        let mut tx = match pool.begin().await {
+            Ok(tx) => tx,
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        };
+        // locking the subject holds back grades and assessments being added
+        // to it, their foreign key check waits for the delete
+        match sqlx::query_scalar::<_, u64>("SELECT id FROM subjects WHERE id = ? FOR UPDATE")
+            .bind(subject_id)
+            .fetch_optional(&mut *tx)
+            .await
+        {
+            Ok(Some(_)) => {}
+            Ok(None) => return HttpResponse::NotFound().json("Subject not found"),
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        }
+
+        // deleting would cascade into the grades and assessments of the subject
+        let in_use: bool = match sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM grades WHERE subject_id = ?) OR EXISTS(SELECT 1 FROM assessments WHERE subject_id = ?)"
+        )
+        .bind(subject_id)
+        .bind(subject_id)
+        .fetch_one(&mut *tx)
+        .await
+        {
+            Ok(v) => v,
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        };
+        if in_use {
+            return HttpResponse::Conflict().json("Subject still has grades or assessments");
+        }
+
+        let result: Result<(), sqlx::Error> = async {
+            sqlx::query("DELETE FROM assessment_type_weights WHERE subject_id = ?")
+                .bind(subject_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DELETE FROM subjects WHERE id = ?")
+                .bind(subject_id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await
+        }
+        .await;
+
+        match result {
+            Ok(_) => HttpResponse::Ok().finish(),
+            Err(e) => HttpResponse::InternalServerError().json(e.to_string())
+        }
+    }
+
+    async fn delete_grading_period(
+        &self,
+        pool: &MySqlPool,
+        period_id: u64
+    ) -> HttpResponse {
+        if self.role != Role::admin {
+            return HttpResponse::Unauthorized().finish();
+        }
+
+        // its grades are kept without a period, the change audited for each
+        let result: Result<sqlx::mysql::MySqlQueryResult, sqlx::Error> = async {
+            let mut tx = pool.begin().await?;
+            let grade_ids: Vec<u64> = sqlx::query_scalar("SELECT id FROM grades WHERE period_id = ? FOR UPDATE")
+                .bind(period_id)
+                .fetch_all(&mut *tx)
+                .await?;
+            let before = audit::snapshot_grades(&mut tx, &grade_ids).await?;
+            let res = sqlx::query("DELETE FROM grading_periods WHERE id = ?")
+                .bind(period_id)
+                .execute(&mut *tx)
+                .await?;
+            audit::record_grade_updates(&mut tx, self, &grade_ids, before).await?;
+            tx.commit().await?;
+            Ok(res)
+        }
+        .await;
+
+        match result {
+            Ok(r) if r.rows_affected() == 0 => HttpResponse::NotFound().json("Grading period not found"),
+            Ok(_) => HttpResponse::Ok().finish(),
+            Err(e) => HttpResponse::InternalServerError().json(e.to_string())
+        }
+    }
+
+    async fn delete_grade_label(
+        &self,
+        pool: &MySqlPool,
+        label_id: u64
+    ) -> HttpResponse {
+        if self.role != Role::admin {
+            return HttpResponse::Unauthorized().finish();
+        }
+        let in_use: bool = match sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM grades g JOIN grading_scale_labels l ON l.value = g.grade \
+             WHERE g.grade_type = 'conceptual' AND l.id = ?)"
+        )
+        .bind(label_id)
+        .fetch_one(pool)
+        .await
+        {
+            Ok(u) => u,
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        };
+        if in_use {
+            return HttpResponse::Conflict().json("Conceptual grades have this label");
+        }
+
+        let result = sqlx::query("DELETE FROM grading_scale_labels WHERE id = ?")
+            .bind(label_id)
+            .execute(pool)
+            .await;
+
+        match result {
+            Ok(r) if r.rows_affected() == 0 => HttpResponse::NotFound().json("Label not found"),
+            Ok(_) => HttpResponse::Ok().finish(),
+            Err(e) => HttpResponse::InternalServerError().json(e.to_string())
+        }
+    }
 }
@@ -1,10 +1,12 @@
 use actix_web::web;
 use chrono::Utc;
 use sqlx::{MySql, MySqlPool, QueryBuilder};
-use std::env;
 use rand::seq::SliceRandom;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
 
 use crate::filters::*;
+use crate::schools;
 use crate::structs::*;
 use crate::traits::Get;
 
@@ -112,42 +114,325 @@ impl Get for MySelf {
         pool: &MySqlPool,
         filter: GradeFilter,
     ) -> Result<Vec<Grade>, sqlx::Error> {
-        let mut query = QueryBuilder::new("SELECT * FROM grades g ");
-        match self.role {
-            Role::student => {
-                query.push("WHERE g.student_id =");
-                query.push_bind(self.id);
-            }
-            Role::teacher => {
-                query.push("JOIN subjects s ON g.subject_id = s.id WHERE s.teacher_id =");
-                query.push_bind(self.id);
-            }
-            Role::admin => {
-                query.push("WHERE 1=1");
-            }
-            Role::father => {
-                query.push("JOIN families f ON g.student_id = f.student_id WHERE f.father_id =");
-                query.push_bind(self.id);
-            }
-            Role::preceptor => {
-                query.push("JOIN subjects s ON g.subject_id = s.id JOIN courses c ON s.course_id = c.id WHERE c.preceptor_id =");
-                query.push_bind(self.id);
-            }
-        };
+        let mut query = QueryBuilder::new("SELECT g.* FROM grades g ");
+        push_grade_scope(self, &mut query);
         if let Some(c) = filter.student_id {
-            query.push(" AND student_id = ");
+            query.push(" AND g.student_id = ");
             query.push_bind(c);
         }
         if let Some(s) = filter.subject_id {
-            query.push(" AND subject_id = ");
+            query.push(" AND g.subject_id = ");
             query.push_bind(s);
         }
         if let Some(d) = filter.description {
-            query.push(" AND description = ");
+            query.push(" AND g.description = ");
             query.push_bind(d);
         }
-        let res = query.build_query_as().fetch_all(pool).await;
-        res
+        if let Some(p) = filter.period_id {
+            query.push(" AND g.period_id = ");
+            query.push_bind(p);
+        }
+        let mut grades: Vec<Grade> = query.build_query_as().fetch_all(pool).await?;
+
+        if grades.iter().any(|g| g.grade_type == Some(GradeType::Conceptual)) {
+            let labels = self.get_grading_scales(pool).await?.labels;
+            for g in grades.iter_mut().filter(|g| g.grade_type == Some(GradeType::Conceptual)) {
+                g.label = labels.iter().find(|l| l.value == g.grade).map(|l| l.label.clone());
+            }
+        }
+        Ok(grades)
+    }
+    async fn get_grading_scales(
+        &self,
+        pool: &MySqlPool,
+    ) -> Result<GradingScales, sqlx::Error> {
+        let scales = sqlx::query_as("SELECT * FROM grading_scales ORDER BY grade_type")
+            .fetch_all(pool)
+            .await?;
+        let labels = sqlx::query_as("SELECT * FROM grading_scale_labels ORDER BY value DESC")
+            .fetch_all(pool)
+            .await?;
+        Ok(GradingScales { scales, labels })
+    }
+    async fn get_grading_periods(
+        &self,
+        pool: &MySqlPool,
+    ) -> Result<Vec<GradingPeriod>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM grading_periods ORDER BY start_date")
+            .fetch_all(pool)
+            .await
+    }
+    async fn get_grade_averages(
+        &self,
+        pool: &MySqlPool,
+        filter: GradeAverageFilter,
+    ) -> Result<Vec<SubjectAverage>, sqlx::Error> {
+        // a grade weighs what its assessment type weighs in the subject, else
+        // in the school, else 1, grades without an assessment too
+        let mut query = QueryBuilder::new(format!(
+            "SELECT g.student_id, g.subject_id, p.id, \
+             ROUND(SUM({NUMERIC_GRADE} * COALESCE(ws.weight, wd.weight, 1)) / SUM(COALESCE(ws.weight, wd.weight, 1)), 2), COUNT(*) \
+             FROM grades g JOIN grading_periods p ON p.id = g.period_id {GRADE_SCALES}\
+             LEFT JOIN assessments a ON a.id = g.assessment_id \
+             LEFT JOIN assessment_type_weights ws ON ws.subject_id = g.subject_id AND ws.assessment_type = a.type \
+             LEFT JOIN assessment_type_weights wd ON wd.subject_id IS NULL AND wd.assessment_type = a.type ",
+        ));
+        push_grade_scope(self, &mut query);
+        if let Some(student_id) = filter.student_id {
+            query.push(" AND g.student_id = ");
+            query.push_bind(student_id);
+        }
+        if let Some(subject_id) = filter.subject_id {
+            query.push(" AND g.subject_id = ");
+            query.push_bind(subject_id);
+        }
+        // periods whose grades all weigh 0 have no average
+        query.push(
+            " GROUP BY g.student_id, g.subject_id, p.id, p.start_date \
+             HAVING SUM(COALESCE(ws.weight, wd.weight, 1)) > 0 \
+             ORDER BY g.student_id, g.subject_id, p.start_date",
+        );
+
+        let rows: Vec<(u64, u64, u64, Decimal, i64)> = query.build_query_as().fetch_all(pool).await?;
+
+        let mut averages: Vec<SubjectAverage> = Vec::new();
+        for (student_id, subject_id, period_id, average, grade_count) in rows {
+            let period = PeriodAverage { period_id, average, grade_count };
+            match averages.last_mut() {
+                Some(a) if a.student_id == student_id && a.subject_id == subject_id => a.periods.push(period),
+                _ => averages.push(SubjectAverage {
+                    student_id,
+                    subject_id,
+                    periods: vec![period],
+                    final_grade: None,
+                }),
+            }
+        }
+        for a in &mut averages {
+            let sum: Decimal = a.periods.iter().map(|p| p.average).sum();
+            a.final_grade = Some((sum / Decimal::from(a.periods.len())).round_dp(2));
+        }
+        Ok(averages)
+    }
+    async fn get_assessment_weights(
+        &self,
+        pool: &MySqlPool,
+        filter: AssessmentWeightFilter,
+    ) -> Result<Vec<AssessmentWeight>, sqlx::Error> {
+        let mut query = QueryBuilder::new(
+            "SELECT subject_id, assessment_type, weight FROM assessment_type_weights WHERE 1=1",
+        );
+        if let Some(subject_id) = filter.subject_id {
+            query.push(" AND (subject_id IS NULL OR subject_id = ");
+            query.push_bind(subject_id);
+            query.push(")");
+        }
+        query.push(" ORDER BY subject_id, assessment_type");
+        query.build_query_as().fetch_all(pool).await
+    }
+    async fn get_grade_statistics(
+        &self,
+        pool: &MySqlPool,
+        filter: GradeStatisticsFilter,
+    ) -> Result<Option<GradeStatistics>, sqlx::Error> {
+        let mut query = QueryBuilder::new("");
+        push_statistics_values(self, &mut query);
+        push_statistics_filter(&mut query, &filter);
+        query.push(
+            ", r AS (SELECT value, ROW_NUMBER() OVER (ORDER BY value) AS n, COUNT(*) OVER () AS total FROM v) \
+             SELECT COUNT(*), ROUND(AVG(value), 2), \
+             (SELECT ROUND(AVG(value), 2) FROM r WHERE n IN (FLOOR((total + 1) / 2), CEIL((total + 1) / 2))), \
+             ROUND(MIN(value), 2), ROUND(MAX(value), 2), ROUND(100 * AVG(passed), 2) FROM v",
+        );
+        let (count, mean, median, min, max, pass_rate): StatisticsRow = query.build_query_as().fetch_one(pool).await?;
+        if matches!(self.role, Role::student | Role::father) && count < MIN_ANONYMOUS_GRADES {
+            return Ok(None);
+        }
+
+        // bins of 1 on the usual scales, 10 bins on wider ones
+        let (min_value, max_value): (Decimal, Decimal) =
+            sqlx::query_as("SELECT min_value, max_value FROM grading_scales WHERE grade_type = 'numerical'")
+                .fetch_one(pool)
+                .await?;
+        let range = max_value - min_value;
+        let width = if range <= Decimal::from(20) { Decimal::ONE } else { range / Decimal::from(10) };
+        let bins = (range / width).ceil().max(Decimal::ONE);
+
+        let mut histogram_query = QueryBuilder::new("");
+        push_statistics_values(self, &mut histogram_query);
+        push_statistics_filter(&mut histogram_query, &filter);
+        histogram_query.push("SELECT CAST(LEAST(GREATEST(FLOOR((value - ");
+        histogram_query.push_bind(min_value);
+        histogram_query.push(") / ");
+        histogram_query.push_bind(width);
+        histogram_query.push("), 0), ");
+        histogram_query.push_bind(bins - Decimal::ONE);
+        histogram_query.push(") AS SIGNED) AS bin, COUNT(*) FROM v GROUP BY bin");
+        let counts: Vec<(i64, i64)> = histogram_query.build_query_as().fetch_all(pool).await?;
+
+        let histogram = (0..bins.to_i64().unwrap_or(1))
+            .map(|bin| {
+                let from = min_value + width * Decimal::from(bin);
+                HistogramBin {
+                    from,
+                    to: (from + width).min(max_value),
+                    count: counts.iter().find(|(b, _)| *b == bin).map_or(0, |(_, c)| *c),
+                }
+            })
+            .collect();
+
+        Ok(Some(GradeStatistics { count, mean, median, min, max, pass_rate, histogram }))
+    }
+    async fn get_subject_statistics(
+        &self,
+        pool: &MySqlPool,
+        filter: SubjectStatisticsFilter,
+    ) -> Result<Vec<SubjectStatistics>, sqlx::Error> {
+        let mut query = QueryBuilder::new("");
+        push_statistics_values(self, &mut query);
+        query.push(" AND g.subject_id IN (SELECT id FROM subjects WHERE course_id = ");
+        query.push_bind(filter.course_id);
+        query.push(")");
+        if let Some(period_id) = filter.period_id {
+            query.push(" AND g.period_id = ");
+            query.push_bind(period_id);
+        }
+        query.push(
+            "), r AS (SELECT subject_id, value, ROW_NUMBER() OVER (PARTITION BY subject_id ORDER BY value) AS n, \
+             COUNT(*) OVER (PARTITION BY subject_id) AS total FROM v), \
+             m AS (SELECT subject_id, ROUND(AVG(value), 2) AS median FROM r \
+             WHERE n IN (FLOOR((total + 1) / 2), CEIL((total + 1) / 2)) GROUP BY subject_id) \
+             SELECT v.subject_id, sj.name, COUNT(*) AS count, ROUND(AVG(v.value), 2) AS mean, m.median, \
+             ROUND(MIN(v.value), 2) AS min, ROUND(MAX(v.value), 2) AS max, ROUND(100 * AVG(v.passed), 2) AS pass_rate \
+             FROM v JOIN subjects sj ON sj.id = v.subject_id JOIN m ON m.subject_id = v.subject_id \
+             GROUP BY v.subject_id, sj.name, m.median",
+        );
+        if matches!(self.role, Role::student | Role::father) {
+            query.push(" HAVING COUNT(*) >= ");
+            query.push_bind(MIN_ANONYMOUS_GRADES);
+        }
+        query.push(" ORDER BY sj.name");
+        query.build_query_as().fetch_all(pool).await
+    }
+    async fn get_report_card(
+        &self,
+        pool: &MySqlPool,
+        student_id: u64,
+        period_id: u64,
+    ) -> Result<Option<ReportCard>, sqlx::Error> {
+        let allowed: bool = match self.role {
+            Role::admin => true,
+            Role::student => self.id == student_id,
+            Role::father => {
+                sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM families WHERE student_id = ? AND father_id = ?)")
+                    .bind(student_id)
+                    .bind(self.id)
+                    .fetch_one(pool)
+                    .await?
+            }
+            Role::preceptor => {
+                sqlx::query_scalar(
+                    "SELECT EXISTS(SELECT 1 FROM users u JOIN courses c ON u.course_id = c.id WHERE u.id = ? AND c.preceptor_id = ?)",
+                )
+                .bind(student_id)
+                .bind(self.id)
+                .fetch_one(pool)
+                .await?
+            }
+            Role::teacher => false,
+        };
+        if !allowed {
+            return Ok(None);
+        }
+
+        let Some((student_name, course_name)): Option<(String, Option<String>)> = sqlx::query_as(
+            "SELECT COALESCE(pd.full_name, u.email), c.name FROM users u \
+             LEFT JOIN personal_data pd ON pd.user_id = u.id \
+             LEFT JOIN courses c ON c.id = u.course_id WHERE u.id = ?",
+        )
+        .bind(student_id)
+        .fetch_optional(pool)
+        .await?
+        else {
+            return Ok(None);
+        };
+        let Some(period): Option<GradingPeriod> = sqlx::query_as("SELECT * FROM grading_periods WHERE id = ?")
+            .bind(period_id)
+            .fetch_optional(pool)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        // the subjects of the course, and any other the student was graded in
+        let subjects: Vec<(u64, String)> = sqlx::query_as(
+            "SELECT s.id, s.name FROM subjects s \
+             WHERE s.course_id = (SELECT course_id FROM users WHERE id = ?) \
+             OR s.id IN (SELECT subject_id FROM grades WHERE student_id = ? AND period_id = ?) \
+             ORDER BY s.name",
+        )
+        .bind(student_id)
+        .bind(student_id)
+        .bind(period_id)
+        .fetch_all(pool)
+        .await?;
+        let grades = self
+            .get_grades(
+                pool,
+                GradeFilter {
+                    subject_id: None,
+                    student_id: Some(student_id),
+                    description: None,
+                    period_id: Some(period_id),
+                    with_averages: None,
+                },
+            )
+            .await?;
+        let averages = self
+            .get_grade_averages(pool, GradeAverageFilter { subject_id: None, student_id: Some(student_id) })
+            .await?;
+        let comments: Vec<(u64, String)> =
+            sqlx::query_as("SELECT subject_id, comment FROM report_card_comments WHERE student_id = ? AND period_id = ?")
+                .bind(student_id)
+                .bind(period_id)
+                .fetch_all(pool)
+                .await?;
+
+        let subjects = subjects
+            .into_iter()
+            .map(|(subject_id, name)| {
+                let average = averages.iter().find(|a| a.subject_id == subject_id);
+                ReportCardSubject {
+                    name,
+                    grades: grades.iter().filter(|g| g.subject_id == subject_id).map(Grade::display).collect(),
+                    period_average: average
+                        .and_then(|a| a.periods.iter().find(|p| p.period_id == period_id))
+                        .map(|p| p.average),
+                    final_grade: average.and_then(|a| a.final_grade),
+                    comment: comments.iter().find(|(s, _)| *s == subject_id).map(|(_, c)| c.clone()),
+                }
+            })
+            .collect();
+
+        let attendance: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT presence, COUNT(*) FROM assistance WHERE student_id = ? AND date BETWEEN ? AND ? \
+             GROUP BY presence ORDER BY presence",
+        )
+        .bind(student_id)
+        .bind(period.start_date)
+        .bind(period.end_date)
+        .fetch_all(pool)
+        .await?;
+        let sanctions: Vec<DisciplinarySanction> = sqlx::query_as(
+            "SELECT * FROM disciplinary_sanctions WHERE student_id = ? AND date BETWEEN ? AND ? ORDER BY date",
+        )
+        .bind(student_id)
+        .bind(period.start_date)
+        .bind(period.end_date)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(Some(ReportCard { student_name, course_name, period, subjects, attendance, sanctions }))
     }
     async fn get_subjects(
         &self,
@@ -413,7 +698,7 @@ impl Get for MySelf {
             {
                 Ok(Some(path)) => path,
                 Ok(None) => {
-                    let base_url = env::var("BASE_URL").expect("BASE_URL must be set");
+                    let base_url = schools::current().base_url();
                     format!("{}uploads/profile_pictures/default.jpg", base_url)
                 }
                 Err(e) => return Err(e),
@@ -906,8 +1191,8 @@ let mut query = QueryBuilder::new(r#"
         ) -> Result<Vec<ChatMessage>, sqlx::Error> {
         
         let mut query = QueryBuilder::new(r#"
-            SELECT * 
-            FROM chat_message cm
+            SELECT cm.*
+            FROM chat_messages cm
             JOIN chats c ON cm.chat_id = c.id
             JOIN chat_participants cp ON c.id = cp.chat_id
             WHERE cp.user_id = ?
@@ -940,4 +1225,180 @@ let mut query = QueryBuilder::new(r#"
 
         Ok(msg)
     }
+
+    async fn get_audit_log(
+        &self,
+        pool: &MySqlPool,
+        filter: AuditLogFilter
+    ) -> Result<Vec<AuditEntry>, sqlx::Error> {
+        let mut query = QueryBuilder::new("SELECT * FROM audit_log al ");
+        match self.role {
+            Role::admin => {
+                query.push("WHERE 1=1");
+            }
+            _ => {
+                query.push("WHERE 1=2"); // only admins read the audit log
+            }
+        }
+        if let Some(actor_id) = filter.actor_id {
+            query.push(" AND al.actor_id = ");
+            query.push_bind(actor_id);
+        }
+        if let Some(entity) = filter.entity {
+            query.push(" AND al.entity = ");
+            query.push_bind(entity);
+        }
+        if let Some(entity_id) = filter.entity_id {
+            query.push(" AND al.entity_id = ");
+            query.push_bind(entity_id);
+        }
+        if let Some(from) = filter.from {
+            query.push(" AND al.created_at >= ");
+            query.push_bind(from);
+        }
+        if let Some(to) = filter.to {
+            query.push(" AND al.created_at < DATE_ADD(");
+            query.push_bind(to);
+            query.push(", INTERVAL 1 DAY)");
+        }
+        query.push(" ORDER BY al.created_at DESC, al.id DESC LIMIT ");
+        query.push_bind(filter.limit.unwrap_or(100).min(1000));
+        query.push(" OFFSET ");
+        query.push_bind(filter.offset.unwrap_or(0));
+
+        query.build_query_as().fetch_all(pool).await
+    }
+    async fn get_families(
+        &self,
+        pool: &MySqlPool,
+        filter: FamilyFilter
+    ) -> Result<Vec<Family>, sqlx::Error> {
+        let mut query: QueryBuilder<MySql> = QueryBuilder::new(
+            "SELECT f.id, f.student_id, f.father_id AS guardian_id, pd.full_name AS guardian_name, \
+             g.email AS guardian_email, f.guardian_type, f.is_primary_contact \
+             FROM families f JOIN users g ON g.id = f.father_id \
+             LEFT JOIN personal_data pd ON pd.user_id = f.father_id ",
+        );
+        match self.role {
+            Role::admin => {
+                query.push("WHERE 1=1");
+            }
+            Role::preceptor => {
+                query.push("JOIN users u ON u.id = f.student_id JOIN courses c ON u.course_id = c.id WHERE c.preceptor_id = ");
+                query.push_bind(self.id);
+            }
+            Role::student => {
+                query.push("WHERE f.student_id = ");
+                query.push_bind(self.id);
+            }
+            Role::father => {
+                query.push("WHERE f.father_id = ");
+                query.push_bind(self.id);
+            }
+            Role::teacher => {
+                query.push("WHERE 1=2");
+            }
+        }
+        if let Some(student_id) = filter.student_id {
+            query.push(" AND f.student_id = ");
+            query.push_bind(student_id);
+        }
+        if let Some(guardian_id) = filter.guardian_id {
+            query.push(" AND f.father_id = ");
+            query.push_bind(guardian_id);
+        }
+        query.push(" ORDER BY f.student_id, f.is_primary_contact DESC, f.id");
+
+        query.build_query_as().fetch_all(pool).await
+    }
+}
+
+/// A grade on the numerical scale: percentages are converted to it and
+/// conceptual grades already store a number of it. Needs `GRADE_SCALES`.
+const NUMERIC_GRADE: &str = "CASE WHEN g.grade_type = 'percentage' \
+     THEN ns.min_value + (g.grade - ps.min_value) * (ns.max_value - ns.min_value) / (ps.max_value - ps.min_value) \
+     ELSE g.grade END";
+
+const GRADE_SCALES: &str = "JOIN grading_scales ns ON ns.grade_type = 'numerical' \
+     JOIN grading_scales ps ON ps.grade_type = 'percentage' ";
+
+/// Students and guardians only see statistics of at least this many grades,
+/// fewer could tell them the grades of classmates.
+const MIN_ANONYMOUS_GRADES: i64 = 5;
+
+type StatisticsRow = (i64, Option<Decimal>, Option<Decimal>, Option<Decimal>, Option<Decimal>, Option<Decimal>);
+
+/// Starts the `v` table of the statistics queries: the `value` of every
+/// grade the user can count, on the numerical scale, and whether it
+/// `passed`. Ends within its WHERE, the caller adds the filters and closes
+/// it. Students and guardians count the grades of the whole course, they
+/// only get aggregates.
+fn push_statistics_values(user: &MySelf, query: &mut QueryBuilder<'_, MySql>) {
+    query.push(format!(
+        "WITH v AS (SELECT g.subject_id, {NUMERIC_GRADE} AS value, \
+         CASE WHEN g.grade_type = 'percentage' THEN g.grade >= ps.passing_value ELSE g.grade >= ns.passing_value END AS passed \
+         FROM grades g {GRADE_SCALES}"
+    ));
+    match user.role {
+        Role::student => {
+            query.push(
+                "WHERE g.subject_id IN (SELECT s.id FROM subjects s JOIN users u ON u.course_id = s.course_id WHERE u.id = ",
+            );
+            query.push_bind(user.id);
+            query.push(")");
+        }
+        Role::father => {
+            query.push(
+                "WHERE g.subject_id IN (SELECT s.id FROM subjects s JOIN users u ON u.course_id = s.course_id \
+                 JOIN families f ON f.student_id = u.id WHERE f.father_id = ",
+            );
+            query.push_bind(user.id);
+            query.push(")");
+        }
+        _ => push_grade_scope(user, query),
+    }
+}
+
+/// Filters of `GradeStatisticsFilter` for `push_statistics_values`, closing
+/// the `v` table.
+fn push_statistics_filter(query: &mut QueryBuilder<'_, MySql>, filter: &GradeStatisticsFilter) {
+    if let Some(assessment_id) = filter.assessment_id {
+        query.push(" AND g.assessment_id = ");
+        query.push_bind(assessment_id);
+    }
+    if let Some(subject_id) = filter.subject_id {
+        query.push(" AND g.subject_id = ");
+        query.push_bind(subject_id);
+    }
+    if let Some(period_id) = filter.period_id {
+        query.push(" AND g.period_id = ");
+        query.push_bind(period_id);
+    }
+    query.push(") ");
+}
+
+/// Joins and conditions limiting `grades g` to the grades the user can see,
+/// ending in a `WHERE` so callers add their filters with `AND`.
+fn push_grade_scope(user: &MySelf, query: &mut QueryBuilder<'_, MySql>) {
+    match user.role {
+        Role::student => {
+            query.push("WHERE g.student_id =");
+            query.push_bind(user.id);
+        }
+        Role::teacher => {
+            query.push("JOIN subjects s ON g.subject_id = s.id WHERE s.teacher_id =");
+            query.push_bind(user.id);
+        }
+        Role::admin => {
+            query.push("WHERE 1=1");
+        }
+        Role::father => {
+            query.push("JOIN families f ON g.student_id = f.student_id WHERE f.father_id =");
+            query.push_bind(user.id);
+        }
+        Role::preceptor => {
+            query.push("JOIN subjects s ON g.subject_id = s.id JOIN courses c ON s.course_id = c.id WHERE c.preceptor_id =");
+            query.push_bind(user.id);
+        }
+    };
 }
@@ -3,7 +3,7 @@ use crate::traits::New;
 
 impl New for MySelf {
     fn new(id: u64, role: Role) -> Self{
-        Self { role, id }
+        Self { role, id, impersonator_id: None }
     }
 }
 
@@ -1,12 +1,15 @@
 use actix_multipart::Multipart;
 use actix_web::HttpResponse;
 use futures::future::join_all;
+use rust_decimal::Decimal;
 use sqlx::MySqlPool;
 use std::str;
 
+use crate::audit;
 use crate::filters::SelfassessableFilter;
 use crate::parse_multipart::parse_multipart;
 use crate::structs::*;
+use super::{assign_grades_to_period, grade_value, holds_role, invalid_grade_label, invalid_period_dates};
 use crate::traits::{Get, Post};
 use crate::email::{send_grade_email, send_disciplinary_sanction_email, send_assistance_email};
 
@@ -177,6 +180,26 @@ impl Post for MySelf {
             return HttpResponse::Unauthorized().finish();
         }
 
+        if let Some(period_id) = grade.period_id {
+            let period_exists: bool = match sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM grading_periods WHERE id = ?)")
+                .bind(period_id)
+                .fetch_one(pool)
+                .await
+            {
+                Ok(v) => v,
+                Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+            };
+            if !period_exists {
+                return HttpResponse::BadRequest().json("Grading period not found");
+            }
+        }
+
+        let (value, label) = match grade_value(pool, grade.grade_type, grade.grade, grade.label.as_deref()).await {
+            Ok(Ok(v)) => v,
+            Ok(Err(e)) => return HttpResponse::BadRequest().json(e),
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        };
+
         if let Some(assessment_id) = grade.assessment_id {
             let assessment_verify: bool = match sqlx::query_scalar(
                 "SELECT EXISTS(SELECT 1 FROM assessments WHERE id = ? AND subject_id = ?)",
@@ -206,15 +229,24 @@ impl Post for MySelf {
             if assessment_already_exixts {
                 return HttpResponse::Unauthorized().finish();
             }
-            let result = sqlx::query("INSERT INTO grades (assessment_id, student_id, grade_type, description, grade, subject_id) VALUES (?, ?, ?, ?, ?, ?)")
-                .bind(assessment_id)
-                .bind(grade.student_id)
-                .bind(&grade.grade_type)
-                .bind(&grade.description)
-                .bind(grade.grade)
-                .bind(grade.subject)
-                .execute(pool)
-                .await;
+            let result: Result<(), sqlx::Error> = async {
+                let mut tx = pool.begin().await?;
+                let grade_id = sqlx::query("INSERT INTO grades (assessment_id, student_id, grade_type, description, grade, subject_id, period_id) VALUES (?, ?, ?, ?, ?, ?, ?)")
+                    .bind(assessment_id)
+                    .bind(grade.student_id)
+                    .bind(grade.grade_type)
+                    .bind(&grade.description)
+                    .bind(value)
+                    .bind(grade.subject)
+                    .bind(grade.period_id)
+                    .execute(&mut *tx)
+                    .await?
+                    .last_insert_id();
+                let after = audit::snapshot::<Grade>(&mut tx, audit::GRADES, grade_id).await?;
+                audit::record(&mut tx, self, audit::GRADES, grade_id, AuditAction::Create, None, after).await?;
+                tx.commit().await
+            }
+            .await;
             if result.is_err() {
                 return HttpResponse::InternalServerError().finish();
             } else {
@@ -250,25 +282,36 @@ impl Post for MySelf {
                     Err(e) => return e,
                 };
 
+                let mut recipients = vec![reply_to];
+                recipients.extend(primary_contact_emails(pool, grade.student_id).await);
                 send_grade_email(
-                    vec![reply_to],
+                    recipients,
                     &subject_name,
                     &sender_name,
                     &receiver_name,
-                    &grade.grade.to_string(),
+                    &GradeType::display(Some(&grade.grade_type), value, label.as_deref()),
                 ).await;
                 return HttpResponse::Created().finish();
             }
            
         }
-        let result = sqlx::query("INSERT INTO grades (student_id, grade_type, description, grade, subject_id) VALUES (?, ?, ?, ?, ?)")
-            .bind(grade.student_id)
-            .bind(&grade.grade_type)
-            .bind(&grade.description)
-            .bind(grade.grade)
-            .bind(grade.subject)
-            .execute(pool)
-            .await;
+        let result: Result<(), sqlx::Error> = async {
+            let mut tx = pool.begin().await?;
+            let grade_id = sqlx::query("INSERT INTO grades (student_id, grade_type, description, grade, subject_id, period_id) VALUES (?, ?, ?, ?, ?, ?)")
+                .bind(grade.student_id)
+                .bind(grade.grade_type)
+                .bind(&grade.description)
+                .bind(value)
+                .bind(grade.subject)
+                .bind(grade.period_id)
+                .execute(&mut *tx)
+                .await?
+                .last_insert_id();
+            let after = audit::snapshot::<Grade>(&mut tx, audit::GRADES, grade_id).await?;
+            audit::record(&mut tx, self, audit::GRADES, grade_id, AuditAction::Create, None, after).await?;
+            tx.commit().await
+        }
+        .await;
         if result.is_err() {
             return HttpResponse::InternalServerError().finish();
         } else {
@@ -377,7 +420,7 @@ impl Post for MySelf {
             multipart,
             Some(&["jpg", "jpeg", "png"]),
             Some(&["image/jpeg", "image/png"]),
-            "./uploads/profile_pictures",
+            "profile_pictures",
         )
         .await
         {
@@ -420,7 +463,7 @@ impl Post for MySelf {
                 "application/pdf",
                 "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
             ]),
-            "./uploads/submissions",
+            "submissions",
         )
         .await
         {
@@ -636,7 +679,7 @@ impl Post for MySelf {
                 "application/pdf",
                 "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
             ]),
-            "./uploads/files",
+            "files",
         )
         .await
         {
@@ -882,17 +925,27 @@ impl Post for MySelf {
                 };
                 
                 
-                send_assistance_email(vec![students.0], &sender_name, &students.1, &assistance.presence, &assistance.date.to_string()).await;
+                let mut recipients = vec![students.0];
+                recipients.extend(primary_contact_emails(pool, assistance.student_id).await);
+                send_assistance_email(recipients, &sender_name, &students.1, &assistance.presence, &assistance.date.to_string()).await;
         }
 
-        let result = sqlx::query("INSERT INTO assistance (student_id, presence, date) VALUES (?, ?, ?)")
-            .bind(assistance.student_id)
-            .bind(assistance.presence)
-            .bind(assistance.date)
-            .execute(pool)
-            .await;
+        let result: Result<(), sqlx::Error> = async {
+            let mut tx = pool.begin().await?;
+            let assistance_id = sqlx::query("INSERT INTO assistance (student_id, presence, date) VALUES (?, ?, ?)")
+                .bind(assistance.student_id)
+                .bind(assistance.presence)
+                .bind(assistance.date)
+                .execute(&mut *tx)
+                .await?
+                .last_insert_id();
+            let after = audit::snapshot::<Assistance>(&mut tx, audit::ASSISTANCE, assistance_id).await?;
+            audit::record(&mut tx, self, audit::ASSISTANCE, assistance_id, AuditAction::Create, None, after).await?;
+            tx.commit().await
+        }
+        .await;
         match result {
-            Ok(_) => return HttpResponse::Created().finish(),
+            Ok(_) => HttpResponse::Created().finish(),
             Err(e) => return HttpResponse::InternalServerError().json(e.to_string())
         }
     }
@@ -949,18 +1002,28 @@ impl Post for MySelf {
                 Err(_) => "Remitente".to_string(),
             };
 
-        send_disciplinary_sanction_email(vec![students.0], &sender_name, &students.1, &ds.sanction_type, &ds.quantity.to_string(), &ds.description, &ds.date.to_string()).await;
-
-        let result = sqlx::query("INSERT INTO disciplinary_sanctions (student_id, sanction_type, quantity, description, date) VALUES (?, ?, ?, ?, ?)")
-            .bind(ds.student_id)
-            .bind(ds.sanction_type)
-            .bind(ds.quantity)
-            .bind(ds.description)
-            .bind(ds.date)
-            .execute(pool)
-            .await;
+        let mut recipients = vec![students.0];
+        recipients.extend(primary_contact_emails(pool, ds.student_id).await);
+        send_disciplinary_sanction_email(recipients, &sender_name, &students.1, &ds.sanction_type, &ds.quantity.to_string(), &ds.description, &ds.date.to_string()).await;
+
+        let result: Result<(), sqlx::Error> = async {
+            let mut tx = pool.begin().await?;
+            let sanction_id = sqlx::query("INSERT INTO disciplinary_sanctions (student_id, sanction_type, quantity, description, date) VALUES (?, ?, ?, ?, ?)")
+                .bind(ds.student_id)
+                .bind(ds.sanction_type)
+                .bind(ds.quantity)
+                .bind(ds.description)
+                .bind(ds.date)
+                .execute(&mut *tx)
+                .await?
+                .last_insert_id();
+            let after = audit::snapshot::<DisciplinarySanction>(&mut tx, audit::DISCIPLINARY_SANCTIONS, sanction_id).await?;
+            audit::record(&mut tx, self, audit::DISCIPLINARY_SANCTIONS, sanction_id, AuditAction::Create, None, after).await?;
+            tx.commit().await
+        }
+        .await;
         match result {
-            Ok(_) => return HttpResponse::Created().finish(),
+            Ok(_) => HttpResponse::Created().finish(),
             Err(e) => return HttpResponse::InternalServerError().json(e.to_string())
         }
     }
@@ -1015,4 +1078,397 @@ impl Post for MySelf {
             Err(e) => HttpResponse::InternalServerError().json(e.to_string())
         }
     }
+
+    async fn post_family(
+        &self,
+        pool: &MySqlPool,
+        family: NewFamily
+    ) -> HttpResponse {
+        match self.role {
+            Role::admin => {}
+            Role::preceptor => {
+                let has_access: bool = match sqlx::query_scalar(
+                    "SELECT EXISTS(SELECT 1 FROM users u JOIN courses c ON u.course_id = c.id WHERE u.id = ? AND c.preceptor_id = ?)"
+                )
+                .bind(family.student_id)
+                .bind(self.id)
+                .fetch_one(pool)
+                .await
+                {
+                    Ok(v) => v,
+                    Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+                };
+                if !has_access {
+                    return HttpResponse::Unauthorized().finish();
+                }
+            }
+            _ => return HttpResponse::Unauthorized().finish(),
+        }
+
+        // guardians log in with the father role, whatever their guardian type
+        let roles_valid: bool = match sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM roles WHERE user_id = ? AND role = 'student') \
+             AND EXISTS(SELECT 1 FROM roles WHERE user_id = ? AND role = 'father')"
+        )
+        .bind(family.student_id)
+        .bind(family.guardian_id)
+        .fetch_one(pool)
+        .await
+        {
+            Ok(v) => v,
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        };
+        if !roles_valid {
+            return HttpResponse::BadRequest().json("Student must have the student role and guardian the father role");
+        }
+
+        let already_linked: bool = match sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM families WHERE student_id = ? AND father_id = ?)"
+        )
+        .bind(family.student_id)
+        .bind(family.guardian_id)
+        .fetch_one(pool)
+        .await
+        {
+            Ok(v) => v,
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        };
+        if already_linked {
+            return HttpResponse::Conflict().json("Guardian already linked to this student");
+        }
+
+        let result = sqlx::query(
+            "INSERT INTO families (student_id, father_id, guardian_type, is_primary_contact) VALUES (?, ?, ?, ?)"
+        )
+        .bind(family.student_id)
+        .bind(family.guardian_id)
+        .bind(&family.guardian_type)
+        .bind(family.is_primary_contact)
+        .execute(pool)
+        .await;
+
+        match result {
+            Ok(r) => HttpResponse::Created().json(r.last_insert_id()),
+            Err(e) => HttpResponse::InternalServerError().json(e.to_string())
+        }
+    }
+
+    async fn post_course(
+        &self,
+        pool: &MySqlPool,
+        course: NewCourse
+    ) -> HttpResponse {
+        if self.role != Role::admin {
+            return HttpResponse::Unauthorized().finish();
+        }
+        if let Err(e) = course.validate() {
+            return HttpResponse::BadRequest().json(e);
+        }
+        if let Some(preceptor_id) = course.preceptor_id {
+            match holds_role(pool, preceptor_id, Role::preceptor).await {
+                Ok(true) => {}
+                Ok(false) => return HttpResponse::BadRequest().json("Preceptor does not hold the preceptor role"),
+                Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+            }
+        }
+
+        let result = sqlx::query(
+            "INSERT INTO courses (year, division, level, shift, preceptor_id, name) VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(course.year)
+        .bind(&course.division)
+        .bind(&course.level)
+        .bind(&course.shift)
+        .bind(course.preceptor_id)
+        .bind(course.name())
+        .execute(pool)
+        .await;
+
+        match result {
+            Ok(r) => HttpResponse::Created().json(r.last_insert_id()),
+            Err(e) => HttpResponse::InternalServerError().json(e.to_string())
+        }
+    }
+
+    async fn post_subject(
+        &self,
+        pool: &MySqlPool,
+        subject: NewSubject
+    ) -> HttpResponse {
+        if self.role != Role::admin {
+            return HttpResponse::Unauthorized().finish();
+        }
+        match holds_role(pool, subject.teacher_id, Role::teacher).await {
+            Ok(true) => {}
+            Ok(false) => return HttpResponse::BadRequest().json("Teacher does not hold the teacher role"),
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        }
+        let course_exists: bool = match sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM courses WHERE id = ?)")
+            .bind(subject.course_id)
+            .fetch_one(pool)
+            .await
+        {
+            Ok(v) => v,
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        };
+        if !course_exists {
+            return HttpResponse::BadRequest().json("Course not found");
+        }
+
+        let result = sqlx::query("INSERT INTO subjects (name, course_id, teacher_id) VALUES (?, ?, ?)")
+            .bind(&subject.name)
+            .bind(subject.course_id)
+            .bind(subject.teacher_id)
+            .execute(pool)
+            .await;
+
+        match result {
+            Ok(r) => HttpResponse::Created().json(r.last_insert_id()),
+            Err(e) => HttpResponse::InternalServerError().json(e.to_string())
+        }
+    }
+
+    async fn post_grading_period(
+        &self,
+        pool: &MySqlPool,
+        period: NewGradingPeriod
+    ) -> HttpResponse {
+        if self.role != Role::admin {
+            return HttpResponse::Unauthorized().finish();
+        }
+        match invalid_period_dates(pool, period.start_date, period.end_date, None).await {
+            Ok(None) => {}
+            Ok(Some(e)) => return HttpResponse::BadRequest().json(e),
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        }
+
+        // unassigned grades within the dates join the period, each audited
+        let result: Result<u64, sqlx::Error> = async {
+            let mut tx = pool.begin().await?;
+            let period_id = sqlx::query("INSERT INTO grading_periods (name, start_date, end_date) VALUES (?, ?, ?)")
+                .bind(&period.name)
+                .bind(period.start_date)
+                .bind(period.end_date)
+                .execute(&mut *tx)
+                .await?
+                .last_insert_id();
+            let grade_ids: Vec<u64> = sqlx::query_scalar(
+                "SELECT id FROM grades WHERE period_id IS NULL AND DATE(created_at) BETWEEN ? AND ? FOR UPDATE"
+            )
+            .bind(period.start_date)
+            .bind(period.end_date)
+            .fetch_all(&mut *tx)
+            .await?;
+            let before = audit::snapshot_grades(&mut tx, &grade_ids).await?;
+            assign_grades_to_period(&mut *tx, period_id).await?;
+            audit::record_grade_updates(&mut tx, self, &grade_ids, before).await?;
+            tx.commit().await?;
+            Ok(period_id)
+        }
+        .await;
+
+        match result {
+            Ok(period_id) => HttpResponse::Created().json(period_id),
+            Err(e) => HttpResponse::InternalServerError().json(e.to_string())
+        }
+    }
+
+    async fn post_report_card_comment(
+        &self,
+        pool: &MySqlPool,
+        comment: NewReportCardComment
+    ) -> HttpResponse {
+        match self.role {
+            Role::admin => {}
+            Role::teacher => {
+                let teacher_subject: bool = match sqlx::query_scalar(
+                    "SELECT EXISTS(SELECT 1 FROM subjects WHERE teacher_id = ? AND id = ?)",
+                )
+                .bind(self.id)
+                .bind(comment.subject_id)
+                .fetch_one(pool)
+                .await
+                {
+                    Ok(s) => s,
+                    Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+                };
+                if !teacher_subject {
+                    return HttpResponse::Unauthorized().finish();
+                }
+            }
+            _ => return HttpResponse::Unauthorized().finish(),
+        }
+        let valid: bool = match sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM users u JOIN subjects s ON s.course_id = u.course_id WHERE u.id = ? AND s.id = ?) \
+             AND EXISTS(SELECT 1 FROM grading_periods WHERE id = ?)"
+        )
+        .bind(comment.student_id)
+        .bind(comment.subject_id)
+        .bind(comment.period_id)
+        .fetch_one(pool)
+        .await
+        {
+            Ok(v) => v,
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        };
+        if !valid {
+            return HttpResponse::BadRequest().json("Student not in the subject's course or grading period not found");
+        }
+
+        // an empty comment removes it from the report card
+        let result = if comment.comment.trim().is_empty() {
+            sqlx::query("DELETE FROM report_card_comments WHERE student_id = ? AND subject_id = ? AND period_id = ?")
+                .bind(comment.student_id)
+                .bind(comment.subject_id)
+                .bind(comment.period_id)
+                .execute(pool)
+                .await
+        } else {
+            sqlx::query(
+                "INSERT INTO report_card_comments (student_id, subject_id, period_id, author_id, comment) VALUES (?, ?, ?, ?, ?) \
+                 ON DUPLICATE KEY UPDATE comment = VALUES(comment), author_id = VALUES(author_id)"
+            )
+            .bind(comment.student_id)
+            .bind(comment.subject_id)
+            .bind(comment.period_id)
+            .bind(self.id)
+            .bind(comment.comment.trim())
+            .execute(pool)
+            .await
+        };
+
+        match result {
+            Ok(_) => HttpResponse::Ok().finish(),
+            Err(e) => HttpResponse::InternalServerError().json(e.to_string())
+        }
+    }
+
+    async fn post_assessment_weights(
+        &self,
+        pool: &MySqlPool,
+        weights: NewAssessmentWeights
+    ) -> HttpResponse {
+        match (&self.role, weights.subject_id) {
+            (Role::admin, _) => {}
+            (Role::teacher, Some(subject_id)) => {
+                let teacher_subject: bool = match sqlx::query_scalar(
+                    "SELECT EXISTS(SELECT 1 FROM subjects WHERE teacher_id = ? AND id = ?)",
+                )
+                .bind(self.id)
+                .bind(subject_id)
+                .fetch_one(pool)
+                .await
+                {
+                    Ok(s) => s,
+                    Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+                };
+                if !teacher_subject {
+                    return HttpResponse::Unauthorized().finish();
+                }
+            }
+            _ => return HttpResponse::Unauthorized().finish(),
+        }
+        if let Some(subject_id) = weights.subject_id {
+            let exists: bool = match sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM subjects WHERE id = ?)")
+                .bind(subject_id)
+                .fetch_one(pool)
+                .await
+            {
+                Ok(e) => e,
+                Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+            };
+            if !exists {
+                return HttpResponse::NotFound().json("Subject not found");
+            }
+        }
+        let hundred = Decimal::from(100);
+        if weights
+            .weights
+            .iter()
+            .filter_map(|w| w.weight)
+            .any(|w| w.is_sign_negative() || w > hundred)
+        {
+            return HttpResponse::BadRequest().json("Weights must be between 0 and 100");
+        }
+
+        // all of them or none
+        let mut tx = match pool.begin().await {
+            Ok(tx) => tx,
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        };
+        for w in &weights.weights {
+            let result = match w.weight {
+                Some(weight) => {
+                    sqlx::query(
+                        "INSERT INTO assessment_type_weights (subject_id, assessment_type, weight) VALUES (?, ?, ?) \
+                         ON DUPLICATE KEY UPDATE weight = VALUES(weight)"
+                    )
+                    .bind(weights.subject_id)
+                    .bind(&w.assessment_type)
+                    .bind(weight)
+                    .execute(&mut *tx)
+                    .await
+                }
+                None => {
+                    sqlx::query("DELETE FROM assessment_type_weights WHERE subject_id <=> ? AND assessment_type = ?")
+                        .bind(weights.subject_id)
+                        .bind(&w.assessment_type)
+                        .execute(&mut *tx)
+                        .await
+                }
+            };
+            if let Err(e) = result {
+                return HttpResponse::InternalServerError().json(e.to_string());
+            }
+        }
+        match tx.commit().await {
+            Ok(_) => HttpResponse::Ok().finish(),
+            Err(e) => HttpResponse::InternalServerError().json(e.to_string())
+        }
+    }
+
+    async fn post_grade_label(
+        &self,
+        pool: &MySqlPool,
+        label: NewGradeLabel
+    ) -> HttpResponse {
+        if self.role != Role::admin {
+            return HttpResponse::Unauthorized().finish();
+        }
+        let name = label.label.trim();
+        match invalid_grade_label(pool, name, label.value, None).await {
+            Ok(None) => {}
+            Ok(Some(e)) => return HttpResponse::BadRequest().json(e),
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        }
+
+        let result = sqlx::query("INSERT INTO grading_scale_labels (label, value) VALUES (?, ?)")
+            .bind(name)
+            .bind(label.value)
+            .execute(pool)
+            .await;
+
+        match result {
+            Ok(r) => HttpResponse::Created().json(r.last_insert_id()),
+            Err(e) => HttpResponse::InternalServerError().json(e.to_string())
+        }
+    }
+}
+
+/// Emails of the guardians flagged as primary contact of a student, who get
+/// a copy of the notifications sent to the student.
+async fn primary_contact_emails(pool: &MySqlPool, student_id: u64) -> Vec<String> {
+    match sqlx::query_scalar(
+        "SELECT u.email FROM families f JOIN users u ON u.id = f.father_id WHERE f.student_id = ? AND f.is_primary_contact = TRUE"
+    )
+    .bind(student_id)
+    .fetch_all(pool)
+    .await
+    {
+        Ok(emails) => emails,
+        Err(e) => {
+            log::error!("Failed to get primary contacts of student {}: {}", student_id, e);
+            vec![]
+        }
+    }
 }
@@ -1,7 +1,10 @@
 use actix_multipart::Multipart;
 use actix_web::HttpResponse;
-use sqlx::MySqlPool;
+use rust_decimal::Decimal;
+use sqlx::{MySqlPool, QueryBuilder};
+use crate::audit;
 use crate::structs::*;
+use super::{assign_grades_to_periods, grade_value, holds_role, invalid_grade_label, invalid_period_dates};
 use crate::traits::{Update, Get};
 
 impl Update for MySelf {
@@ -66,29 +69,83 @@ impl Update for MySelf {
         if !is_authorized {
             return HttpResponse::Unauthorized().finish();
         }
-        let mut query = String::from("UPDATE grades SET ");
-        let mut first = true;
+        if let Some(period_id) = data.period_id {
+            let period_exists: bool = match sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM grading_periods WHERE id = ?)")
+                .bind(period_id)
+                .fetch_one(pool)
+                .await
+            {
+                Ok(v) => v,
+                Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+            };
+            if !period_exists {
+                return HttpResponse::BadRequest().json("Grading period not found");
+            }
+        }
+        // the grade is validated against its type, the new one or the stored one
+        let mut value = None;
+        if data.grade.is_some() || data.grade_type.is_some() || data.label.is_some() {
+            let current: Option<(Option<GradeType>, Decimal)> =
+                match sqlx::query_as("SELECT grade_type, grade FROM grades WHERE id = ?")
+                    .bind(grade_id)
+                    .fetch_optional(pool)
+                    .await
+                {
+                    Ok(c) => c,
+                    Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+                };
+            let Some((current_type, current_grade)) = current else {
+                return HttpResponse::NotFound().finish();
+            };
+            let grade_type = data.grade_type.or(current_type).unwrap_or(GradeType::Numerical);
+            // a new label replaces the stored grade
+            let grade = if data.label.is_some() { data.grade } else { data.grade.or(Some(current_grade)) };
+            match grade_value(pool, grade_type, grade, data.label.as_deref()).await {
+                Ok(Ok((v, _))) => value = Some((grade_type, v)),
+                Ok(Err(e)) => return HttpResponse::BadRequest().json(e),
+                Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+            }
+        }
+
+        let mut query = QueryBuilder::new("UPDATE grades SET ");
+        let mut fields = query.separated(", ");
+        let mut empty = true;
         if let Some(ref description) = data.description {
-            if !first { query.push_str(", "); } query.push_str("description = '"); query.push_str(description); query.push('\''); first = false;
+            fields.push("description = ").push_bind_unseparated(description);
+            empty = false;
         }
-        if let Some(ref grade) = data.grade {
-            if !first { query.push_str(", "); } query.push_str("grade = "); query.push_str(&grade.to_string()); first = false;
+        if let Some((grade_type, grade)) = value {
+            fields.push("grade = ").push_bind_unseparated(grade);
+            fields.push("grade_type = ").push_bind_unseparated(grade_type);
+            empty = false;
         }
         if let Some(student_id) = data.student_id {
-            if !first { query.push_str(", "); } query.push_str("student_id = "); query.push_str(&student_id.to_string()); first = false;
+            fields.push("student_id = ").push_bind_unseparated(student_id);
+            empty = false;
         }
         if let Some(subject_id) = data.subject_id {
-            if !first { query.push_str(", "); } query.push_str("subject_id = "); query.push_str(&subject_id.to_string()); first = false;
+            fields.push("subject_id = ").push_bind_unseparated(subject_id);
+            empty = false;
         }
         if let Some(assessment_id) = data.assessment_id {
-            if !first { query.push_str(", "); } query.push_str("assessment_id = "); query.push_str(&assessment_id.to_string()); first = false;
+            fields.push("assessment_id = ").push_bind_unseparated(assessment_id);
+            empty = false;
+        }
+        if let Some(period_id) = data.period_id {
+            fields.push("period_id = ").push_bind_unseparated(period_id);
+            empty = false;
+        }
+        if empty { return HttpResponse::BadRequest().body("No fields to update"); }
+        query.push(" WHERE id = ").push_bind(grade_id);
+        let res: Result<(), sqlx::Error> = async {
+            let mut tx = pool.begin().await?;
+            let before = audit::snapshot::<Grade>(&mut tx, audit::GRADES, grade_id).await?;
+            query.build().execute(&mut *tx).await?;
+            let after = audit::snapshot::<Grade>(&mut tx, audit::GRADES, grade_id).await?;
+            audit::record(&mut tx, self, audit::GRADES, grade_id, AuditAction::Update, before, after).await?;
+            tx.commit().await
         }
-        if let Some(ref grade_type) = data.grade_type {
-            if !first { query.push_str(", "); } query.push_str("grade_type = '"); query.push_str(&format!("{:?}", grade_type)); query.push('\''); first = false;
-        }
-        if first { return HttpResponse::BadRequest().body("No fields to update"); }
-        query.push_str(" WHERE id = ?");
-        let res = sqlx::query(&query).bind(grade_id).execute(pool).await;
+        .await;
         match res {
             Ok(_) => HttpResponse::Ok().finish(),
             Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
@@ -169,7 +226,7 @@ impl Update for MySelf {
             multipart,
             Some(&["jpg", "jpeg", "png"]),
             Some(&["image/jpeg", "image/png"]),
-            "uploads/profile_pictures",
+            "profile_pictures",
         ).await {
             Ok(h) => h,
             Err(e) => return HttpResponse::BadRequest().json(format!("Invalid upload: {}", e)),
@@ -291,14 +348,22 @@ impl Update for MySelf {
                 return HttpResponse::Unauthorized().finish();
             }
         }
-        let result = sqlx::query("UPDATE assistance SET presence = ?, date = ? WHERE id = ?")
-            .bind(data.presence)
-            .bind(data.date)
-            .bind(assistance_id)
-            .execute(pool)
-            .await;
+        let result: Result<(), sqlx::Error> = async {
+            let mut tx = pool.begin().await?;
+            let before = audit::snapshot::<Assistance>(&mut tx, audit::ASSISTANCE, assistance_id).await?;
+            sqlx::query("UPDATE assistance SET presence = ?, date = ? WHERE id = ?")
+                .bind(data.presence)
+                .bind(data.date)
+                .bind(assistance_id)
+                .execute(&mut *tx)
+                .await?;
+            let after = audit::snapshot::<Assistance>(&mut tx, audit::ASSISTANCE, assistance_id).await?;
+            audit::record(&mut tx, self, audit::ASSISTANCE, assistance_id, AuditAction::Update, before, after).await?;
+            tx.commit().await
+        }
+        .await;
             match result {
-                Ok(_) => return HttpResponse::Ok().finish(),
+                Ok(_) => HttpResponse::Ok().finish(),
                 Err(e) => return HttpResponse::InternalServerError().json(e.to_string())
             }
     }
@@ -334,16 +399,24 @@ impl Update for MySelf {
                 return HttpResponse::Unauthorized().finish();
             }
         }
-        let result = sqlx::query("UPDATE disciplinary_sanctions SET sanction_type = ?, date = ?, quantity = ?, description = ? WHERE id = ?")
-            .bind(data.sanction_type)
-            .bind(data.date)
-            .bind(data.quantity)
-            .bind(data.description)
-            .bind(disciplinary_sanction_id)
-            .execute(pool)
-            .await;
+        let result: Result<(), sqlx::Error> = async {
+            let mut tx = pool.begin().await?;
+            let before = audit::snapshot::<DisciplinarySanction>(&mut tx, audit::DISCIPLINARY_SANCTIONS, disciplinary_sanction_id).await?;
+            sqlx::query("UPDATE disciplinary_sanctions SET sanction_type = ?, date = ?, quantity = ?, description = ? WHERE id = ?")
+                .bind(data.sanction_type)
+                .bind(data.date)
+                .bind(data.quantity)
+                .bind(data.description)
+                .bind(disciplinary_sanction_id)
+                .execute(&mut *tx)
+                .await?;
+            let after = audit::snapshot::<DisciplinarySanction>(&mut tx, audit::DISCIPLINARY_SANCTIONS, disciplinary_sanction_id).await?;
+            audit::record(&mut tx, self, audit::DISCIPLINARY_SANCTIONS, disciplinary_sanction_id, AuditAction::Update, before, after).await?;
+            tx.commit().await
+        }
+        .await;
             match result {
-                Ok(_) => return HttpResponse::Ok().finish(),
+                Ok(_) => HttpResponse::Ok().finish(),
                 Err(e) => return HttpResponse::InternalServerError().json(e.to_string())
             }
     }
@@ -375,4 +448,339 @@ impl Update for MySelf {
             Err(e) => HttpResponse::InternalServerError().json(e.to_string())
         }
     }
+
+    async fn update_family(
+        &self,
+        pool: &MySqlPool,
+        family_id: u64,
+        data: UpdateFamily
+    ) -> HttpResponse {
+        match self.role {
+            Role::admin => {}
+            Role::preceptor => {
+                let has_access: bool = match sqlx::query_scalar(
+                    "SELECT EXISTS(SELECT 1 FROM families f JOIN users u ON f.student_id = u.id JOIN courses c ON u.course_id = c.id WHERE f.id = ? AND c.preceptor_id = ?)"
+                )
+                .bind(family_id)
+                .bind(self.id)
+                .fetch_one(pool)
+                .await
+                {
+                    Ok(v) => v,
+                    Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+                };
+                if !has_access {
+                    return HttpResponse::Unauthorized().finish();
+                }
+            }
+            _ => return HttpResponse::Unauthorized().finish(),
+        }
+
+        let result = sqlx::query(
+            "UPDATE families SET guardian_type = COALESCE(?, guardian_type), is_primary_contact = COALESCE(?, is_primary_contact) WHERE id = ?"
+        )
+        .bind(data.guardian_type)
+        .bind(data.is_primary_contact)
+        .bind(family_id)
+        .execute(pool)
+        .await;
+
+        match result {
+            Ok(r) if r.rows_affected() == 0 => HttpResponse::NotFound().json("Family not found"),
+            Ok(_) => HttpResponse::Ok().finish(),
+            Err(e) => HttpResponse::InternalServerError().json(e.to_string())
+        }
+    }
+
+    async fn update_course(
+        &self,
+        pool: &MySqlPool,
+        course_id: u64,
+        data: UpdateCourse
+    ) -> HttpResponse {
+        if self.role != Role::admin {
+            return HttpResponse::Unauthorized().finish();
+        }
+        if let Err(e) = data.validate() {
+            return HttpResponse::BadRequest().json(e);
+        }
+        if let Some(Some(preceptor_id)) = data.preceptor_id {
+            match holds_role(pool, preceptor_id, Role::preceptor).await {
+                Ok(true) => {}
+                Ok(false) => return HttpResponse::BadRequest().json("Preceptor does not hold the preceptor role"),
+                Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+            }
+        }
+
+        // a default name ("1° A") follows year and division, a custom one is kept;
+        // name goes first since MySQL assigns left to right
+        let result = sqlx::query(
+            "UPDATE courses SET \
+             name = COALESCE(?, IF(name = CONCAT(year, '° ', division), CONCAT(COALESCE(?, year), '° ', COALESCE(?, division)), name)), \
+             year = COALESCE(?, year), division = COALESCE(?, division), level = COALESCE(?, level), \
+             shift = COALESCE(?, shift), preceptor_id = IF(?, ?, preceptor_id) \
+             WHERE id = ?"
+        )
+        .bind(&data.name)
+        .bind(data.year)
+        .bind(&data.division)
+        .bind(data.year)
+        .bind(&data.division)
+        .bind(&data.level)
+        .bind(&data.shift)
+        .bind(data.preceptor_id.is_some())
+        .bind(data.preceptor_id.flatten())
+        .bind(course_id)
+        .execute(pool)
+        .await;
+
+        match result {
+            Ok(r) if r.rows_affected() == 0 => HttpResponse::NotFound().json("Course not found"),
+            Ok(_) => HttpResponse::Ok().finish(),
+            Err(e) => HttpResponse::InternalServerError().json(e.to_string())
+        }
+    }
+
+    async fn update_subject(
+        &self,
+        pool: &MySqlPool,
+        subject_id: u64,
+        data: UpdateSubject
+    ) -> HttpResponse {
+        if self.role != Role::admin {
+            return HttpResponse::Unauthorized().finish();
+        }
+        if let Some(teacher_id) = data.teacher_id {
+            match holds_role(pool, teacher_id, Role::teacher).await {
+                Ok(true) => {}
+                Ok(false) => return HttpResponse::BadRequest().json("Teacher does not hold the teacher role"),
+                Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+            }
+        }
+        if let Some(course_id) = data.course_id {
+            let course_exists: bool = match sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM courses WHERE id = ?)")
+                .bind(course_id)
+                .fetch_one(pool)
+                .await
+            {
+                Ok(v) => v,
+                Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+            };
+            if !course_exists {
+                return HttpResponse::BadRequest().json("Course not found");
+            }
+        }
+
+        let result = sqlx::query(
+            "UPDATE subjects SET name = COALESCE(?, name), teacher_id = COALESCE(?, teacher_id), course_id = COALESCE(?, course_id) WHERE id = ?"
+        )
+        .bind(&data.name)
+        .bind(data.teacher_id)
+        .bind(data.course_id)
+        .bind(subject_id)
+        .execute(pool)
+        .await;
+
+        match result {
+            Ok(r) if r.rows_affected() == 0 => HttpResponse::NotFound().json("Subject not found"),
+            Ok(_) => HttpResponse::Ok().finish(),
+            Err(e) => HttpResponse::InternalServerError().json(e.to_string())
+        }
+    }
+
+    async fn update_grading_period(
+        &self,
+        pool: &MySqlPool,
+        period_id: u64,
+        data: UpdateGradingPeriod
+    ) -> HttpResponse {
+        if self.role != Role::admin {
+            return HttpResponse::Unauthorized().finish();
+        }
+        let current: GradingPeriod = match sqlx::query_as("SELECT * FROM grading_periods WHERE id = ?")
+            .bind(period_id)
+            .fetch_optional(pool)
+            .await
+        {
+            Ok(Some(p)) => p,
+            Ok(None) => return HttpResponse::NotFound().json("Grading period not found"),
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        };
+        let start_date = data.start_date.unwrap_or(current.start_date);
+        let end_date = data.end_date.unwrap_or(current.end_date);
+        match invalid_period_dates(pool, start_date, end_date, Some(period_id)).await {
+            Ok(None) => {}
+            Ok(Some(e)) => return HttpResponse::BadRequest().json(e),
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        }
+
+        // grades left outside the new dates lose the period and, like the
+        // unassigned ones, go to whichever period now covers them
+        let result: Result<(), sqlx::Error> = async {
+            let mut tx = pool.begin().await?;
+            sqlx::query("UPDATE grading_periods SET name = COALESCE(?, name), start_date = ?, end_date = ? WHERE id = ?")
+                .bind(&data.name)
+                .bind(start_date)
+                .bind(end_date)
+                .bind(period_id)
+                .execute(&mut *tx)
+                .await?;
+            // the grades that may move: the ones leaving the period and the
+            // unassigned ones some period covers now, each move is audited
+            let grade_ids: Vec<u64> = sqlx::query_scalar(
+                "SELECT g.id FROM grades g \
+                 WHERE (g.period_id = ? AND DATE(g.created_at) NOT BETWEEN ? AND ?) \
+                 OR (g.period_id IS NULL AND EXISTS(SELECT 1 FROM grading_periods p \
+                     WHERE DATE(g.created_at) BETWEEN p.start_date AND p.end_date)) \
+                 FOR UPDATE"
+            )
+            .bind(period_id)
+            .bind(start_date)
+            .bind(end_date)
+            .fetch_all(&mut *tx)
+            .await?;
+            let before = audit::snapshot_grades(&mut tx, &grade_ids).await?;
+            sqlx::query("UPDATE grades SET period_id = NULL WHERE period_id = ? AND DATE(created_at) NOT BETWEEN ? AND ?")
+                .bind(period_id)
+                .bind(start_date)
+                .bind(end_date)
+                .execute(&mut *tx)
+                .await?;
+            assign_grades_to_periods(&mut *tx).await?;
+            audit::record_grade_updates(&mut tx, self, &grade_ids, before).await?;
+            tx.commit().await
+        }
+        .await;
+
+        match result {
+            Ok(_) => HttpResponse::Ok().finish(),
+            Err(e) => HttpResponse::InternalServerError().json(e.to_string())
+        }
+    }
+
+    async fn update_grading_scale(
+        &self,
+        pool: &MySqlPool,
+        grade_type: GradeType,
+        data: UpdateGradingScale
+    ) -> HttpResponse {
+        if self.role != Role::admin {
+            return HttpResponse::Unauthorized().finish();
+        }
+        if grade_type == GradeType::Conceptual {
+            return HttpResponse::BadRequest().json("Conceptual grades are set by their labels");
+        }
+        let current: GradingScale = match sqlx::query_as("SELECT * FROM grading_scales WHERE grade_type = ?")
+            .bind(grade_type)
+            .fetch_one(pool)
+            .await
+        {
+            Ok(s) => s,
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        };
+        let min_value = data.min_value.unwrap_or(current.min_value);
+        let max_value = data.max_value.unwrap_or(current.max_value);
+        let passing_value = data.passing_value.unwrap_or(current.passing_value);
+        if min_value >= max_value {
+            return HttpResponse::BadRequest().json("min_value must be less than max_value");
+        }
+        if passing_value < min_value || passing_value > max_value {
+            return HttpResponse::BadRequest().json("passing_value must be within the scale");
+        }
+
+        // grades given on the old scale, and the labels on the numerical one, must still fit
+        let out_of_scale: bool = match sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM grades WHERE COALESCE(grade_type, 'numerical') = ? AND (grade < ? OR grade > ?)) \
+             OR (? = 'numerical' AND EXISTS(SELECT 1 FROM grading_scale_labels WHERE value < ? OR value > ?))"
+        )
+        .bind(grade_type)
+        .bind(min_value)
+        .bind(max_value)
+        .bind(grade_type)
+        .bind(min_value)
+        .bind(max_value)
+        .fetch_one(pool)
+        .await
+        {
+            Ok(o) => o,
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        };
+        if out_of_scale {
+            return HttpResponse::Conflict().json("Grades or conceptual labels fall outside the new scale");
+        }
+
+        let result = sqlx::query("UPDATE grading_scales SET min_value = ?, max_value = ?, passing_value = ? WHERE grade_type = ?")
+            .bind(min_value)
+            .bind(max_value)
+            .bind(passing_value)
+            .bind(grade_type)
+            .execute(pool)
+            .await;
+
+        match result {
+            Ok(_) => HttpResponse::Ok().finish(),
+            Err(e) => HttpResponse::InternalServerError().json(e.to_string())
+        }
+    }
+
+    async fn update_grade_label(
+        &self,
+        pool: &MySqlPool,
+        label_id: u64,
+        data: UpdateGradeLabel
+    ) -> HttpResponse {
+        if self.role != Role::admin {
+            return HttpResponse::Unauthorized().finish();
+        }
+        // the label is locked until its grades follow it, a concurrent edit waits
+        let mut tx = match pool.begin().await {
+            Ok(tx) => tx,
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        };
+        let current: GradeLabel = match sqlx::query_as("SELECT * FROM grading_scale_labels WHERE id = ? FOR UPDATE")
+            .bind(label_id)
+            .fetch_optional(&mut *tx)
+            .await
+        {
+            Ok(Some(l)) => l,
+            Ok(None) => return HttpResponse::NotFound().json("Label not found"),
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        };
+        let label = data.label.as_deref().map_or(current.label.as_str(), str::trim);
+        let value = data.value.unwrap_or(current.value);
+        match invalid_grade_label(pool, label, value, Some(label_id)).await {
+            Ok(None) => {}
+            Ok(Some(e)) => return HttpResponse::BadRequest().json(e),
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        }
+
+        // the grades given with the label keep it, each change audited
+        let result: Result<(), sqlx::Error> = async {
+            let grade_ids: Vec<u64> =
+                sqlx::query_scalar("SELECT id FROM grades WHERE grade_type = 'conceptual' AND grade = ? FOR UPDATE")
+                    .bind(current.value)
+                    .fetch_all(&mut *tx)
+                    .await?;
+            let before = audit::snapshot_grades(&mut tx, &grade_ids).await?;
+            sqlx::query("UPDATE grades SET grade = ? WHERE grade_type = 'conceptual' AND grade = ?")
+                .bind(value)
+                .bind(current.value)
+                .execute(&mut *tx)
+                .await?;
+            audit::record_grade_updates(&mut tx, self, &grade_ids, before).await?;
+            sqlx::query("UPDATE grading_scale_labels SET label = ?, value = ? WHERE id = ?")
+                .bind(label)
+                .bind(value)
+                .bind(label_id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await
+        }
+        .await;
+
+        match result {
+            Ok(_) => HttpResponse::Ok().finish(),
+            Err(e) => HttpResponse::InternalServerError().json(e.to_string())
+        }
+    }
 }
@@ -3,11 +3,11 @@ use actix_web::error::{ErrorBadRequest, JsonPayloadError};
 use serde_json::error::Category;
 use log::error;
 
-pub fn json_config() -> JsonConfig {
+pub fn json_config(limit: usize) -> JsonConfig {
     JsonConfig::default()
-        .limit(4096)
+        .limit(limit)
         .content_type_required(true)
-        .error_handler(|err, _req| {
+        .error_handler(move |err, _req| {
             error!("JSON parsing error: {:?}", err);
             
             let user_message = match err {
@@ -25,7 +25,9 @@ pub fn json_config() -> JsonConfig {
                     Category::Eof => "Incomplete JSON data received",
                     _ => "Malformed JSON request",
                 },
-                JsonPayloadError::Overflow { .. } => "Request payload too large (max 4KB)",
+                JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+                    return ErrorBadRequest(format!("Request payload too large (max {} bytes)", limit));
+                }
                 JsonPayloadError::ContentType => "Content-Type must be application/json",
                 JsonPayloadError::Payload(_) => "Error reading request body",
                 _ => "Bad request",
@@ -1,20 +1,49 @@
-use jsonwebtoken::{decode, Algorithm, DecodingKey, TokenData, Validation};
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, Header, TokenData, Validation};
+use jsonwebtoken::errors::ErrorKind;
 use serde::{Serialize, Deserialize};
-use std::fs;
+use sqlx::MySqlPool;
 
+use crate::keyring;
+use crate::schools;
+use crate::sessions::is_session_active;
 use crate::structs::MySelf;
 
 #[derive(Serialize, Deserialize)]
 pub struct Claims {
     pub exp: usize,
+    pub sid: String,
     pub user: MySelf,
+    /// Present when an admin acts as `user`, see `views::impersonation`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonation: Option<Impersonation>,
+    /// School the token was issued by, tokens from before schools have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub school: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Impersonation {
+    pub admin_id: u64,
+    pub read_only: bool,
 }
 
 impl Claims {
-    pub fn new(user: MySelf) -> Claims {
+    pub fn new(user: MySelf, sid: String) -> Claims {
         Claims {
             user,
-            exp: (chrono::Utc::now().timestamp() + 3600) as usize, // 1 hour expiration
+            sid,
+            exp: (chrono::Utc::now().timestamp() + 900) as usize, // 15 minutes expiration, renewed with the refresh token
+            impersonation: None,
+            school: Some(schools::current().id.clone()),
+        }
+    }
+
+    /// Impersonation tokens are never renewed: the session has no refresh
+    /// token and ends with this token.
+    pub fn impersonating(user: MySelf, sid: String, impersonation: Impersonation) -> Claims {
+        Claims {
+            impersonation: Some(impersonation),
+            ..Claims::new(user, sid)
         }
     }
 }
@@ -27,20 +56,49 @@ fn get_validation() -> Validation {
     validation
 }
 
-pub fn validate(jwt: &str) -> Result<TokenData<Claims>, jsonwebtoken::errors::Error> {
-    
+pub fn sign(claims: &Claims) -> anyhow::Result<String> {
+    keyring::with_signing_key(|kid, encoding_key| {
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some(kid.to_string());
+        encode(&header, claims, encoding_key)
+    })?
+    .map_err(Into::into)
+}
+
+/// Checks the signature and expiry only, see `validate`.
+fn decode_token(jwt: &str) -> Result<TokenData<Claims>, jsonwebtoken::errors::Error> {
     let validation = get_validation();
-    
-    let public_key_pem = fs::read("/shared/ecc_public_key.pem")
-        .expect("ecc_public_key.pem not found");
+    let header = decode_header(jwt)?;
 
-    let decoding_key = match DecodingKey::from_ec_pem(&public_key_pem){
-        Ok(key) => key,
-        Err(e) => panic!("{}", e),
-    };
+    let mut last_error = None;
+    let decoded = keyring::with_verifying_keys(header.kid.as_deref(), |key| {
+        match decode::<Claims>(jwt, key, &validation) {
+            Ok(d) => Some(d),
+            Err(e) => {
+                last_error = Some(e);
+                None
+            }
+        }
+    });
+    decoded.ok_or_else(|| last_error.unwrap_or_else(|| ErrorKind::InvalidToken.into()))
+}
 
-    let decode = decode::<Claims>(jwt, &decoding_key, &validation);
-    
-    decode
+/// School of a valid token, used to find the school of requests that come
+/// through a host shared by every school.
+pub fn school_of(jwt: &str) -> Option<String> {
+    decode_token(jwt).ok()?.claims.school
 }
 
+pub async fn validate(jwt: &str, pool: &MySqlPool) -> Result<TokenData<Claims>, jsonwebtoken::errors::Error> {
+    let decoded = decode_token(jwt)?;
+
+    // a revoked or expired session invalidates every token issued for it
+    match is_session_active(pool, &decoded.claims.sid).await {
+        Ok(true) => Ok(decoded),
+        Ok(false) => Err(ErrorKind::InvalidToken.into()),
+        Err(e) => {
+            log::error!("Failed to check session {}: {}", decoded.claims.sid, e);
+            Err(ErrorKind::InvalidToken.into())
+        }
+    }
+}
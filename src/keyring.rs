@@ -0,0 +1,273 @@
+use anyhow::{anyhow, Context};
+use base64::Engine;
+use chrono::{NaiveDateTime, Utc};
+use jsonwebtoken::{DecodingKey, EncodingKey};
+use ring::rand::SystemRandom;
+use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::RwLock;
+
+use crate::config;
+
+/// Key ids are the creation time of the key, so sorting them gives the
+/// rotation order and the newest one is the signing key.
+const KID_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// How long a replaced key is still accepted. Must be longer than the access
+/// token lifetime so rotating never logs anyone out.
+const RETIRE_AFTER_MINUTES: i64 = 60;
+
+/// Least seconds between the reloads triggered by unknown kids, which come in
+/// unverified tokens anyone can forge.
+const RELOAD_INTERVAL_SECONDS: i64 = 30;
+
+const LEGACY_PRIVATE_KEY: &str = "ecc_private_key.pem";
+const LEGACY_PUBLIC_KEY: &str = "ecc_public_key.pem";
+
+// DER prefix of a SubjectPublicKeyInfo for an uncompressed P-256 point
+const P256_SPKI_PREFIX: [u8; 26] = [
+    0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a,
+    0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+];
+
+pub struct KeyRing {
+    signing_kid: String,
+    signing_key: EncodingKey,
+    verifying_keys: BTreeMap<String, DecodingKey>,
+}
+
+static KEYRING: RwLock<Option<KeyRing>> = RwLock::new(None);
+
+/// Unix time of the last reload asked for by `with_verifying_keys`.
+static LAST_ON_DEMAND_RELOAD: AtomicI64 = AtomicI64::new(0);
+
+fn keys_dir() -> PathBuf {
+    PathBuf::from(&config::get().jwt_keys_dir)
+}
+
+fn private_key_path(dir: &Path, kid: &str) -> PathBuf {
+    dir.join(format!("{}.private.pem", kid))
+}
+
+fn public_key_path(dir: &Path, kid: &str) -> PathBuf {
+    dir.join(format!("{}.public.pem", kid))
+}
+
+fn new_kid() -> String {
+    Utc::now().format(KID_FORMAT).to_string()
+}
+
+fn to_pem(label: &str, der: &[u8]) -> String {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(der);
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).expect("base64 is ascii"));
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", label));
+    pem
+}
+
+/// Every kid found in the directory, oldest first.
+fn list_kids(dir: &Path) -> anyhow::Result<Vec<String>> {
+    let mut kids = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        if let Some(kid) = name.strip_suffix(".public.pem")
+            && NaiveDateTime::parse_from_str(kid, KID_FORMAT).is_ok()
+        {
+            kids.push(kid.to_string());
+        }
+    }
+    kids.sort();
+    Ok(kids)
+}
+
+/// Kids that are still accepted: the newest one plus every key that was
+/// replaced less than `RETIRE_AFTER_MINUTES` ago.
+fn live_kids(kids: &[String]) -> Vec<String> {
+    let now = Utc::now().naive_utc();
+    kids.iter()
+        .enumerate()
+        .filter(|(i, _)| match kids.get(i + 1) {
+            None => true,
+            Some(next) => NaiveDateTime::parse_from_str(next, KID_FORMAT)
+                .map(|replaced_at| (now - replaced_at).num_minutes() < RETIRE_AFTER_MINUTES)
+                .unwrap_or(false),
+        })
+        .map(|(_, kid)| kid.clone())
+        .collect()
+}
+
+fn generate_key(dir: &Path) -> anyhow::Result<String> {
+    let rng = SystemRandom::new();
+    let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+        .map_err(|_| anyhow!("failed to generate P-256 key"))?;
+    let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+        .map_err(|e| anyhow!("generated key rejected: {}", e))?;
+
+    let mut spki = P256_SPKI_PREFIX.to_vec();
+    spki.extend_from_slice(key_pair.public_key().as_ref());
+
+    let mut kid = new_kid();
+    // two rotations within the same second would otherwise share a kid
+    while public_key_path(dir, &kid).exists() {
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        kid = new_kid();
+    }
+
+    // private key first: a public key file is what makes a kid visible.
+    // Only the owner can read it, whatever the umask.
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(private_key_path(dir, &kid))?
+        .write_all(to_pem("PRIVATE KEY", pkcs8.as_ref()).as_bytes())?;
+    fs::write(public_key_path(dir, &kid), to_pem("PUBLIC KEY", &spki))?;
+    Ok(kid)
+}
+
+/// First start after the keyring was introduced: keep using the key pair
+/// created by create_database.py so issued tokens stay valid.
+fn import_legacy_key(dir: &Path) -> anyhow::Result<Option<String>> {
+    let legacy_dir = Path::new(&config::get().legacy_keys_dir);
+    let (private_key, public_key) = (legacy_dir.join(LEGACY_PRIVATE_KEY), legacy_dir.join(LEGACY_PUBLIC_KEY));
+    if !private_key.exists() || !public_key.exists() {
+        return Ok(None);
+    }
+    let kid = new_kid();
+    fs::copy(private_key, private_key_path(dir, &kid))?;
+    fs::set_permissions(private_key_path(dir, &kid), fs::Permissions::from_mode(0o600))?;
+    fs::copy(public_key, public_key_path(dir, &kid))?;
+    Ok(Some(kid))
+}
+
+fn load(dir: &Path) -> anyhow::Result<KeyRing> {
+    fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+
+    let mut kids = list_kids(dir)?;
+    if kids.is_empty() {
+        let kid = match import_legacy_key(dir)? {
+            Some(kid) => kid,
+            None => generate_key(dir)?,
+        };
+        log::info!("Created JWT signing key {}", kid);
+        kids.push(kid);
+    }
+
+    let mut verifying_keys = BTreeMap::new();
+    for kid in live_kids(&kids) {
+        let pem = fs::read(public_key_path(dir, &kid))?;
+        let key = DecodingKey::from_ec_pem(&pem).with_context(|| format!("invalid public key {}", kid))?;
+        verifying_keys.insert(kid, key);
+    }
+
+    let signing_kid = kids.last().expect("keyring has at least one key").clone();
+    let pem = fs::read(private_key_path(dir, &signing_kid))
+        .with_context(|| format!("missing private key for {}", signing_kid))?;
+    let signing_key = EncodingKey::from_ec_pem(&pem).with_context(|| format!("invalid private key {}", signing_kid))?;
+
+    Ok(KeyRing {
+        signing_kid,
+        signing_key,
+        verifying_keys,
+    })
+}
+
+/// Loads the keys from disk, replacing the ones in memory.
+pub fn reload() -> anyhow::Result<()> {
+    let keyring = load(&keys_dir())?;
+    *KEYRING.write().expect("keyring lock poisoned") = Some(keyring);
+    Ok(())
+}
+
+/// Adds a new signing key. Older keys keep verifying tokens for
+/// `RETIRE_AFTER_MINUTES`; keys past that window are deleted.
+pub fn rotate() -> anyhow::Result<String> {
+    let dir = keys_dir();
+    fs::create_dir_all(&dir)?;
+    let kid = generate_key(&dir)?;
+
+    let kids = list_kids(&dir)?;
+    let live = live_kids(&kids);
+    for old in kids.iter().filter(|k| !live.contains(k)) {
+        let _ = fs::remove_file(private_key_path(&dir, old));
+        let _ = fs::remove_file(public_key_path(&dir, old));
+        log::info!("Deleted retired JWT key {}", old);
+    }
+
+    reload()?;
+    Ok(kid)
+}
+
+pub fn with_signing_key<T>(f: impl FnOnce(&str, &EncodingKey) -> T) -> anyhow::Result<T> {
+    let guard = KEYRING.read().expect("keyring lock poisoned");
+    let keyring = guard.as_ref().ok_or_else(|| anyhow!("keyring not loaded"))?;
+    Ok(f(&keyring.signing_kid, &keyring.signing_key))
+}
+
+/// Runs `f` with the verification key for `kid`, or with every accepted key
+/// for tokens signed before key ids existed. Unknown kids trigger a reload,
+/// since another instance (or the CLI) may have rotated the keys, at most
+/// once every `RELOAD_INTERVAL_SECONDS`.
+pub fn with_verifying_keys<T>(kid: Option<&str>, mut f: impl FnMut(&DecodingKey) -> Option<T>) -> Option<T> {
+    {
+        let guard = KEYRING.read().expect("keyring lock poisoned");
+        let keyring = guard.as_ref()?;
+        match kid {
+            Some(kid) => {
+                if let Some(key) = keyring.verifying_keys.get(kid) {
+                    return f(key);
+                }
+            }
+            None => return keyring.verifying_keys.values().rev().find_map(&mut f),
+        }
+    }
+
+    let kid = kid?;
+    let candidate = NaiveDateTime::parse_from_str(kid, KID_FORMAT).ok()?;
+    if candidate > Utc::now().naive_utc() + chrono::Duration::minutes(1) {
+        return None;
+    }
+    let newest_known = {
+        let guard = KEYRING.read().expect("keyring lock poisoned");
+        guard.as_ref()?.verifying_keys.keys().next_back().cloned()
+    };
+    // only a kid newer than ours can appear on disk, anything else is retired or forged
+    let newer = newest_known
+        .and_then(|k| NaiveDateTime::parse_from_str(&k, KID_FORMAT).ok())
+        .is_none_or(|newest| candidate > newest);
+    if !newer {
+        return None;
+    }
+    // one request per interval reads the disk, the others wait for the next one
+    let now = Utc::now().timestamp();
+    let last = LAST_ON_DEMAND_RELOAD.load(Ordering::Relaxed);
+    if now - last < RELOAD_INTERVAL_SECONDS
+        || LAST_ON_DEMAND_RELOAD
+            .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+    {
+        return None;
+    }
+    if let Err(e) = reload() {
+        log::error!("Failed to reload JWT keys: {}", e);
+        return None;
+    }
+
+    let guard = KEYRING.read().expect("keyring lock poisoned");
+    guard.as_ref()?.verifying_keys.get(kid).and_then(f)
+}
+
+/// Kids currently accepted, oldest first, and the one used for signing.
+pub fn key_ids() -> anyhow::Result<(Vec<String>, String)> {
+    let guard = KEYRING.read().expect("keyring lock poisoned");
+    let keyring = guard.as_ref().ok_or_else(|| anyhow!("keyring not loaded"))?;
+    Ok((keyring.verifying_keys.keys().cloned().collect(), keyring.signing_kid.clone()))
+}
@@ -0,0 +1,147 @@
+use actix_web::HttpRequest;
+use sqlx::MySqlPool;
+use std::time::Duration;
+use tokio::time::sleep;
+
+use crate::email::send_account_locked_email;
+use crate::schools;
+
+const WINDOW_MINUTES: i64 = 15;
+const MAX_ACCOUNT_FAILURES: i64 = 5;
+const MAX_IP_FAILURES: i64 = 20;
+const LOCKOUT_MINUTES: i64 = 15;
+const MAX_DELAY_SECONDS: u64 = 8;
+
+pub fn client_ip(req: &HttpRequest) -> String {
+    req.connection_info()
+        .realip_remote_addr()
+        .unwrap_or("unknown")
+        .to_string()
+}
+
+/// Stores the attempt before the password is checked and returns the
+/// seconds left until the email or IP may try again, or `None` if the
+/// attempt is allowed. Counting after storing means requests fired at once
+/// each see the others, so no more than the limit get through. Unknown
+/// emails are limited the same way, a lock does not reveal an account.
+pub async fn begin_attempt(pool: &MySqlPool, email: &str, ip: &str) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query("INSERT INTO login_attempts (email, ip_address) VALUES (?, ?)")
+        .bind(email)
+        .bind(ip)
+        .execute(pool)
+        .await?;
+
+    let (email_attempts, email_oldest, ip_attempts, ip_oldest): (i64, Option<i64>, i64, Option<i64>) = sqlx::query_as(
+        "SELECT \
+         COUNT(IF(email = ?, 1, NULL)), MIN(IF(email = ?, TIMESTAMPDIFF(SECOND, NOW(), DATE_ADD(attempted_at, INTERVAL ? MINUTE)), NULL)), \
+         COUNT(IF(ip_address = ?, 1, NULL)), MIN(IF(ip_address = ?, TIMESTAMPDIFF(SECOND, NOW(), DATE_ADD(attempted_at, INTERVAL ? MINUTE)), NULL)) \
+         FROM login_attempts WHERE (email = ? OR ip_address = ?) AND attempted_at > DATE_SUB(NOW(), INTERVAL ? MINUTE)",
+    )
+    .bind(email)
+    .bind(email)
+    .bind(WINDOW_MINUTES)
+    .bind(ip)
+    .bind(ip)
+    .bind(WINDOW_MINUTES)
+    .bind(email)
+    .bind(ip)
+    .bind(WINDOW_MINUTES)
+    .fetch_one(pool)
+    .await?;
+    // this attempt is one of them
+    if email_attempts > MAX_ACCOUNT_FAILURES {
+        return Ok(Some(email_oldest.unwrap_or(WINDOW_MINUTES * 60).max(1)));
+    }
+    if ip_attempts > MAX_IP_FAILURES {
+        return Ok(Some(ip_oldest.unwrap_or(WINDOW_MINUTES * 60).max(1)));
+    }
+    Ok(None)
+}
+
+/// Handles a failed attempt stored by `begin_attempt`: marks the account as
+/// locked once it reaches the limit, which notifies the user once, and waits
+/// a delay that grows with every consecutive failure.
+pub async fn record_failure(pool: &MySqlPool, email: &str, ip: &str) -> Result<(), sqlx::Error> {
+    let failures: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM login_attempts WHERE email = ? AND attempted_at > DATE_SUB(NOW(), INTERVAL ? MINUTE)",
+    )
+    .bind(email)
+    .bind(WINDOW_MINUTES)
+    .fetch_one(pool)
+    .await?;
+
+    if failures >= MAX_ACCOUNT_FAILURES {
+        let locked = sqlx::query(
+            "UPDATE users SET locked_until = DATE_ADD(NOW(), INTERVAL ? MINUTE) \
+             WHERE email = ? AND (locked_until IS NULL OR locked_until < NOW())",
+        )
+        .bind(LOCKOUT_MINUTES)
+        .bind(email)
+        .execute(pool)
+        .await?;
+
+        if locked.rows_affected() > 0 {
+            log::warn!("Account {} locked after {} failed attempts (last from {})", email, failures, ip);
+
+            let full_name: Option<String> = sqlx::query_scalar(
+                "SELECT pd.full_name FROM personal_data pd JOIN users u ON u.id = pd.user_id WHERE u.email = ?",
+            )
+            .bind(email)
+            .fetch_optional(pool)
+            .await?;
+
+            // sent in the background, the failed login answers with the same delay as always
+            let email = email.to_string();
+            let ip = ip.to_string();
+            actix_web::rt::spawn(schools::scope(schools::current(), async move {
+                send_account_locked_email(
+                    email.clone(),
+                    full_name.as_deref().unwrap_or(&email),
+                    &ip,
+                    LOCKOUT_MINUTES,
+                )
+                .await;
+            }));
+        }
+    }
+
+    // 1s, 2s, 4s, 8s, 8s...
+    let exponent = (failures - 1).clamp(0, 3) as u32;
+    sleep(Duration::from_secs(2u64.pow(exponent).min(MAX_DELAY_SECONDS))).await;
+
+    Ok(())
+}
+
+pub async fn record_success(pool: &MySqlPool, email: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM login_attempts WHERE email = ?")
+        .bind(email)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn unlock_account(pool: &MySqlPool, user_id: u64) -> Result<bool, sqlx::Error> {
+    let email: Option<String> = sqlx::query_scalar("SELECT email FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+    let email = match email {
+        Some(e) => e,
+        None => return Ok(false),
+    };
+
+    sqlx::query("UPDATE users SET locked_until = NULL WHERE id = ?")
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    record_success(pool, &email).await?;
+
+    Ok(true)
+}
+
+pub async fn delete_old_attempts(pool: &MySqlPool) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query("DELETE FROM login_attempts WHERE attempted_at < DATE_SUB(NOW(), INTERVAL 1 DAY)")
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected())
+}
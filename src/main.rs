@@ -1,51 +1,167 @@
 use actix_cors::Cors;
 use actix_files::Files;
-use actix_web::{App, HttpServer, middleware::Logger, web};
-use chrono::Datelike;
+use actix_web::{App, HttpServer, middleware::{from_fn, Logger}, web};
 use env_logger;
-use sqlx::mysql::MySqlPool;
 
+mod api_tokens;
+mod audit;
+mod auth;
+mod config;
 mod cron;
 mod filters;
 mod parse_multipart;
+mod passwords;
 mod impls;
 mod json;
 mod jwt;
+mod keyring;
+mod login_guard;
+mod migrations;
+mod report_card;
+mod rollover;
 mod routes;
+mod schools;
+mod sessions;
 mod structs;
+mod totp;
 mod traits;
+mod user_import;
 mod views;
 mod email;
 mod websocket;
+mod years;
 
 use jwt::Claims;
 
 use cron::start_cron_task;
 use routes::register_services;
-use websocket::ChatConnectionManager;
+use schools::{resolve_school, Tenant, Tenants, UPLOAD_DIRS};
+use years::{reject_past_year_writes, Years};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv::dotenv().ok();
-    let mut db_url = std::env::var("DATABASE_URL").expect("database url should be setted");
-    let actual_year = chrono::Utc::now().year();
-    db_url.push_str(actual_year.to_string().as_str());
 
-    let pool = MySqlPool::connect(&db_url)
-        .await
-        .expect("Failed to connect to database");
+    let config = match config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
-    let pool_for_cron = pool.clone();
-    tokio::spawn(async move {
-        start_cron_task(pool_for_cron).await;
-    });
+    // `goschool rotate-keys` adds a new JWT signing key and exits
+    if std::env::args().nth(1).as_deref() == Some("rotate-keys") {
+        match keyring::rotate() {
+            Ok(kid) => println!("New JWT signing key: {}", kid),
+            Err(e) => {
+                eprintln!("Failed to rotate JWT keys: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
 
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
-    let json_conf = json::json_config();
 
-    // Initialize WebSocket connection manager for chat
-    let chat_manager = web::Data::new(ChatConnectionManager::new());
-    log::info!("Chat WebSocket manager initialized");
+    let years = Years::from_url(&config.database_url).expect("Invalid DATABASE_URL");
+    let schools = schools::load().expect("Invalid schools");
+
+    // `goschool migrations` lists the migrations of every school and exits
+    // with 1 if any is pending, `goschool migrate` applies them and exits
+    let migration_command = std::env::args()
+        .nth(1)
+        .filter(|c| c == "migrations" || c == "migrate");
+    let auto_migrate = config.auto_migrate;
+    let mut pending_migrations = 0;
+
+    // shared by every worker, so each past year and chat connection is
+    // tracked once per school
+    let mut tenants = Vec::new();
+    let mut upload_dirs = Vec::new();
+    for school in schools {
+        let tenant = Tenant::connect(school, &years)
+            .await
+            .expect("Failed to connect to database");
+        log::info!("School {}: academic year {}", tenant.school.id, tenant.active_year.0);
+
+        // every year of the school, past ones on a writable pool of their own
+        // since the ones the service reads them with are read-only
+        let active_year = tenant.active_year.0;
+        let mut databases = vec![(active_year, tenant.pool.get_ref().clone())];
+        for year in tenant.years.existing().await.expect("Failed to list academic years") {
+            if year != active_year {
+                let pool = sqlx::MySqlPool::connect_with(tenant.years.connect_options(year))
+                    .await
+                    .expect("Failed to connect to database");
+                databases.push((year, pool));
+            }
+        }
+
+        for (year, pool) in &databases {
+            match migration_command.as_deref() {
+                Some("migrations") => {
+                    let status = migrations::status(pool).await.expect("Failed to read migrations");
+                    for s in &status {
+                        match s.applied_at {
+                            Some(at) => println!("{} {} {:04} {} applied {}", tenant.school.id, year, s.migration.version, s.migration.name, at),
+                            None => println!("{} {} {:04} {} PENDING", tenant.school.id, year, s.migration.version, s.migration.name),
+                        }
+                    }
+                    pending_migrations += status.iter().filter(|s| s.applied_at.is_none()).count();
+                }
+                Some(_) => {
+                    migrations::run(pool).await.expect("Failed to apply migrations");
+                }
+                None if auto_migrate => {
+                    migrations::run(pool).await.expect("Failed to apply migrations");
+                }
+                None => {
+                    let pending = migrations::pending(pool).await.expect("Failed to read migrations");
+                    if let Some(first) = pending.first() {
+                        panic!(
+                            "School {} has {} pending migrations in {} from {:04} ({}), run `goschool migrate`",
+                            tenant.school.id,
+                            pending.len(),
+                            year,
+                            first.version,
+                            first.name
+                        );
+                    }
+                }
+            }
+        }
+        for (_, pool) in databases.into_iter().skip(1) {
+            pool.close().await;
+        }
+        if migration_command.is_some() {
+            continue;
+        }
+
+        let pool_for_cron = tenant.pool.get_ref().clone();
+        tokio::spawn(schools::scope(tenant.school.clone().into_inner(), async move {
+            start_cron_task(pool_for_cron).await;
+        }));
+
+        for dir in UPLOAD_DIRS {
+            let dir = tenant.school.upload_dir(dir);
+            std::fs::create_dir_all(format!("{}/{}", config.uploads_dir(), dir))
+                .expect("Failed to create upload directory");
+            upload_dirs.push(dir);
+        }
+        tenants.push(tenant);
+    }
+    if migration_command.is_some() {
+        std::process::exit(if pending_migrations > 0 { 1 } else { 0 });
+    }
+    let tenants = web::Data::new(Tenants(tenants));
+
+    let json_conf = json::json_config(config.json_limit_bytes);
+    let uploads_dir = config.uploads_dir();
+    let app_config = web::Data::new(config.clone());
+
+    keyring::reload().expect("Failed to load JWT keys");
+    log::info!("JWT keyring loaded");
 
     HttpServer::new(move || {
         let cors = Cors::default()
@@ -54,19 +170,20 @@ async fn main() -> std::io::Result<()> {
             .allow_any_header()
             .supports_credentials();
 
-        App::new()
+        let mut app = App::new()
+            .wrap(from_fn(reject_past_year_writes))
+            .wrap(from_fn(resolve_school))
             .wrap(Logger::default())
             .wrap(cors)
-            .app_data(web::Data::new(pool.clone()))
-            .app_data(chat_manager.clone())
-            .app_data(json_conf.clone())
-            .service(Files::new("/uploads/profile_pictures", "./uploads/profile_pictures").index_file("404"))
-            .service(Files::new("/uploads/files", "./uploads/files").index_file("404"))
-            .service(Files::new("/uploads/submissions", "./uploads/submissions").index_file("404"))
-            .service(Files::new("/uploads/chat_files", "./uploads/chat_files").index_file("404"))
-            .configure(register_services)
+            .app_data(tenants.clone())
+            .app_data(app_config.clone())
+            .app_data(json_conf.clone());
+        for dir in &upload_dirs {
+            app = app.service(Files::new(&format!("/uploads/{}", dir), format!("{}/{}", uploads_dir, dir)).index_file("404"));
+        }
+        app.configure(register_services)
     })
-    .bind("0.0.0.0:80")?
+    .bind(&config.bind_address)?
     .run()
     .await
 }
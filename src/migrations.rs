@@ -0,0 +1,200 @@
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::pool::PoolConnection;
+use sqlx::{MySql, MySqlPool};
+
+/// A file of `migrations/`, embedded in the binary.
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    sql: &'static str,
+}
+
+/// Every schema change, oldest first. A new one goes in a new file with the
+/// next version; applied files are never edited, their checksum is checked.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        sql: include_str!("../migrations/0001_initial.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "accounts_and_audit",
+        sql: include_str!("../migrations/0002_accounts_and_audit.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "chat",
+        sql: include_str!("../migrations/0003_chat.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "grading_periods",
+        sql: include_str!("../migrations/0004_grading_periods.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "report_card_comments",
+        sql: include_str!("../migrations/0005_report_card_comments.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "assessment_type_weights",
+        sql: include_str!("../migrations/0006_assessment_type_weights.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "grading_scales",
+        sql: include_str!("../migrations/0007_grading_scales.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "audit_impersonator",
+        sql: include_str!("../migrations/0008_audit_impersonator.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "year_rollovers",
+        sql: include_str!("../migrations/0009_year_rollovers.sql"),
+    },
+];
+
+/// How long `run` waits for another instance applying the migrations.
+const LOCK_TIMEOUT_SECONDS: i64 = 300;
+
+pub struct MigrationStatus {
+    pub migration: &'static Migration,
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+fn checksum(sql: &str) -> String {
+    hex::encode(Sha256::digest(sql.as_bytes()))
+}
+
+async fn ensure_table(conn: &mut PoolConnection<MySql>) -> Result<(), sqlx::Error> {
+    sqlx::raw_sql(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+           version INT UNSIGNED PRIMARY KEY,
+           name VARCHAR(255) NOT NULL,
+           checksum CHAR(64) NOT NULL,
+           applied_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+         )",
+    )
+    .execute(&mut **conn)
+    .await?;
+    Ok(())
+}
+
+/// State of every migration in the database. Fails if an applied migration
+/// changed or the database was migrated by a newer build.
+async fn statuses(conn: &mut PoolConnection<MySql>) -> anyhow::Result<Vec<MigrationStatus>> {
+    ensure_table(conn).await?;
+    let applied: Vec<(u32, String, DateTime<Utc>)> =
+        sqlx::query_as("SELECT version, checksum, applied_at FROM schema_migrations ORDER BY version")
+            .fetch_all(&mut **conn)
+            .await?;
+
+    for (version, applied_checksum, _) in &applied {
+        match MIGRATIONS.iter().find(|m| m.version == *version) {
+            Some(m) if checksum(m.sql) != *applied_checksum => {
+                anyhow::bail!("Migration {} ({}) changed after it was applied", m.version, m.name)
+            }
+            Some(_) => {}
+            None => anyhow::bail!(
+                "The database has migration {}, unknown to this build. It was migrated by a newer version.",
+                version
+            ),
+        }
+    }
+
+    Ok(MIGRATIONS
+        .iter()
+        .map(|m| MigrationStatus {
+            migration: m,
+            applied_at: applied.iter().find(|(v, _, _)| *v == m.version).map(|(_, _, at)| *at),
+        })
+        .collect())
+}
+
+pub async fn status(pool: &MySqlPool) -> anyhow::Result<Vec<MigrationStatus>> {
+    let mut conn = pool.acquire().await?;
+    statuses(&mut conn).await
+}
+
+/// Migrations not applied yet, oldest first.
+pub async fn pending(pool: &MySqlPool) -> anyhow::Result<Vec<&'static Migration>> {
+    Ok(status(pool)
+        .await?
+        .into_iter()
+        .filter(|s| s.applied_at.is_none())
+        .map(|s| s.migration)
+        .collect())
+}
+
+/// Applies the pending migrations and returns them. MySQL commits DDL on
+/// its own, so a migration is recorded once all its statements ran and a
+/// failed one is retried from the start: they must be safe to run again.
+pub async fn run(pool: &MySqlPool) -> anyhow::Result<Vec<&'static Migration>> {
+    // one connection, the migrations use session variables and prepared statements
+    let mut conn = pool.acquire().await?;
+
+    // instances starting together wait for the first one, then find nothing
+    // pending; the lock is held by the session, so it is released by hand
+    // before the connection goes back to the pool
+    let locked: Option<i64> = sqlx::query_scalar("SELECT GET_LOCK('schema_migrations', ?)")
+        .bind(LOCK_TIMEOUT_SECONDS)
+        .fetch_one(&mut *conn)
+        .await?;
+    if locked != Some(1) {
+        anyhow::bail!("Timed out waiting for another instance to finish the migrations");
+    }
+    let applied = apply_pending(&mut conn).await;
+    sqlx::query("DO RELEASE_LOCK('schema_migrations')")
+        .execute(&mut *conn)
+        .await?;
+    applied
+}
+
+async fn apply_pending(conn: &mut PoolConnection<MySql>) -> anyhow::Result<Vec<&'static Migration>> {
+    let pending: Vec<&Migration> = statuses(conn)
+        .await?
+        .into_iter()
+        .filter(|s| s.applied_at.is_none())
+        .map(|s| s.migration)
+        .collect();
+
+    for migration in &pending {
+        for statement in statements(migration.sql) {
+            sqlx::raw_sql(&statement)
+                .execute(&mut **conn)
+                .await
+                .map_err(|e| anyhow::anyhow!("Migration {} ({}) failed: {}", migration.version, migration.name, e))?;
+        }
+        sqlx::query("INSERT INTO schema_migrations (version, name, checksum) VALUES (?, ?, ?)")
+            .bind(migration.version)
+            .bind(migration.name)
+            .bind(checksum(migration.sql))
+            .execute(&mut **conn)
+            .await?;
+        log::info!("Applied migration {} ({})", migration.version, migration.name);
+    }
+    Ok(pending)
+}
+
+/// Splits a migration on `;`, dropping `--` comment lines. Migrations have
+/// no procedures, so no statement contains a `;` of its own.
+fn statements(script: &str) -> Vec<String> {
+    let without_comments: String = script
+        .lines()
+        .filter(|l| !l.trim_start().starts_with("--"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    without_comments
+        .split(';')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect()
+}
@@ -2,19 +2,20 @@ use actix_multipart::Multipart;
 use futures_util::StreamExt;
 use sanitize_filename::sanitize;
 use std::collections::HashMap;
-use std::env;
 use std::io::Write;
 use tempfile::NamedTempFile;
 use uuid::Uuid;
 
-/// Tamaño máximo permitido para subir archivos: 10 MB
-const MAX_FILE_SIZE: usize = 10 * 1024 * 1024;
+use crate::config;
+use crate::schools;
 
+/// `upload_dir` es uno de `schools::UPLOAD_DIRS`, el archivo se guarda en
+/// `uploads_dir` dentro del directorio de la escuela.
 pub async fn parse_multipart<'a>(
     mut multipart: Multipart,
     supported_extensions: Option<&[&str]>,
     supported_mime_types: Option<&[&str]>,
-    upload_path: &'a str,
+    upload_dir: &'a str,
 ) -> Result<HashMap<String, Vec<u8>>, &'a str> {
     let mut fields = HashMap::new();
 
@@ -58,9 +59,10 @@ pub async fn parse_multipart<'a>(
 
             // Nombre único para almacenar (UUID + extensión)
             let unique_name = format!("{}.{}", Uuid::new_v4(), extension);
-            let base_path = env::var("BASE_PATH").unwrap();
-            dbg!(&base_path); // Imprime el valor de BASE_PATH
-            let upload_path_str = format!("{}/{}/{}", base_path, upload_path, unique_name);
+            let config = config::get();
+            let school = schools::current();
+            let upload_dir = school.upload_dir(upload_dir);
+            let upload_path_str = format!("{}/{}/{}", config.uploads_dir(), upload_dir, unique_name);
             dbg!(&upload_path_str); // Imprime la ruta completa de guardado
 
             // Crear archivo temporal
@@ -72,8 +74,8 @@ pub async fn parse_multipart<'a>(
             while let Some(chunk) = field.next().await {
                 let chunk = chunk.map_err(|_| "Failed to read chunk")?;
                 total_size += chunk.len();
-                if total_size > MAX_FILE_SIZE {
-                    return Err("File too large. Limit is 10MB");
+                if total_size > config.max_upload_bytes {
+                    return Err("File too large");
                 }
 
                 temp_file
@@ -119,8 +121,8 @@ pub async fn parse_multipart<'a>(
 
             // Guardar en el HashMap la ruta del archivo guardado (como string en bytes)
 
-            let url = env::var("BASE_URL").expect("BASE_URL must be set");
-            let path = format!("{}{}/{}", url, upload_path, unique_name);
+            let url = school.base_url();
+            let path = format!("{}uploads/{}/{}", url, upload_dir, unique_name);
             dbg!(&path);
             fields.insert("file".to_string(), path.into_bytes());
         } else {
@@ -135,3 +137,13 @@ pub async fn parse_multipart<'a>(
 
     Ok(fields)
 }
+
+/// Ruta en disco de un archivo guardado por `parse_multipart`, a partir de
+/// su URL.
+pub fn stored_path(url: &str) -> Option<String> {
+    let relative = url.strip_prefix(&schools::current().base_url())?.strip_prefix("uploads/")?;
+    if relative.split('/').any(|part| part == "..") {
+        return None;
+    }
+    Some(format!("{}/{}", config::get().uploads_dir(), relative))
+}
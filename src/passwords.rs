@@ -0,0 +1,27 @@
+use bcrypt::{hash, verify, DEFAULT_COST};
+use sqlx::MySqlPool;
+
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// Basic policy applied to every password chosen by a user.
+pub fn check_password_strength(password: &str) -> Result<(), String> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(format!("Password must be at least {} characters long", MIN_PASSWORD_LENGTH));
+    }
+    if !password.chars().any(|c| c.is_alphabetic()) || !password.chars().any(|c| c.is_numeric()) {
+        return Err("Password must contain letters and numbers".to_string());
+    }
+    Ok(())
+}
+
+pub fn hash_password(password: &str) -> Result<String, bcrypt::BcryptError> {
+    hash(password, DEFAULT_COST)
+}
+
+pub async fn verify_current_password(pool: &MySqlPool, user_id: u64, password: &str) -> Result<bool, sqlx::Error> {
+    let hashed_pass: String = sqlx::query_scalar("SELECT password FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(verify(password, &hashed_pass).unwrap_or(false))
+}
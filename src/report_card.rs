@@ -0,0 +1,286 @@
+use chrono::{NaiveDate, Utc};
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+use rust_decimal::Decimal;
+
+use crate::structs::ReportCard;
+
+// A4 in points
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 50.0;
+
+const REGULAR: Name = Name(b"F1");
+const BOLD: Name = Name(b"F2");
+
+// x of the columns of the grades table
+const SUBJECT_X: f32 = MARGIN;
+const GRADES_X: f32 = 220.0;
+const AVERAGE_X: f32 = 420.0;
+const FINAL_X: f32 = 490.0;
+
+/// Content streams of the pages, filled top to bottom.
+struct Pages {
+    done: Vec<Vec<u8>>,
+    content: Content,
+    y: f32,
+}
+
+impl Pages {
+    fn new() -> Pages {
+        Pages { done: Vec::new(), content: Content::new(), y: PAGE_HEIGHT - MARGIN }
+    }
+
+    /// Starts a new page unless `height` still fits in this one.
+    fn reserve(&mut self, height: f32) {
+        if self.y - height < MARGIN {
+            let content = std::mem::replace(&mut self.content, Content::new());
+            self.done.push(content.finish());
+            self.y = PAGE_HEIGHT - MARGIN;
+        }
+    }
+
+    /// Writes `text` with its baseline `size` below the current position.
+    fn text(&mut self, x: f32, font: Name, size: f32, text: &str) {
+        self.content
+            .begin_text()
+            .set_font(font, size)
+            .next_line(x, self.y - size)
+            .show(Str(&win_ansi(text)))
+            .end_text();
+    }
+
+    fn rule(&mut self) {
+        self.content
+            .set_line_width(0.5)
+            .move_to(MARGIN, self.y)
+            .line_to(PAGE_WIDTH - MARGIN, self.y)
+            .stroke();
+    }
+
+    fn down(&mut self, height: f32) {
+        self.y -= height;
+    }
+
+    fn finish(mut self) -> Vec<Vec<u8>> {
+        self.done.push(self.content.finish());
+        self.done
+    }
+}
+
+/// Text in the WinAnsiEncoding of the standard fonts, which covers the
+/// Spanish letters. Other characters are replaced by `?`.
+fn win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            ' '..='~' | '\u{a0}'..='\u{ff}' => c as u8,
+            '€' => 0x80,
+            '…' => 0x85,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            _ => b'?',
+        })
+        .collect()
+}
+
+/// Splits `text` in lines of about `width` points, Helvetica letters being
+/// half their size wide on average.
+fn wrap(text: &str, width: f32, size: f32) -> Vec<String> {
+    let max_chars = (width / (size * 0.5)) as usize;
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > max_chars {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+fn date(d: NaiveDate) -> String {
+    d.format("%d/%m/%Y").to_string()
+}
+
+fn grade(g: Option<Decimal>) -> String {
+    g.map_or_else(|| "-".to_string(), |g| g.round_dp(2).to_string())
+}
+
+fn presence_label(presence: &str) -> &str {
+    match presence {
+        "present" => "Presentes",
+        "absent" => "Ausentes",
+        "excused" => "Ausentes justificadas",
+        "late" => "Llegadas tarde",
+        other => other,
+    }
+}
+
+fn sanction_label(sanction_type: &str) -> &str {
+    match sanction_type {
+        "admonition" => "Amonestación",
+        "warning" => "Apercibimiento",
+        "free" => "Libre",
+        other => other,
+    }
+}
+
+fn layout(card: &ReportCard, school_name: &str) -> Vec<Vec<u8>> {
+    let mut pages = Pages::new();
+
+    pages.text(MARGIN, BOLD, 18.0, "Boletín de calificaciones");
+    pages.down(24.0);
+    pages.text(MARGIN, REGULAR, 12.0, school_name);
+    pages.down(26.0);
+    pages.text(MARGIN, REGULAR, 11.0, &format!("Alumno: {}", card.student_name));
+    pages.down(15.0);
+    if let Some(course) = &card.course_name {
+        pages.text(MARGIN, REGULAR, 11.0, &format!("Curso: {}", course));
+        pages.down(15.0);
+    }
+    pages.text(
+        MARGIN,
+        REGULAR,
+        11.0,
+        &format!("Período: {} ({} al {})", card.period.name, date(card.period.start_date), date(card.period.end_date)),
+    );
+    pages.down(28.0);
+
+    pages.text(SUBJECT_X, BOLD, 10.0, "Materia");
+    pages.text(GRADES_X, BOLD, 10.0, "Notas");
+    pages.text(AVERAGE_X, BOLD, 10.0, "Promedio");
+    pages.text(FINAL_X, BOLD, 10.0, "Anual");
+    pages.down(15.0);
+    pages.rule();
+    pages.down(6.0);
+
+    for subject in &card.subjects {
+        let names = wrap(&subject.name, GRADES_X - SUBJECT_X - 10.0, 10.0);
+        let grades = wrap(&subject.grades.join("  "), AVERAGE_X - GRADES_X - 10.0, 10.0);
+        let comment = subject
+            .comment
+            .as_deref()
+            .map(|c| wrap(&format!("Observaciones: {}", c), PAGE_WIDTH - 2.0 * MARGIN - 10.0, 9.0))
+            .unwrap_or_default();
+        let rows = names.len().max(grades.len()).max(1);
+        pages.reserve(rows as f32 * 13.0 + comment.len() as f32 * 12.0 + 8.0);
+
+        let top = pages.y;
+        for (i, name) in names.iter().enumerate() {
+            pages.y = top - i as f32 * 13.0;
+            pages.text(SUBJECT_X, REGULAR, 10.0, name);
+        }
+        for (i, line) in grades.iter().enumerate() {
+            pages.y = top - i as f32 * 13.0;
+            pages.text(GRADES_X, REGULAR, 10.0, line);
+        }
+        pages.y = top;
+        pages.text(AVERAGE_X, BOLD, 10.0, &grade(subject.period_average));
+        pages.text(FINAL_X, REGULAR, 10.0, &grade(subject.final_grade));
+        pages.y = top - rows as f32 * 13.0;
+        for line in &comment {
+            pages.text(SUBJECT_X + 10.0, REGULAR, 9.0, line);
+            pages.down(12.0);
+        }
+        pages.down(4.0);
+        pages.rule();
+        pages.down(6.0);
+    }
+    if card.subjects.is_empty() {
+        pages.text(SUBJECT_X, REGULAR, 10.0, "Sin materias");
+        pages.down(14.0);
+    }
+
+    pages.down(14.0);
+    pages.reserve(20.0 + card.attendance.len().max(1) as f32 * 14.0);
+    pages.text(MARGIN, BOLD, 12.0, "Asistencia");
+    pages.down(18.0);
+    for (presence, days) in &card.attendance {
+        pages.text(MARGIN, REGULAR, 10.0, &format!("{}: {}", presence_label(presence), days));
+        pages.down(14.0);
+    }
+    if card.attendance.is_empty() {
+        pages.text(MARGIN, REGULAR, 10.0, "Sin registros");
+        pages.down(14.0);
+    }
+
+    pages.down(14.0);
+    pages.reserve(34.0);
+    pages.text(MARGIN, BOLD, 12.0, "Sanciones disciplinarias");
+    pages.down(18.0);
+    for sanction in &card.sanctions {
+        let text = format!(
+            "{}  {} x{}  {}",
+            date(sanction.date),
+            sanction_label(&sanction.sanction_type),
+            sanction.quantity,
+            sanction.description
+        );
+        let lines = wrap(&text, PAGE_WIDTH - 2.0 * MARGIN, 10.0);
+        pages.reserve(lines.len() as f32 * 14.0);
+        for line in &lines {
+            pages.text(MARGIN, REGULAR, 10.0, line);
+            pages.down(14.0);
+        }
+    }
+    if card.sanctions.is_empty() {
+        pages.text(MARGIN, REGULAR, 10.0, "Sin sanciones");
+        pages.down(14.0);
+    }
+
+    pages.down(20.0);
+    pages.reserve(12.0);
+    pages.text(MARGIN, REGULAR, 8.0, &format!("Emitido el {}", date(Utc::now().date_naive())));
+
+    pages.finish()
+}
+
+/// The report card as a PDF, in the standard Helvetica fonts so nothing has
+/// to be embedded.
+pub fn render(card: &ReportCard, school_name: &str) -> Vec<u8> {
+    let pages = layout(card, school_name);
+
+    let catalog_id = Ref::new(1);
+    let tree_id = Ref::new(2);
+    let regular_id = Ref::new(3);
+    let bold_id = Ref::new(4);
+    let info_id = Ref::new(5);
+    let page_ids: Vec<Ref> = (0..pages.len()).map(|i| Ref::new(6 + 2 * i as i32)).collect();
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(tree_id);
+    pdf.pages(tree_id).kids(page_ids.iter().copied()).count(pages.len() as i32);
+    pdf.document_info(info_id)
+        .title(TextStr(&format!("Boletín {} - {}", card.student_name, card.period.name)))
+        .creator(TextStr(school_name));
+
+    for (page_id, content) in page_ids.iter().zip(&pages) {
+        let content_id = Ref::new(page_id.get() + 1);
+        let mut page = pdf.page(*page_id);
+        page.parent(tree_id)
+            .media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT))
+            .contents(content_id);
+        page.resources().fonts().pair(REGULAR, regular_id).pair(BOLD, bold_id);
+        page.finish();
+        pdf.stream(content_id, content);
+    }
+
+    pdf.type1_font(regular_id)
+        .base_font(Name(b"Helvetica"))
+        .encoding_predefined(Name(b"WinAnsiEncoding"));
+    pdf.type1_font(bold_id)
+        .base_font(Name(b"Helvetica-Bold"))
+        .encoding_predefined(Name(b"WinAnsiEncoding"));
+
+    pdf.finish()
+}
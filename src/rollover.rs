@@ -0,0 +1,227 @@
+use std::collections::{HashMap, HashSet};
+
+use sqlx::{Connection, MySqlConnection, MySqlPool};
+
+use crate::migrations;
+use crate::structs::{CoursePromotion, RolloverReport, RolloverRequest};
+use crate::years::Years;
+
+/// Tables carried over to the new year, parents first. Grades, attendance,
+/// messages and the rest stay in the year they belong to.
+const CARRIED_TABLES: &[&str] = &[
+    "users",
+    "roles",
+    "personal_data",
+    "families",
+    "courses",
+    "subjects",
+    "timetables",
+    "user_totp",
+    "totp_recovery_codes",
+    "two_factor_policies",
+    "api_tokens",
+    "assessment_type_weights",
+    "grading_scales",
+    "grading_scale_labels",
+    "year_rollovers",
+];
+
+#[derive(sqlx::FromRow)]
+struct CourseRow {
+    id: u64,
+    year: i32,
+    division: String,
+    level: String,
+    name: String,
+}
+
+/// Where each student goes: `Some(course)` or `None` for graduates.
+struct Plan {
+    report: RolloverReport,
+    moves: Vec<(u64, Option<u64>)>,
+}
+
+/// Next course of the same level and division, the first secondary course
+/// after the last primary one, and no course (graduation) after the last
+/// secondary one. `course_map` overrides it per course.
+fn next_course(course: &CourseRow, courses: &[CourseRow]) -> Option<u64> {
+    let same_level = courses
+        .iter()
+        .find(|c| c.level == course.level && c.division == course.division && c.year == course.year + 1);
+    if let Some(c) = same_level {
+        return Some(c.id);
+    }
+    if course.level == "primary" {
+        return courses
+            .iter()
+            .filter(|c| c.level == "secondary" && c.division == course.division)
+            .min_by_key(|c| c.year)
+            .map(|c| c.id);
+    }
+    None
+}
+
+async fn plan(pool: &MySqlPool, from_year: i32, request: &RolloverRequest) -> Result<Plan, sqlx::Error> {
+    let courses: Vec<CourseRow> = sqlx::query_as("SELECT id, year, division, level, name FROM courses ORDER BY level, year, division")
+        .fetch_all(pool)
+        .await?;
+    let students: Vec<(u64, u64)> = sqlx::query_as(
+        "SELECT u.id, u.course_id FROM users u JOIN roles r ON r.user_id = u.id \
+         WHERE r.role = 'student' AND u.course_id IS NOT NULL",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let repeaters: HashSet<u64> = request.repeaters.iter().copied().collect();
+    let next: HashMap<u64, Option<u64>> = courses
+        .iter()
+        .map(|c| (c.id, request.course_map.get(&c.id).copied().unwrap_or_else(|| next_course(c, &courses))))
+        .collect();
+
+    let mut promotions: Vec<CoursePromotion> = courses
+        .iter()
+        .map(|c| CoursePromotion {
+            course_id: c.id,
+            course_name: c.name.clone(),
+            next_course_id: next[&c.id],
+            promoted: 0,
+            repeating: 0,
+            graduating: 0,
+        })
+        .collect();
+
+    let mut moves = Vec::new();
+    for (student_id, course_id) in students {
+        let Some(promotion) = promotions.iter_mut().find(|p| p.course_id == course_id) else {
+            continue;
+        };
+        if repeaters.contains(&student_id) {
+            promotion.repeating += 1;
+            continue;
+        }
+        match promotion.next_course_id {
+            Some(_) => promotion.promoted += 1,
+            None => promotion.graduating += 1,
+        }
+        moves.push((student_id, promotion.next_course_id));
+    }
+
+    Ok(Plan {
+        report: RolloverReport {
+            from_year,
+            to_year: from_year + 1,
+            promoted: promotions.iter().map(|p| p.promoted).sum(),
+            repeating: promotions.iter().map(|p| p.repeating).sum(),
+            graduating: promotions.iter().map(|p| p.graduating).sum(),
+            courses: promotions,
+        },
+        moves,
+    })
+}
+
+/// Computes the promotion of every student from `from_year` and, unless it
+/// is a dry run, creates the database of the next year with it.
+pub async fn rollover(
+    years: &Years,
+    pool: &MySqlPool,
+    from_year: i32,
+    request: &RolloverRequest,
+) -> anyhow::Result<RolloverReport> {
+    let plan = plan(pool, from_year, request).await?;
+    if request.dry_run {
+        return Ok(plan.report);
+    }
+
+    let to_year = from_year + 1;
+    if years.existing().await?.contains(&to_year) {
+        anyhow::bail!("The database for {} already exists", to_year);
+    }
+
+    let new_db = years.database_name(to_year);
+    sqlx::raw_sql(&format!("CREATE DATABASE `{}`", new_db))
+        .execute(pool)
+        .await?;
+
+    // DDL cannot be rolled back in MySQL, so a failure drops the new database
+    if let Err(e) = populate(years, from_year, to_year, &plan).await {
+        if let Err(drop_error) = sqlx::raw_sql(&format!("DROP DATABASE `{}`", new_db)).execute(pool).await {
+            log::error!("Failed to drop {} after a failed rollover: {}", new_db, drop_error);
+        }
+        return Err(e);
+    }
+
+    Ok(plan.report)
+}
+
+async fn populate(years: &Years, from_year: i32, to_year: i32, plan: &Plan) -> anyhow::Result<()> {
+    let new_pool = MySqlPool::connect_with(years.connect_options(to_year)).await?;
+    migrations::run(&new_pool).await?;
+    new_pool.close().await;
+
+    let old_db = years.database_name(from_year);
+    let new_db = years.database_name(to_year);
+
+    let mut conn = MySqlConnection::connect_with(&years.connect_options(to_year)).await?;
+    sqlx::raw_sql("SET FOREIGN_KEY_CHECKS = 0").execute(&mut conn).await?;
+    let mut tx = conn.begin().await?;
+
+    for table in CARRIED_TABLES {
+        // only the columns both years have, the old schema may be older, and
+        // no generated ones, MySQL computes them
+        let columns: Vec<String> = sqlx::query_scalar(
+            "SELECT n.COLUMN_NAME FROM information_schema.COLUMNS n \
+             JOIN information_schema.COLUMNS o ON o.TABLE_SCHEMA = ? AND o.TABLE_NAME = n.TABLE_NAME AND o.COLUMN_NAME = n.COLUMN_NAME \
+             WHERE n.TABLE_SCHEMA = ? AND n.TABLE_NAME = ? AND n.EXTRA NOT LIKE '%GENERATED%' ORDER BY n.ORDINAL_POSITION",
+        )
+        .bind(&old_db)
+        .bind(&new_db)
+        .bind(table)
+        .fetch_all(&mut *tx)
+        .await?;
+        if columns.is_empty() {
+            continue;
+        }
+
+        let columns = columns.iter().map(|c| format!("`{}`", c)).collect::<Vec<_>>().join(", ");
+        // the old year's rows replace the defaults the migrations inserted
+        sqlx::raw_sql(&format!("DELETE FROM `{new_db}`.`{table}`"))
+            .execute(&mut *tx)
+            .await?;
+        sqlx::raw_sql(&format!(
+            "INSERT INTO `{new_db}`.`{table}` ({columns}) SELECT {columns} FROM `{old_db}`.`{table}`"
+        ))
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for (student_id, course_id) in &plan.moves {
+        sqlx::query("UPDATE users SET course_id = ? WHERE id = ?")
+            .bind(course_id)
+            .bind(student_id)
+            .execute(&mut *tx)
+            .await?;
+
+        // graduates keep their account, without the student role
+        if course_id.is_none() {
+            sqlx::query("DELETE FROM roles WHERE user_id = ? AND role = 'student'")
+                .bind(student_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DELETE FROM families WHERE student_id = ?")
+                .bind(student_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+
+    // links the new year to the old one, see `YearPools::linked`
+    sqlx::query("INSERT INTO year_rollovers (from_year) VALUES (?)")
+        .bind(from_year)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    sqlx::raw_sql("SET FOREIGN_KEY_CHECKS = 1").execute(&mut conn).await?;
+    conn.close().await?;
+    Ok(())
+}
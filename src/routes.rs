@@ -10,7 +10,7 @@ use crate::views::{
     },
 
     // Courses
-    courses::get_courses,
+    courses::{get_courses, post_course, update_course, delete_course},
 
     // Answer checks
     get_if_answered::{
@@ -18,10 +18,25 @@ use crate::views::{
         get_if_selfassessable_answered,
     },
 
+    // Grading periods
+    grading_periods::{get_grading_periods, post_grading_period, update_grading_period, delete_grading_period},
+
+    // Grading scales
+    grading_scales::{get_grading_scales, update_grading_scale, post_grade_label, update_grade_label, delete_grade_label},
+
+    // Report cards
+    report_cards::{get_report_card, post_report_card_comment},
+
+    // Assessment weights
+    assessment_weights::{get_assessment_weights, post_assessment_weights},
+
     // Grades
     grades::{
         delete_grade,
+        get_grade_averages,
+        get_grade_statistics,
         get_grades,
+        get_subject_statistics,
         post_grade,
         update_grade,
     },
@@ -32,12 +47,58 @@ use crate::views::{
     // Auth
     login::login,
     logout::logout,
+    refresh::refresh,
+    password_reset::{request_password_reset, confirm_password_reset},
+
+    // Account settings
+    account::{change_password, change_email, confirm_email_change},
+
+    // Two-factor authentication
+    two_factor::{
+        setup_two_factor,
+        enable_two_factor,
+        disable_two_factor,
+        regenerate_two_factor_recovery_codes,
+        login_two_factor_setup,
+        login_two_factor,
+        get_two_factor_policies,
+        update_two_factor_policy,
+        reset_user_two_factor,
+    },
     register::{register, register_testing_users},
+    invitations::{accept_invitation, resend_invitation},
+    user_import::import_users,
     verify_token::verify_token,
 
+    // Sessions
+    sessions::{get_sessions, delete_session, delete_user_sessions},
+
+    // User administration
+    users::unlock_user,
+    jwt_keys::{get_jwt_keys, rotate_jwt_keys},
+
+    // Audit log
+    audit_log::get_audit_log,
+
+    // Service accounts
+    api_tokens::{
+        create_service_account,
+        get_service_accounts,
+        create_api_token,
+        get_api_tokens,
+        delete_api_token,
+    },
+
+    // Academic year
+    academic_years::get_academic_years,
+    rollover::post_rollover,
+
+    // Impersonation
+    impersonation::{start_impersonation, get_impersonation, stop_impersonation},
+
     // Roles
     role::get_role,
-    roles::get_roles,
+    roles::{get_roles, get_my_roles, switch_role},
 
     // Selfassessables
     selfassessables::{
@@ -50,7 +111,7 @@ use crate::views::{
     students::get_students,
 
     // Subjects
-    subjects::get_subjects,
+    subjects::{get_subjects, post_subject, update_subject, delete_subject},
 
     // Subject messages
     subject_messages::{
@@ -87,6 +148,14 @@ use crate::views::{
         delete_disciplinary_sanction,
     },
 
+    // Families
+    families::{
+        get_families,
+        post_family,
+        update_family,
+        delete_family,
+    },
+
     // Personal data
     personal_data::{
         get_personal_data,
@@ -133,10 +202,29 @@ use crate::websocket::chat_websocket;
 pub fn register_services(cfg: &mut web::ServiceConfig) {
     cfg.service(get_assessments)
         .service(get_courses)
+        .service(post_course)
+        .service(update_course)
+        .service(delete_course)
         .service(get_grades)
         .service(post_grade)
         .service(update_grade)
         .service(delete_grade)
+        .service(get_grade_averages)
+        .service(get_grade_statistics)
+        .service(get_subject_statistics)
+        .service(get_grading_periods)
+        .service(post_grading_period)
+        .service(update_grading_period)
+        .service(delete_grading_period)
+        .service(get_grading_scales)
+        .service(update_grading_scale)
+        .service(post_grade_label)
+        .service(update_grade_label)
+        .service(delete_grade_label)
+        .service(get_report_card)
+        .service(post_report_card_comment)
+        .service(get_assessment_weights)
+        .service(post_assessment_weights)
         .service(get_messages)
         .service(post_message)
         .service(update_message)
@@ -151,10 +239,47 @@ pub fn register_services(cfg: &mut web::ServiceConfig) {
         .service(update_self_profile_picture)
         .service(get_role)
         .service(get_roles)
+        .service(get_my_roles)
+        .service(switch_role)
         .service(get_students)
         .service(get_subjects)
+        .service(post_subject)
+        .service(update_subject)
+        .service(delete_subject)
         .service(login)
         .service(logout)
+        .service(refresh)
+        .service(request_password_reset)
+        .service(confirm_password_reset)
+        .service(change_password)
+        .service(change_email)
+        .service(confirm_email_change)
+        .service(setup_two_factor)
+        .service(enable_two_factor)
+        .service(disable_two_factor)
+        .service(regenerate_two_factor_recovery_codes)
+        .service(login_two_factor_setup)
+        .service(login_two_factor)
+        .service(get_two_factor_policies)
+        .service(update_two_factor_policy)
+        .service(reset_user_two_factor)
+        .service(get_sessions)
+        .service(delete_session)
+        .service(delete_user_sessions)
+        .service(unlock_user)
+        .service(get_jwt_keys)
+        .service(rotate_jwt_keys)
+        .service(get_audit_log)
+        .service(create_service_account)
+        .service(get_service_accounts)
+        .service(create_api_token)
+        .service(get_api_tokens)
+        .service(delete_api_token)
+        .service(get_academic_years)
+        .service(post_rollover)
+        .service(start_impersonation)
+        .service(get_impersonation)
+        .service(stop_impersonation)
         .service(post_assessment)
         .service(update_assessment)
         .service(delete_assessment)
@@ -172,6 +297,9 @@ pub fn register_services(cfg: &mut web::ServiceConfig) {
         .service(post_profile_picture)
         .service(register)
         .service(register_testing_users)
+        .service(accept_invitation)
+        .service(resend_invitation)
+        .service(import_users)
         .service(get_timetable)
         .service(post_timetable)
         .service(update_timetable)
@@ -188,6 +316,10 @@ pub fn register_services(cfg: &mut web::ServiceConfig) {
         .service(post_disciplinary_sanction)
         .service(update_disciplinary_sanction)
         .service(delete_disciplinary_sanction)
+        .service(get_families)
+        .service(post_family)
+        .service(update_family)
+        .service(delete_family)
         // Chat WebSocket
         .service(chat_websocket)
         // Chat REST API
@@ -0,0 +1,269 @@
+use std::collections::HashSet;
+use std::fs::read_to_string;
+use std::future::Future;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{Extensions, ServiceRequest, ServiceResponse};
+use actix_web::error::{ErrorInternalServerError, InternalError};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpResponse};
+use serde::Deserialize;
+use sqlx::mysql::MySqlPool;
+
+use crate::auth::access_token;
+use crate::config;
+use crate::jwt;
+use crate::websocket::ChatConnectionManager;
+use crate::years::{ActiveYear, YearPools, Years};
+
+/// Id of the only school when `schools_file` is not set. It keeps the
+/// database names, upload directories and email templates of a single
+/// school deployment.
+pub const DEFAULT_SCHOOL: &str = "default";
+
+/// Upload directories, each one has a directory per school.
+pub const UPLOAD_DIRS: &[&str] = &["profile_pictures", "files", "submissions", "chat_files"];
+
+#[derive(Deserialize, Clone, Default)]
+pub struct Branding {
+    pub logo_url: Option<String>,
+    pub primary_color: Option<String>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct EmailSender {
+    pub from: String,
+    pub username: String,
+    pub password: String,
+    /// Defaults to `smtp_host`.
+    pub smtp_host: Option<String>,
+}
+
+/// A school served by this instance, see `schools_file`.
+#[derive(Deserialize, Clone)]
+pub struct School {
+    pub id: String,
+    pub name: String,
+    /// Hosts the school is reached at, e.g. `sanmartin.goschool.app`.
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    /// Defaults to `database_url` with the id added to the database prefix
+    /// (`goschool_` -> `goschool_sanmartin_2025`).
+    pub database_url: Option<String>,
+    /// Default to `base_url` and `frontend_url` of the configuration.
+    pub base_url: Option<String>,
+    pub frontend_url: Option<String>,
+    /// Defaults to the `email_*` settings.
+    pub email: Option<EmailSender>,
+    #[serde(default)]
+    pub branding: Branding,
+}
+
+impl School {
+    fn from_config() -> School {
+        let config = config::get();
+        School {
+            id: DEFAULT_SCHOOL.to_string(),
+            name: config.school_name.clone(),
+            hosts: Vec::new(),
+            database_url: None,
+            base_url: None,
+            frontend_url: None,
+            email: None,
+            branding: Branding {
+                logo_url: config.school_logo_url.clone(),
+                primary_color: config.school_primary_color.clone(),
+            },
+        }
+    }
+
+    pub fn base_url(&self) -> String {
+        self.base_url
+            .clone()
+            .unwrap_or_else(|| config::get().base_url.clone())
+    }
+
+    pub fn frontend_url(&self) -> String {
+        self.frontend_url
+            .clone()
+            .or_else(|| config::get().frontend_url.clone())
+            .unwrap_or_else(|| self.base_url())
+    }
+
+    /// Directory under `uploads_dir` and `/uploads/` for one of
+    /// `UPLOAD_DIRS`: `files` becomes `{id}/files`, the default school keeps
+    /// the shared directories.
+    pub fn upload_dir(&self, kind: &str) -> String {
+        if self.id == DEFAULT_SCHOOL {
+            kind.to_string()
+        } else {
+            format!("{}/{}", self.id, kind)
+        }
+    }
+
+    /// Database names of the school, one per academic year.
+    fn years(&self, default: &Years) -> anyhow::Result<Years> {
+        match &self.database_url {
+            Some(url) => Years::from_url(url),
+            None if self.id == DEFAULT_SCHOOL => Ok(default.clone()),
+            None => Ok(default.for_school(&self.id)),
+        }
+    }
+}
+
+/// Schools of the JSON file at `schools_file`, or a single school
+/// configured by the `school_*` settings when it is not set.
+pub fn load() -> anyhow::Result<Vec<School>> {
+    let config = config::get();
+    let Some(path) = &config.schools_file else {
+        if config.email_from.is_none() {
+            anyhow::bail!("EMAIL_FROM, EMAIL_USERNAME and EMAIL_PASSWORD must be set");
+        }
+        return Ok(vec![School::from_config()]);
+    };
+    let schools: Vec<School> = serde_json::from_str(&read_to_string(path)?)?;
+    if schools.is_empty() {
+        anyhow::bail!("{} has no schools", path);
+    }
+
+    let mut ids = HashSet::new();
+    let mut hosts = HashSet::new();
+    for school in &schools {
+        // the id ends up in database names and upload paths
+        if school.id.is_empty()
+            || school.id == DEFAULT_SCHOOL
+            || !school.id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            anyhow::bail!("Invalid school id '{}', use lowercase letters, digits and '_'", school.id);
+        }
+        if !ids.insert(school.id.as_str()) {
+            anyhow::bail!("School '{}' is repeated", school.id);
+        }
+        match &school.email {
+            Some(sender) if sender.from.parse::<lettre::message::Mailbox>().is_err() => {
+                anyhow::bail!("Invalid email sender '{}' of school '{}'", sender.from, school.id)
+            }
+            None if config.email_from.is_none() => {
+                anyhow::bail!("School '{}' has no email and EMAIL_FROM is not set", school.id)
+            }
+            _ => {}
+        }
+        for host in &school.hosts {
+            if !hosts.insert(host.to_lowercase()) {
+                anyhow::bail!("Host '{}' belongs to more than one school", host);
+            }
+        }
+    }
+    Ok(schools)
+}
+
+tokio::task_local! {
+    static CURRENT_SCHOOL: Arc<School>;
+}
+
+/// School of the request being handled. Code that has no request, like the
+/// emails sent from the traits, finds its school through this.
+pub fn current() -> Arc<School> {
+    CURRENT_SCHOOL
+        .try_with(Arc::clone)
+        .unwrap_or_else(|_| Arc::new(School::from_config()))
+}
+
+/// Runs `f` as `school`. Tasks spawned while handling a request need it to
+/// keep their school.
+pub async fn scope<F: Future>(school: Arc<School>, f: F) -> F::Output {
+    CURRENT_SCHOOL.scope(school, f).await
+}
+
+/// Everything the handlers take from the app data that belongs to a school.
+pub struct Tenant {
+    pub school: web::Data<School>,
+    pub pool: web::Data<MySqlPool>,
+    pub years: web::Data<Years>,
+    pub active_year: web::Data<ActiveYear>,
+    pub year_pools: web::Data<YearPools>,
+    pub chat_manager: web::Data<ChatConnectionManager>,
+}
+
+impl Tenant {
+    pub async fn connect(school: School, default_years: &Years) -> anyhow::Result<Tenant> {
+        let years = school.years(default_years)?;
+        let active_year = years.active().await?;
+        let pool = MySqlPool::connect_with(years.connect_options(active_year)).await?;
+
+        Ok(Tenant {
+            school: web::Data::new(school),
+            pool: web::Data::new(pool),
+            year_pools: web::Data::new(YearPools::new(years.clone(), active_year)),
+            years: web::Data::new(years),
+            active_year: web::Data::new(ActiveYear(active_year)),
+            chat_manager: web::Data::new(ChatConnectionManager::new()),
+        })
+    }
+
+    fn app_data(&self) -> Extensions {
+        let mut data = Extensions::new();
+        data.insert(self.school.clone());
+        data.insert(self.pool.clone());
+        data.insert(self.years.clone());
+        data.insert(self.active_year.clone());
+        data.insert(self.year_pools.clone());
+        data.insert(self.chat_manager.clone());
+        data
+    }
+}
+
+pub struct Tenants(pub Vec<Tenant>);
+
+impl Tenants {
+    fn by_host(&self, host: &str) -> Option<&Tenant> {
+        // without the port
+        let host = host.rsplit_once(':').map_or(host, |(h, _)| h);
+        self.0
+            .iter()
+            .find(|t| t.school.hosts.iter().any(|h| h.eq_ignore_ascii_case(host)))
+    }
+
+    fn by_id(&self, id: &str) -> Option<&Tenant> {
+        self.0.iter().find(|t| t.school.id == id)
+    }
+}
+
+/// Picks the school of the request by its host, then by the `school` claim
+/// of its JWT, and makes the pools, years and chat connections of that
+/// school the app data every handler sees. A token of another school than
+/// the host is rejected by the `Claims` extractor.
+pub async fn resolve_school(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let tenants = req
+        .app_data::<web::Data<Tenants>>()
+        .cloned()
+        .ok_or_else(|| ErrorInternalServerError("Schools not configured"))?;
+
+    let host = req.connection_info().host().to_string();
+    let tenant = tenants
+        .by_host(&host)
+        .or_else(|| {
+            let token = access_token(req.request())?;
+            tenants.by_id(&jwt::school_of(&token)?)
+        })
+        .or_else(|| match tenants.0.as_slice() {
+            [only] => Some(only),
+            _ => None,
+        });
+    let Some(tenant) = tenant else {
+        return Err(InternalError::from_response(
+            "Unknown school",
+            HttpResponse::NotFound().json("Unknown school"),
+        )
+        .into());
+    };
+
+    let school = tenant.school.clone().into_inner();
+    req.add_data_container(Rc::new(tenant.app_data()));
+    scope(school, next.call(req)).await
+}
@@ -0,0 +1,230 @@
+use actix_web::cookie::{time::Duration, Cookie};
+use actix_web::HttpRequest;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use sqlx::{MySqlConnection, MySqlExecutor, MySqlPool};
+use uuid::Uuid;
+
+use crate::structs::{MySelf, Role};
+use crate::traits::New;
+
+pub const ACCESS_COOKIE: &str = "jwt";
+pub const REFRESH_COOKIE: &str = "refresh_token";
+const REFRESH_TOKEN_DAYS: i64 = 30;
+/// Same lifetime as the access token, impersonation sessions are not renewed.
+const IMPERSONATION_MINUTES: i64 = 15;
+
+/// Refresh tokens are only stored as a SHA-256 digest so a database leak
+/// does not hand out live sessions.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+pub fn generate_token() -> String {
+    let bytes: [u8; 32] = rand::rng().random();
+    hex::encode(bytes)
+}
+
+fn client_info(req: &HttpRequest) -> (Option<String>, Option<String>) {
+    let user_agent = req
+        .headers()
+        .get("User-Agent")
+        .and_then(|h| h.to_str().ok())
+        .map(|s| s.chars().take(255).collect::<String>());
+    let ip_address = req
+        .connection_info()
+        .realip_remote_addr()
+        .map(|s| s.to_string());
+    (user_agent, ip_address)
+}
+
+/// Creates a new session for the user and returns its id together with the
+/// plaintext refresh token (the only time it is available).
+pub async fn create_session(
+    pool: &MySqlPool,
+    user_id: u64,
+    role: &Role,
+    req: &HttpRequest,
+) -> Result<(String, String), sqlx::Error> {
+    let session_id = Uuid::new_v4().to_string();
+    let refresh_token = generate_token();
+    let (user_agent, ip_address) = client_info(req);
+
+    sqlx::query(
+        "INSERT INTO sessions (id, user_id, role, refresh_token_hash, expires_at, user_agent, ip_address) \
+         VALUES (?, ?, ?, ?, DATE_ADD(NOW(), INTERVAL ? DAY), ?, ?)",
+    )
+    .bind(&session_id)
+    .bind(user_id)
+    .bind(role)
+    .bind(hash_token(&refresh_token))
+    .bind(REFRESH_TOKEN_DAYS)
+    .bind(user_agent)
+    .bind(ip_address)
+    .execute(pool)
+    .await?;
+
+    Ok((session_id, refresh_token))
+}
+
+/// Creates a short session in which `admin_id` acts as the user. The stored
+/// refresh token hash matches no token ever handed out, so it cannot be renewed.
+pub async fn create_impersonation_session(
+    conn: &mut MySqlConnection,
+    admin_id: u64,
+    user_id: u64,
+    role: &Role,
+    req: &HttpRequest,
+) -> Result<String, sqlx::Error> {
+    let session_id = Uuid::new_v4().to_string();
+    let (user_agent, ip_address) = client_info(req);
+
+    sqlx::query(
+        "INSERT INTO sessions (id, user_id, role, refresh_token_hash, expires_at, user_agent, ip_address, impersonator_id) \
+         VALUES (?, ?, ?, ?, DATE_ADD(NOW(), INTERVAL ? MINUTE), ?, ?, ?)",
+    )
+    .bind(&session_id)
+    .bind(user_id)
+    .bind(role)
+    .bind(hash_token(&generate_token()))
+    .bind(IMPERSONATION_MINUTES)
+    .bind(user_agent)
+    .bind(ip_address)
+    .bind(admin_id)
+    .execute(conn)
+    .await?;
+
+    Ok(session_id)
+}
+
+pub async fn is_session_active(pool: &MySqlPool, session_id: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM sessions WHERE id = ? AND revoked_at IS NULL AND expires_at > NOW())",
+    )
+    .bind(session_id)
+    .fetch_one(pool)
+    .await
+}
+
+/// Exchanges a refresh token for a new one. Returns the session id, the user
+/// bound to it and the new refresh token, or `None` if the token is unknown,
+/// revoked, expired, reused by a concurrent refresh or the user no longer
+/// holds the session role.
+pub async fn rotate_refresh_token(
+    pool: &MySqlPool,
+    refresh_token: &str,
+) -> Result<Option<(String, MySelf, String)>, sqlx::Error> {
+    let session: Option<(String, u64, Role)> = sqlx::query_as(
+        "SELECT id, user_id, role FROM sessions \
+         WHERE refresh_token_hash = ? AND revoked_at IS NULL AND expires_at > NOW()",
+    )
+    .bind(hash_token(refresh_token))
+    .fetch_optional(pool)
+    .await?;
+
+    let (session_id, user_id, role) = match session {
+        Some(s) => s,
+        None => return Ok(None),
+    };
+
+    let role_existance: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM roles WHERE user_id = ? AND role = ?)",
+    )
+    .bind(user_id)
+    .bind(&role)
+    .fetch_one(pool)
+    .await?;
+    if !role_existance {
+        revoke_session(pool, &session_id).await?;
+        return Ok(None);
+    }
+
+    // another refresh with the same token already rotated it: the token was
+    // reused, so neither copy keeps the session
+    let new_refresh_token = generate_token();
+    let res = sqlx::query(
+        "UPDATE sessions SET refresh_token_hash = ?, last_used_at = NOW(), \
+         expires_at = DATE_ADD(NOW(), INTERVAL ? DAY) \
+         WHERE id = ? AND refresh_token_hash = ? AND revoked_at IS NULL",
+    )
+    .bind(hash_token(&new_refresh_token))
+    .bind(REFRESH_TOKEN_DAYS)
+    .bind(&session_id)
+    .bind(hash_token(refresh_token))
+    .execute(pool)
+    .await?;
+    if res.rows_affected() == 0 {
+        log::warn!("Refresh token of session {} reused, revoking it", session_id);
+        revoke_session(pool, &session_id).await?;
+        return Ok(None);
+    }
+
+    Ok(Some((session_id, MySelf::new(user_id, role), new_refresh_token)))
+}
+
+pub async fn revoke_session<'e, E: MySqlExecutor<'e>>(executor: E, session_id: &str) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query("UPDATE sessions SET revoked_at = NOW() WHERE id = ? AND revoked_at IS NULL")
+        .bind(session_id)
+        .execute(executor)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn revoke_session_by_refresh_token(pool: &MySqlPool, refresh_token: &str) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query("UPDATE sessions SET revoked_at = NOW() WHERE refresh_token_hash = ? AND revoked_at IS NULL")
+        .bind(hash_token(refresh_token))
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Ends every session of a user, optionally keeping the one making the request.
+pub async fn revoke_user_sessions(
+    pool: &MySqlPool,
+    user_id: u64,
+    except_session_id: Option<&str>,
+) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE sessions SET revoked_at = NOW() WHERE user_id = ? AND revoked_at IS NULL AND id <> ?",
+    )
+    .bind(user_id)
+    .bind(except_session_id.unwrap_or(""))
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected())
+}
+
+pub async fn delete_expired_sessions(pool: &MySqlPool) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query(
+        "DELETE FROM sessions WHERE expires_at < NOW() OR revoked_at < DATE_SUB(NOW(), INTERVAL 7 DAY)",
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected())
+}
+
+pub fn access_cookie(token: String) -> Cookie<'static> {
+    Cookie::build(ACCESS_COOKIE, token)
+        .path("/")
+        .http_only(true)
+        .secure(false)
+        .finish()
+}
+
+pub fn refresh_cookie(token: String) -> Cookie<'static> {
+    Cookie::build(REFRESH_COOKIE, token)
+        .path("/")
+        .http_only(true)
+        .secure(false)
+        .max_age(Duration::days(REFRESH_TOKEN_DAYS))
+        .finish()
+}
+
+pub fn expired_cookie(name: &'static str) -> Cookie<'static> {
+    Cookie::build(name, "")
+        .path("/")
+        .http_only(true)
+        .secure(false)
+        .max_age(Duration::seconds(0))
+        .finish()
+}
@@ -2,11 +2,16 @@ use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sqlx::{Decode, FromRow, MySql, QueryBuilder, Type};
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct MySelf {
     pub role: Role,
     pub id: u64,
+    /// Admin acting as this user, filled from the token's impersonation and
+    /// never part of the token itself.
+    #[serde(skip)]
+    pub impersonator_id: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, FromRow)]
@@ -20,9 +25,9 @@ pub struct User {
 
 #[derive(Serialize, Deserialize)]
 pub struct NewUser {
-    pub password: String,
     pub email: String,
     pub role: Role,
+    pub course_id: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -276,6 +281,152 @@ pub struct Grade {
     pub assessment_id: Option<u64>,
     pub grade_type: Option<GradeType>,
     pub created_at: Option<DateTime<Utc>>,
+    // past years that were never migrated have no periods
+    #[sqlx(default)]
+    pub period_id: Option<u64>,
+    /// Label of a conceptual grade.
+    #[sqlx(default)]
+    pub label: Option<String>,
+}
+
+impl Grade {
+    /// The grade as the student sees it: the label, the percentage or the
+    /// number.
+    pub fn display(&self) -> String {
+        GradeType::display(self.grade_type.as_ref(), self.grade, self.label.as_deref())
+    }
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct GradingPeriod {
+    pub id: u64,
+    pub name: String,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewGradingPeriod {
+    pub name: String,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UpdateGradingPeriod {
+    pub name: Option<String>,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+}
+
+/// Average of the numerical grades of a student in a subject during a period.
+#[derive(Debug, FromRow, Serialize)]
+pub struct PeriodAverage {
+    pub period_id: u64,
+    pub average: Decimal,
+    pub grade_count: i64,
+}
+
+/// Period averages of a student in a subject. The final grade is the mean
+/// of the period averages.
+#[derive(Debug, Serialize)]
+pub struct SubjectAverage {
+    pub student_id: u64,
+    pub subject_id: u64,
+    pub periods: Vec<PeriodAverage>,
+    pub final_grade: Option<Decimal>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistogramBin {
+    pub from: Decimal,
+    /// Excluded, except in the last bin.
+    pub to: Decimal,
+    pub count: i64,
+}
+
+/// Distribution of a set of grades on the numerical scale, without who got
+/// them. `pass_rate` is a percentage, each grade passing by its own scale.
+#[derive(Debug, Serialize)]
+pub struct GradeStatistics {
+    pub count: i64,
+    pub mean: Option<Decimal>,
+    pub median: Option<Decimal>,
+    pub min: Option<Decimal>,
+    pub max: Option<Decimal>,
+    pub pass_rate: Option<Decimal>,
+    pub histogram: Vec<HistogramBin>,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct SubjectStatistics {
+    pub subject_id: u64,
+    pub name: String,
+    pub count: i64,
+    pub mean: Option<Decimal>,
+    pub median: Option<Decimal>,
+    pub min: Option<Decimal>,
+    pub max: Option<Decimal>,
+    pub pass_rate: Option<Decimal>,
+}
+
+/// Grades with the averages of the same students and subjects, for
+/// `GET /api/v1/grades/?with_averages=true`.
+#[derive(Serialize)]
+pub struct GradesWithAverages {
+    pub grades: Vec<Grade>,
+    pub averages: Vec<SubjectAverage>,
+}
+
+/// Weight of an assessment type in the averages of a subject, or of every
+/// subject without its own when `subject_id` is `None`.
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct AssessmentWeight {
+    pub subject_id: Option<u64>,
+    pub assessment_type: AssessmentType,
+    pub weight: Decimal,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewAssessmentWeight {
+    pub assessment_type: AssessmentType,
+    /// `None` removes it, the type falls back to the school default or 1.
+    pub weight: Option<Decimal>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewAssessmentWeights {
+    /// `None` sets the school defaults.
+    pub subject_id: Option<u64>,
+    pub weights: Vec<NewAssessmentWeight>,
+}
+
+/// Everything printed on the report card of a student for a grading period.
+pub struct ReportCard {
+    pub student_name: String,
+    pub course_name: Option<String>,
+    pub period: GradingPeriod,
+    pub subjects: Vec<ReportCardSubject>,
+    /// Days per `assistance.presence` value.
+    pub attendance: Vec<(String, i64)>,
+    pub sanctions: Vec<DisciplinarySanction>,
+}
+
+pub struct ReportCardSubject {
+    pub name: String,
+    /// As `Grade::display` shows them.
+    pub grades: Vec<String>,
+    pub period_average: Option<Decimal>,
+    pub final_grade: Option<Decimal>,
+    pub comment: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewReportCardComment {
+    pub student_id: u64,
+    pub subject_id: u64,
+    pub period_id: u64,
+    pub comment: String,
 }
 
 
@@ -289,7 +440,7 @@ pub struct Timetable {
     pub end_time: NaiveTime
 }
 
-#[derive(Debug, Type, Serialize, Deserialize)]
+#[derive(Debug, Type, Serialize, Deserialize, PartialEq, Clone, Copy)]
 #[sqlx(rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
 pub enum GradeType {
@@ -298,6 +449,62 @@ pub enum GradeType {
     Percentage,
 }
 
+impl GradeType {
+    /// A grade of this type as the student sees it. Grades without a type
+    /// are numerical.
+    pub fn display(grade_type: Option<&GradeType>, grade: Decimal, label: Option<&str>) -> String {
+        match (grade_type, label) {
+            (Some(GradeType::Conceptual), Some(label)) => label.to_string(),
+            (Some(GradeType::Percentage), _) => format!("{}%", grade.normalize()),
+            _ => grade.normalize().to_string(),
+        }
+    }
+}
+
+/// Range of the numerical or percentage grades. Conceptual grades use the
+/// values of their labels, on the numerical scale.
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct GradingScale {
+    pub grade_type: GradeType,
+    pub min_value: Decimal,
+    pub max_value: Decimal,
+    pub passing_value: Decimal,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UpdateGradingScale {
+    pub min_value: Option<Decimal>,
+    pub max_value: Option<Decimal>,
+    pub passing_value: Option<Decimal>,
+}
+
+/// A conceptual grade and the numerical grade it stands for.
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct GradeLabel {
+    pub id: u64,
+    pub label: String,
+    pub value: Decimal,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewGradeLabel {
+    pub label: String,
+    pub value: Decimal,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UpdateGradeLabel {
+    pub label: Option<String>,
+    pub value: Option<Decimal>,
+}
+
+#[derive(Serialize)]
+pub struct GradingScales {
+    pub scales: Vec<GradingScale>,
+    /// Best first.
+    pub labels: Vec<GradeLabel>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct NewGrade {
     pub subject: u64,
@@ -305,7 +512,11 @@ pub struct NewGrade {
     pub student_id: u64,
     pub grade_type: GradeType,
     pub description: String,
-    pub grade: f32,
+    /// A conceptual grade takes `label` instead.
+    pub grade: Option<Decimal>,
+    pub label: Option<String>,
+    /// Defaults to the period of the current date.
+    pub period_id: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -539,7 +750,74 @@ pub struct UpdateCourse {
     pub division: Option<String>,
     pub level: Option<String>,
     pub shift: Option<String>,
+    /// `None` when omitted, `Some(None)` when sent as `null` to remove the preceptor.
+    #[serde(default, deserialize_with = "explicit_null")]
+    pub preceptor_id: Option<Option<u64>>,
+    pub name: Option<String>,
+}
+
+/// Wraps a present value, `null` included, in `Some` so `#[serde(default)]`
+/// is left to tell an omitted field apart.
+fn explicit_null<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+pub const COURSE_LEVELS: [&str; 2] = ["primary", "secondary"];
+pub const COURSE_SHIFTS: [&str; 2] = ["morning", "afternoon"];
+
+/// Checks the course fields the database would reject or truncate.
+fn validate_course_fields(
+    year: Option<i32>,
+    division: Option<&str>,
+    level: Option<&str>,
+    shift: Option<&str>,
+) -> Result<(), String> {
+    if year.is_some_and(|y| y < 1) {
+        return Err("Year must be positive".to_string());
+    }
+    if division.is_some_and(|d| d.chars().count() != 1) {
+        return Err("Division must be a single character".to_string());
+    }
+    if level.is_some_and(|l| !COURSE_LEVELS.contains(&l)) {
+        return Err(format!("Level must be one of {:?}", COURSE_LEVELS));
+    }
+    if shift.is_some_and(|s| !COURSE_SHIFTS.contains(&s)) {
+        return Err(format!("Shift must be one of {:?}", COURSE_SHIFTS));
+    }
+    Ok(())
+}
+
+impl UpdateCourse {
+    pub fn validate(&self) -> Result<(), String> {
+        validate_course_fields(self.year, self.division.as_deref(), self.level.as_deref(), self.shift.as_deref())
+    }
+}
+
+impl NewCourse {
+    pub fn validate(&self) -> Result<(), String> {
+        validate_course_fields(Some(self.year), Some(&self.division), Some(&self.level), Some(&self.shift))
+    }
+
+    /// The given name, or "1° A" style from year and division.
+    pub fn name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("{}° {}", self.year, self.division))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewCourse {
+    pub year: i32,
+    pub division: String,
+    pub level: String,
+    pub shift: String,
     pub preceptor_id: Option<u64>,
+    pub name: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -550,6 +828,8 @@ pub struct UpdateGrade {
     pub subject_id: Option<u64>,
     pub assessment_id: Option<u64>,
     pub grade_type: Option<GradeType>,
+    pub period_id: Option<u64>,
+    pub label: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -559,6 +839,13 @@ pub struct UpdateSubject {
     pub course_id: Option<u64>,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct NewSubject {
+    pub name: String,
+    pub teacher_id: u64,
+    pub course_id: u64,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct UpdateAssessment {
     pub subject_id: Option<u64>,
@@ -613,3 +900,231 @@ pub struct UpdateSubmission {
     pub task_id: Option<u64>,
 }
 
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub role: Role,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub impersonator_id: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PasswordResetConfirm {
+    pub token: String,
+    pub new_password: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ChangePassword {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ChangeEmail {
+    pub current_password: String,
+    pub new_email: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EmailChangeConfirm {
+    pub token: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SwitchRole {
+    pub role: Role,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TwoFactorCode {
+    pub code: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TwoFactorChallenge {
+    pub challenge_id: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TwoFactorLogin {
+    pub challenge_id: String,
+    pub code: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DisableTwoFactor {
+    pub current_password: String,
+    pub code: String,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct TwoFactorPolicy {
+    pub role: Role,
+    pub required: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewServiceAccount {
+    pub email: String,
+    pub roles: Vec<Role>,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct ServiceAccount {
+    pub id: u64,
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewApiToken {
+    pub user_id: u64,
+    pub name: String,
+    pub role: Role,
+    pub allowed_endpoints: Option<Vec<String>>,
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: u64,
+    pub user_id: u64,
+    pub name: String,
+    pub token_hint: String,
+    pub role: Role,
+    pub allowed_endpoints: Option<Vec<String>>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Type, Serialize, Deserialize)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: u64,
+    pub actor_id: u64,
+    pub actor_role: Role,
+    // past years that were never migrated have no impersonators
+    #[sqlx(default)]
+    pub impersonator_id: Option<u64>,
+    pub entity: String,
+    pub entity_id: u64,
+    pub action: AuditAction,
+    pub before_data: Option<serde_json::Value>,
+    pub after_data: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImpersonationRequest {
+    pub user_id: u64,
+    pub role: Role,
+    #[serde(default)]
+    pub allow_writes: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AcceptInvitation {
+    pub token: String,
+    pub password: String,
+    pub full_name: String,
+    pub birth_date: NaiveDate,
+    pub address: String,
+    pub phone_number: String,
+}
+
+#[derive(Deserialize)]
+pub struct ImportUsersQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportRowError {
+    pub row: u64,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Type, Serialize, Deserialize)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum GuardianType {
+    Mother,
+    Father,
+    Tutor,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct Family {
+    pub id: u64,
+    pub student_id: u64,
+    pub guardian_id: u64,
+    pub guardian_name: Option<String>,
+    pub guardian_email: String,
+    pub guardian_type: GuardianType,
+    pub is_primary_contact: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewFamily {
+    pub student_id: u64,
+    pub guardian_id: u64,
+    pub guardian_type: GuardianType,
+    #[serde(default)]
+    pub is_primary_contact: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateFamily {
+    pub guardian_type: Option<GuardianType>,
+    pub is_primary_contact: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RolloverRequest {
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub repeaters: Vec<u64>,
+    #[serde(default)]
+    pub course_map: HashMap<u64, Option<u64>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CoursePromotion {
+    pub course_id: u64,
+    pub course_name: String,
+    pub next_course_id: Option<u64>,
+    pub promoted: u64,
+    pub repeating: u64,
+    pub graduating: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RolloverReport {
+    pub from_year: i32,
+    pub to_year: i32,
+    pub promoted: u64,
+    pub repeating: u64,
+    pub graduating: u64,
+    pub courses: Vec<CoursePromotion>,
+}
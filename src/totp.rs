@@ -0,0 +1,278 @@
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha1::Sha1;
+use sqlx::MySqlPool;
+
+use crate::sessions::hash_token;
+use crate::structs::Role;
+
+const STEP_SECONDS: u64 = 30;
+const DIGITS: u32 = 6;
+const RECOVERY_CODES: usize = 10;
+pub const CHALLENGE_MINUTES: i64 = 5;
+pub const MAX_CHALLENGE_ATTEMPTS: i32 = 5;
+
+/// What a login has to go through before a session is issued.
+#[derive(Debug, PartialEq)]
+pub enum TwoFactorRequirement {
+    None,
+    Verify,
+    Enroll,
+}
+
+pub fn generate_secret() -> String {
+    let bytes: [u8; 20] = rand::rng().random();
+    base32::encode(base32::Alphabet::Rfc4648 { padding: false }, &bytes)
+}
+
+fn hotp(key: &[u8], counter: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    // RFC 4226 dynamic truncation
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = ((hash[offset] as u32 & 0x7f) << 24)
+        | ((hash[offset + 1] as u32) << 16)
+        | ((hash[offset + 2] as u32) << 8)
+        | (hash[offset + 3] as u32);
+
+    binary % 10u32.pow(DIGITS)
+}
+
+/// Checks a code against the current, previous and next time step. Returns
+/// the matched step so callers can reject codes at or before `last_step`.
+pub fn verify_code(secret: &str, code: &str, last_step: Option<u64>) -> Option<u64> {
+    let key = base32::decode(base32::Alphabet::Rfc4648 { padding: false }, secret)?;
+    let code: u32 = code.trim().parse().ok()?;
+    let now_step = chrono::Utc::now().timestamp() as u64 / STEP_SECONDS;
+
+    (now_step.saturating_sub(1)..=now_step + 1)
+        .filter(|step| last_step.is_none_or(|last| *step > last))
+        .find(|step| hotp(&key, *step) == code)
+}
+
+pub fn otpauth_url(secret: &str, account: &str) -> String {
+    let issuer = crate::config::get().totp_issuer.clone();
+    let encode = |s: &str| -> String {
+        s.bytes()
+            .map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'@' => (b as char).to_string(),
+                _ => format!("%{:02X}", b),
+            })
+            .collect()
+    };
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        encode(&issuer),
+        encode(account),
+        secret,
+        encode(&issuer),
+        DIGITS,
+        STEP_SECONDS
+    )
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.trim().replace('-', "").to_lowercase()
+}
+
+pub async fn is_enabled(pool: &MySqlPool, user_id: u64) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM user_totp WHERE user_id = ? AND enabled = TRUE)")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn requirement_for(pool: &MySqlPool, user_id: u64, role: &Role) -> Result<TwoFactorRequirement, sqlx::Error> {
+    if is_enabled(pool, user_id).await? {
+        return Ok(TwoFactorRequirement::Verify);
+    }
+
+    let required: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM two_factor_policies WHERE role = ? AND required = TRUE)")
+        .bind(role)
+        .fetch_one(pool)
+        .await?;
+    if required {
+        return Ok(TwoFactorRequirement::Enroll);
+    }
+
+    Ok(TwoFactorRequirement::None)
+}
+
+/// True if any role of the user is under a 2FA policy, in which case 2FA
+/// cannot be turned off.
+pub async fn required_by_policy(pool: &MySqlPool, user_id: u64) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM roles r JOIN two_factor_policies p ON p.role = r.role WHERE r.user_id = ? AND p.required = TRUE)",
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+}
+
+/// Stores a fresh, not yet confirmed secret for the user and returns it.
+pub async fn start_enrollment(pool: &MySqlPool, user_id: u64) -> Result<String, sqlx::Error> {
+    let secret = generate_secret();
+    sqlx::query(
+        "INSERT INTO user_totp (user_id, secret, enabled) VALUES (?, ?, FALSE) \
+         ON DUPLICATE KEY UPDATE secret = IF(enabled, secret, VALUES(secret))",
+    )
+    .bind(user_id)
+    .bind(&secret)
+    .execute(pool)
+    .await?;
+
+    // an already enabled secret is never replaced by a new setup
+    sqlx::query_scalar("SELECT secret FROM user_totp WHERE user_id = ?")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+}
+
+/// Verifies a TOTP code for the user (enabled or pending secret) and records
+/// the used step against replays.
+pub async fn check_totp(pool: &MySqlPool, user_id: u64, code: &str) -> Result<bool, sqlx::Error> {
+    let row: Option<(String, Option<u64>)> = sqlx::query_as("SELECT secret, last_used_step FROM user_totp WHERE user_id = ?")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+    let (secret, last_step) = match row {
+        Some(r) => r,
+        None => return Ok(false),
+    };
+
+    match verify_code(&secret, code, last_step) {
+        Some(step) => {
+            // only one of two requests with the same code moves the step forward
+            let res = sqlx::query(
+                "UPDATE user_totp SET last_used_step = ? \
+                 WHERE user_id = ? AND (last_used_step IS NULL OR last_used_step < ?)",
+            )
+            .bind(step)
+            .bind(user_id)
+            .bind(step)
+            .execute(pool)
+            .await?;
+            Ok(res.rows_affected() == 1)
+        }
+        None => Ok(false),
+    }
+}
+
+pub async fn use_recovery_code(pool: &MySqlPool, user_id: u64, code: &str) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE totp_recovery_codes SET used_at = NOW() WHERE user_id = ? AND code_hash = ? AND used_at IS NULL",
+    )
+    .bind(user_id)
+    .bind(hash_token(&normalize_recovery_code(code)))
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Accepts either a TOTP code or an unused recovery code.
+pub async fn check_second_factor(pool: &MySqlPool, user_id: u64, code: &str) -> Result<bool, sqlx::Error> {
+    if check_totp(pool, user_id, code).await? {
+        return Ok(true);
+    }
+    use_recovery_code(pool, user_id, code).await
+}
+
+/// Marks the pending secret as enabled and returns a new set of recovery codes.
+pub async fn enable(pool: &MySqlPool, user_id: u64) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query("UPDATE user_totp SET enabled = TRUE, confirmed_at = NOW() WHERE user_id = ?")
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    regenerate_recovery_codes(pool, user_id).await
+}
+
+pub async fn disable(pool: &MySqlPool, user_id: u64) -> Result<bool, sqlx::Error> {
+    sqlx::query("DELETE FROM totp_recovery_codes WHERE user_id = ?")
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    let res = sqlx::query("DELETE FROM user_totp WHERE user_id = ?")
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn regenerate_recovery_codes(pool: &MySqlPool, user_id: u64) -> Result<Vec<String>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM totp_recovery_codes WHERE user_id = ?")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let mut codes = Vec::with_capacity(RECOVERY_CODES);
+    for _ in 0..RECOVERY_CODES {
+        let bytes: [u8; 5] = rand::rng().random();
+        let raw = hex::encode(bytes);
+        sqlx::query("INSERT INTO totp_recovery_codes (user_id, code_hash) VALUES (?, ?)")
+            .bind(user_id)
+            .bind(hash_token(&raw))
+            .execute(&mut *tx)
+            .await?;
+        codes.push(format!("{}-{}", &raw[..5], &raw[5..]));
+    }
+    tx.commit().await?;
+
+    Ok(codes)
+}
+
+pub async fn create_challenge(pool: &MySqlPool, user_id: u64, role: &Role) -> Result<String, sqlx::Error> {
+    let challenge_id = uuid::Uuid::new_v4().to_string();
+    sqlx::query(
+        "INSERT INTO login_challenges (id, user_id, role, expires_at) VALUES (?, ?, ?, DATE_ADD(NOW(), INTERVAL ? MINUTE))",
+    )
+    .bind(&challenge_id)
+    .bind(user_id)
+    .bind(role)
+    .bind(CHALLENGE_MINUTES)
+    .execute(pool)
+    .await?;
+    Ok(challenge_id)
+}
+
+/// Returns the user and role of a pending challenge, counting the attempt.
+pub async fn take_challenge_attempt(pool: &MySqlPool, challenge_id: &str) -> Result<Option<(u64, Role)>, sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE login_challenges SET attempts = attempts + 1 WHERE id = ? AND expires_at > NOW() AND attempts < ?",
+    )
+    .bind(challenge_id)
+    .bind(MAX_CHALLENGE_ATTEMPTS)
+    .execute(pool)
+    .await?;
+    if res.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    sqlx::query_as("SELECT user_id, role FROM login_challenges WHERE id = ?")
+        .bind(challenge_id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn peek_challenge(pool: &MySqlPool, challenge_id: &str) -> Result<Option<(u64, Role)>, sqlx::Error> {
+    sqlx::query_as("SELECT user_id, role FROM login_challenges WHERE id = ? AND expires_at > NOW() AND attempts < ?")
+        .bind(challenge_id)
+        .bind(MAX_CHALLENGE_ATTEMPTS)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Uses up a challenge once its code was accepted. Only one of two requests
+/// completing the same challenge gets `true` and may log in.
+pub async fn consume_challenge(pool: &MySqlPool, challenge_id: &str) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query("DELETE FROM login_challenges WHERE id = ? AND expires_at > NOW()")
+        .bind(challenge_id)
+        .execute(pool)
+        .await?;
+    sqlx::query("DELETE FROM login_challenges WHERE expires_at < NOW()")
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected() == 1)
+}
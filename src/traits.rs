@@ -26,6 +26,51 @@ pub trait Get {
         pool: &MySqlPool,
         filter: GradeFilter)
     -> Result<Vec<Grade>, sqlx::Error>;
+
+    async fn get_grading_scales(
+        &self,
+        pool: &MySqlPool)
+    -> Result<GradingScales, sqlx::Error>;
+
+    async fn get_grading_periods(
+        &self,
+        pool: &MySqlPool)
+    -> Result<Vec<GradingPeriod>, sqlx::Error>;
+
+    async fn get_grade_averages(
+        &self,
+        pool: &MySqlPool,
+        filter: GradeAverageFilter)
+    -> Result<Vec<SubjectAverage>, sqlx::Error>;
+
+    async fn get_assessment_weights(
+        &self,
+        pool: &MySqlPool,
+        filter: AssessmentWeightFilter)
+    -> Result<Vec<AssessmentWeight>, sqlx::Error>;
+
+    /// `None` if a student or guardian asks for fewer grades than
+    /// anonymous statistics need.
+    async fn get_grade_statistics(
+        &self,
+        pool: &MySqlPool,
+        filter: GradeStatisticsFilter)
+    -> Result<Option<GradeStatistics>, sqlx::Error>;
+
+    async fn get_subject_statistics(
+        &self,
+        pool: &MySqlPool,
+        filter: SubjectStatisticsFilter)
+    -> Result<Vec<SubjectStatistics>, sqlx::Error>;
+
+    /// `None` if the student or period does not exist or the user cannot
+    /// see the student's report card.
+    async fn get_report_card(
+        &self,
+        pool: &MySqlPool,
+        student_id: u64,
+        period_id: u64)
+    -> Result<Option<ReportCard>, sqlx::Error>;
     
     async fn get_subjects(
         &self,
@@ -119,6 +164,16 @@ pub trait Get {
         pool: &MySqlPool,
         filter: ChatMessageFilter
     ) -> Result<Vec<ChatMessage>, sqlx::Error>;
+    async fn get_audit_log(
+        &self,
+        pool: &MySqlPool,
+        filter: AuditLogFilter
+    ) -> Result<Vec<AuditEntry>, sqlx::Error>;
+    async fn get_families(
+        &self,
+        pool: &MySqlPool,
+        filter: FamilyFilter
+    ) -> Result<Vec<Family>, sqlx::Error>;
 }
 
 pub trait Post  {
@@ -183,6 +238,41 @@ pub trait Post  {
         pool: &MySqlPool,
         timetable: NewTimetable
     ) -> HttpResponse;
+    async fn post_family(
+        &self,
+        pool: &MySqlPool,
+        family: NewFamily
+    ) -> HttpResponse;
+    async fn post_course(
+        &self,
+        pool: &MySqlPool,
+        course: NewCourse
+    ) -> HttpResponse;
+    async fn post_subject(
+        &self,
+        pool: &MySqlPool,
+        subject: NewSubject
+    ) -> HttpResponse;
+    async fn post_grading_period(
+        &self,
+        pool: &MySqlPool,
+        period: NewGradingPeriod
+    ) -> HttpResponse;
+    async fn post_report_card_comment(
+        &self,
+        pool: &MySqlPool,
+        comment: NewReportCardComment
+    ) -> HttpResponse;
+    async fn post_assessment_weights(
+        &self,
+        pool: &MySqlPool,
+        weights: NewAssessmentWeights
+    ) -> HttpResponse;
+    async fn post_grade_label(
+        &self,
+        pool: &MySqlPool,
+        label: NewGradeLabel
+    ) -> HttpResponse;
     // async fn post_chat(
     //     &self,
     //     pool: &MySqlPool,
@@ -247,6 +337,31 @@ pub trait Delete {
         pool: &MySqlPool,
         timetable_id: u64
     ) -> HttpResponse;
+    async fn delete_family(
+        &self,
+        pool: &MySqlPool,
+        family_id: u64
+    ) -> HttpResponse;
+    async fn delete_course(
+        &self,
+        pool: &MySqlPool,
+        course_id: u64
+    ) -> HttpResponse;
+    async fn delete_subject(
+        &self,
+        pool: &MySqlPool,
+        subject_id: u64
+    ) -> HttpResponse;
+    async fn delete_grading_period(
+        &self,
+        pool: &MySqlPool,
+        period_id: u64
+    ) -> HttpResponse;
+    async fn delete_grade_label(
+        &self,
+        pool: &MySqlPool,
+        label_id: u64
+    ) -> HttpResponse;
     // async fn delete_chat(
     //     &self,
     //     pool: &MySqlPool,
@@ -320,6 +435,42 @@ pub trait Update {
         timetable_id: u64,
         data: UpdateTimetable
     ) -> HttpResponse;
+    async fn update_family(
+        &self,
+        pool: &MySqlPool,
+        family_id: u64,
+        data: UpdateFamily
+    ) -> HttpResponse;
+    async fn update_course(
+        &self,
+        pool: &MySqlPool,
+        course_id: u64,
+        data: UpdateCourse
+    ) -> HttpResponse;
+    async fn update_subject(
+        &self,
+        pool: &MySqlPool,
+        subject_id: u64,
+        data: UpdateSubject
+    ) -> HttpResponse;
+    async fn update_grading_period(
+        &self,
+        pool: &MySqlPool,
+        period_id: u64,
+        data: UpdateGradingPeriod
+    ) -> HttpResponse;
+    async fn update_grading_scale(
+        &self,
+        pool: &MySqlPool,
+        grade_type: GradeType,
+        data: UpdateGradingScale
+    ) -> HttpResponse;
+    async fn update_grade_label(
+        &self,
+        pool: &MySqlPool,
+        label_id: u64,
+        data: UpdateGradeLabel
+    ) -> HttpResponse;
     // async fn update_chat(
     //     &self,
     //     pool: &MySqlPool,
@@ -0,0 +1,255 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+use sqlx::{MySql, MySqlPool, QueryBuilder};
+
+use crate::passwords::hash_password;
+use crate::sessions::generate_token;
+use crate::structs::{ImportRowError, Role};
+
+/// One line of the CSV, by header name. Everything is read as text so each
+/// field can be reported on its own.
+#[derive(Deserialize)]
+struct CsvRow {
+    email: String,
+    role: String,
+    full_name: String,
+    birth_date: String,
+    #[serde(default)]
+    course: String,
+    #[serde(default)]
+    parent_email: String,
+    #[serde(default)]
+    address: String,
+    #[serde(default)]
+    phone_number: String,
+}
+
+pub struct ImportedUser {
+    pub email: String,
+    role: Role,
+    full_name: String,
+    birth_date: NaiveDate,
+    address: String,
+    phone_number: String,
+    course_id: Option<u64>,
+    parent_emails: Vec<String>,
+}
+
+/// Rows ready to be imported, plus the fathers they reference that already
+/// have an account.
+pub struct ValidatedImport {
+    pub users: Vec<ImportedUser>,
+    existing_fathers: HashMap<String, u64>,
+}
+
+/// Parses and checks every row against the CSV itself and the database.
+/// Returns the per-row errors, rows are numbered by their CSV line.
+pub async fn validate(pool: &MySqlPool, csv_data: &[u8]) -> Result<Result<ValidatedImport, Vec<ImportRowError>>, sqlx::Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(csv_data);
+
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    for record in reader.deserialize::<CsvRow>() {
+        match record {
+            Ok(row) => rows.push(row),
+            Err(e) => {
+                let line = e.position().map(|p| p.line()).unwrap_or(1);
+                errors.push(ImportRowError { row: line, errors: vec![e.to_string()] });
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Ok(Err(errors));
+    }
+    if rows.is_empty() {
+        return Ok(Err(vec![ImportRowError { row: 1, errors: vec!["No users in file".to_string()] }]));
+    }
+
+    let courses: Vec<(u64, String)> = sqlx::query_as("SELECT id, name FROM courses")
+        .fetch_all(pool)
+        .await?;
+
+    let mut emails: Vec<String> = Vec::new();
+    for row in &rows {
+        emails.push(row.email.to_lowercase());
+        emails.extend(split_emails(&row.parent_email));
+    }
+    let existing = existing_users(pool, &emails).await?;
+
+    // fathers created by this same file
+    let new_fathers: HashSet<String> = rows
+        .iter()
+        .filter(|r| r.role.eq_ignore_ascii_case("father"))
+        .map(|r| r.email.to_lowercase())
+        .collect();
+
+    let mut users = Vec::new();
+    let mut seen = HashSet::new();
+    for (i, row) in rows.into_iter().enumerate() {
+        // the header is line 1
+        let line = i as u64 + 2;
+        let mut row_errors = Vec::new();
+
+        let email = row.email.to_lowercase();
+        if email.parse::<lettre::Address>().is_err() {
+            row_errors.push(format!("Invalid email '{}'", row.email));
+        } else if existing.contains_key(&email) {
+            row_errors.push(format!("Email '{}' already registered", row.email));
+        } else if !seen.insert(email.clone()) {
+            row_errors.push(format!("Email '{}' repeated in file", row.email));
+        }
+
+        let role = serde_json::from_value::<Role>(serde_json::Value::String(row.role.to_lowercase())).ok();
+        if role.is_none() {
+            row_errors.push(format!("Invalid role '{}'", row.role));
+        }
+
+        if row.full_name.is_empty() {
+            row_errors.push("Missing full_name".to_string());
+        }
+
+        let birth_date = NaiveDate::parse_from_str(&row.birth_date, "%Y-%m-%d").ok();
+        if birth_date.is_none() {
+            row_errors.push(format!("Invalid birth_date '{}', expected YYYY-MM-DD", row.birth_date));
+        }
+
+        // a course is given by id or by name
+        let course_id = if row.course.is_empty() {
+            if role == Some(Role::student) {
+                row_errors.push("Students need a course".to_string());
+            }
+            None
+        } else {
+            let course = courses
+                .iter()
+                .find(|(id, name)| row.course.parse::<u64>() == Ok(*id) || name.eq_ignore_ascii_case(&row.course));
+            if course.is_none() {
+                row_errors.push(format!("Unknown course '{}'", row.course));
+            }
+            course.map(|(id, _)| *id)
+        };
+
+        let parent_emails = split_emails(&row.parent_email);
+        if !parent_emails.is_empty() && role.is_some() && role != Some(Role::student) {
+            row_errors.push("Only students can have a parent_email".to_string());
+        }
+        for parent in &parent_emails {
+            let is_father = new_fathers.contains(parent) || existing.get(parent).is_some_and(|(_, father)| *father);
+            if !is_father {
+                row_errors.push(format!("Parent '{}' is not a father in the file or the database", parent));
+            }
+        }
+
+        match (role, birth_date) {
+            (Some(role), Some(birth_date)) if row_errors.is_empty() => users.push(ImportedUser {
+                email,
+                role,
+                full_name: row.full_name,
+                birth_date,
+                address: row.address,
+                phone_number: row.phone_number,
+                course_id,
+                parent_emails,
+            }),
+            _ => errors.push(ImportRowError { row: line, errors: row_errors }),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Ok(Err(errors));
+    }
+
+    let existing_fathers = existing
+        .into_iter()
+        .filter(|(_, (_, father))| *father)
+        .map(|(email, (id, _))| (email, id))
+        .collect();
+    Ok(Ok(ValidatedImport { users, existing_fathers }))
+}
+
+fn split_emails(field: &str) -> Vec<String> {
+    field
+        .split(';')
+        .map(|e| e.trim().to_lowercase())
+        .filter(|e| !e.is_empty())
+        .collect()
+}
+
+/// Id of each already registered email and whether it holds the father role.
+async fn existing_users(pool: &MySqlPool, emails: &[String]) -> Result<HashMap<String, (u64, bool)>, sqlx::Error> {
+    let mut query: QueryBuilder<MySql> = QueryBuilder::new(
+        "SELECT u.id, u.email, EXISTS(SELECT 1 FROM roles r WHERE r.user_id = u.id AND r.role = 'father') \
+         FROM users u WHERE u.email IN (",
+    );
+    let mut separated = query.separated(", ");
+    for email in emails {
+        separated.push_bind(email);
+    }
+    query.push(")");
+
+    let rows: Vec<(u64, String, bool)> = query.build_query_as().fetch_all(pool).await?;
+    Ok(rows
+        .into_iter()
+        .map(|(id, email, father)| (email.to_lowercase(), (id, father)))
+        .collect())
+}
+
+/// Creates every user with its role, personal data and family links in one
+/// transaction. Users get an unusable password until they accept their
+/// invitation. Returns the id and email of each new user.
+pub async fn import(pool: &MySqlPool, validated: ValidatedImport) -> Result<Vec<(u64, String)>, anyhow::Error> {
+    // one hash for the whole file, bcrypt is too slow to run per row
+    let placeholder_password = hash_password(&generate_token())?;
+
+    let mut tx = pool.begin().await?;
+    let mut ids: HashMap<String, u64> = validated.existing_fathers;
+    let mut created = Vec::new();
+
+    for user in &validated.users {
+        let user_id = sqlx::query("INSERT INTO users (password, email, course_id) VALUES (?, ?, ?)")
+            .bind(&placeholder_password)
+            .bind(&user.email)
+            .bind(user.course_id)
+            .execute(&mut *tx)
+            .await?
+            .last_insert_id();
+
+        sqlx::query("INSERT INTO roles (user_id, role) VALUES (?, ?)")
+            .bind(user_id)
+            .bind(&user.role)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO personal_data (user_id, full_name, birth_date, address, phone_number) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(user_id)
+        .bind(&user.full_name)
+        .bind(user.birth_date)
+        .bind(&user.address)
+        .bind(&user.phone_number)
+        .execute(&mut *tx)
+        .await?;
+
+        ids.insert(user.email.clone(), user_id);
+        created.push((user_id, user.email.clone()));
+    }
+
+    // after all users, a father may come later in the file than his children
+    for user in &validated.users {
+        for parent in &user.parent_emails {
+            sqlx::query("INSERT INTO families (student_id, father_id) VALUES (?, ?)")
+                .bind(ids[&user.email])
+                .bind(ids[parent])
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+
+    tx.commit().await?;
+    Ok(created)
+}
@@ -0,0 +1,23 @@
+use actix_web::{get, web, HttpResponse, Responder};
+
+use crate::structs::MySelf;
+use crate::years::{ActiveYear, Years};
+
+/// Years that can be selected with `academic_year` / `X-Academic-Year`.
+#[get("/api/v1/academic_years/")]
+pub async fn get_academic_years(
+    _user: MySelf,
+    years: web::Data<Years>,
+    active_year: web::Data<ActiveYear>,
+) -> impl Responder {
+    match years.existing().await {
+        Ok(existing) => {
+            let available: Vec<i32> = existing.into_iter().filter(|y| *y <= active_year.0).collect();
+            HttpResponse::Ok().json(serde_json::json!({
+                "active": active_year.0,
+                "years": available,
+            }))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
@@ -0,0 +1,190 @@
+use actix_web::{post, put, web, HttpResponse, Responder};
+use lettre::Address;
+use sqlx::mysql::MySqlPool;
+
+use crate::email::{send_email_change_email, send_email_changed_email};
+use crate::jwt::Claims;
+use crate::passwords::{check_password_strength, hash_password, verify_current_password};
+use crate::schools;
+use crate::sessions::{generate_token, hash_token, revoke_user_sessions};
+use crate::structs::{ChangeEmail, ChangePassword, EmailChangeConfirm};
+
+const EMAIL_CHANGE_HOURS: i64 = 24;
+
+#[put("/api/v1/account/password/")]
+pub async fn change_password(
+    claims: Claims,
+    pool: web::Data<MySqlPool>,
+    data: web::Json<ChangePassword>,
+) -> impl Responder {
+    let user = claims.user;
+
+    match verify_current_password(pool.get_ref(), user.id, &data.current_password).await {
+        Ok(true) => {}
+        Ok(false) => return HttpResponse::Unauthorized().json("Invalid credentials"),
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    }
+
+    if let Err(e) = check_password_strength(&data.new_password) {
+        return HttpResponse::BadRequest().json(e);
+    }
+
+    let hashed_pass = match hash_password(&data.new_password) {
+        Ok(h) => h,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    if let Err(e) = sqlx::query("UPDATE users SET password = ? WHERE id = ?")
+        .bind(&hashed_pass)
+        .bind(user.id)
+        .execute(pool.get_ref())
+        .await
+    {
+        return HttpResponse::InternalServerError().json(e.to_string());
+    }
+
+    // keep the session that made the change, end every other one
+    if let Err(e) = revoke_user_sessions(pool.get_ref(), user.id, Some(&claims.sid)).await {
+        return HttpResponse::InternalServerError().json(e.to_string());
+    }
+
+    HttpResponse::Ok().json("password updated")
+}
+
+#[put("/api/v1/account/email/")]
+pub async fn change_email(
+    claims: Claims,
+    pool: web::Data<MySqlPool>,
+    data: web::Json<ChangeEmail>,
+) -> impl Responder {
+    let user = claims.user;
+    match verify_current_password(pool.get_ref(), user.id, &data.current_password).await {
+        Ok(true) => {}
+        Ok(false) => return HttpResponse::Unauthorized().json("Invalid credentials"),
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    }
+
+    let new_email = data.new_email.trim().to_string();
+    if new_email.parse::<Address>().is_err() {
+        return HttpResponse::BadRequest().json("Invalid email address");
+    }
+
+    let email_taken: bool = match sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM users WHERE email = ?)")
+        .bind(&new_email)
+        .fetch_one(pool.get_ref())
+        .await
+    {
+        Ok(t) => t,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+    if email_taken {
+        return HttpResponse::Conflict().json("Email already in use");
+    }
+
+    // only the latest request can be confirmed
+    if let Err(e) = sqlx::query("UPDATE email_changes SET used_at = NOW() WHERE user_id = ? AND used_at IS NULL")
+        .bind(user.id)
+        .execute(pool.get_ref())
+        .await
+    {
+        return HttpResponse::InternalServerError().json(e.to_string());
+    }
+
+    let confirm_token = generate_token();
+    if let Err(e) = sqlx::query(
+        "INSERT INTO email_changes (user_id, new_email, token_hash, expires_at) VALUES (?, ?, ?, DATE_ADD(NOW(), INTERVAL ? HOUR))",
+    )
+    .bind(user.id)
+    .bind(&new_email)
+    .bind(hash_token(&confirm_token))
+    .bind(EMAIL_CHANGE_HOURS)
+    .execute(pool.get_ref())
+    .await
+    {
+        return HttpResponse::InternalServerError().json(e.to_string());
+    }
+
+    let full_name: String = sqlx::query_scalar("SELECT full_name FROM personal_data WHERE user_id = ?")
+        .bind(user.id)
+        .fetch_one(pool.get_ref())
+        .await
+        .unwrap_or_else(|_| new_email.clone());
+
+    // the page posts the token back, so link scanners opening it change nothing
+    let frontend_url = schools::current().frontend_url();
+    let confirm_link = format!("{}confirm_email_change?token={}", frontend_url, confirm_token);
+
+    send_email_change_email(new_email, &full_name, &confirm_link, EMAIL_CHANGE_HOURS).await;
+
+    HttpResponse::Accepted().json("Confirmation email sent to the new address")
+}
+
+#[post("/api/v1/account/email/confirm/")]
+pub async fn confirm_email_change(
+    pool: web::Data<MySqlPool>,
+    data: web::Json<EmailChangeConfirm>,
+) -> impl Responder {
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    let change: Option<(u64, u64, String)> = match sqlx::query_as(
+        "SELECT id, user_id, new_email FROM email_changes WHERE token_hash = ? AND used_at IS NULL AND expires_at > NOW() FOR UPDATE",
+    )
+    .bind(hash_token(&data.token))
+    .fetch_optional(&mut *tx)
+    .await
+    {
+        Ok(c) => c,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    let (change_id, user_id, new_email) = match change {
+        Some(c) => c,
+        None => return HttpResponse::BadRequest().json("Invalid or expired confirmation link"),
+    };
+
+    let (old_email, full_name): (String, Option<String>) = match sqlx::query_as(
+        "SELECT u.email, pd.full_name FROM users u LEFT JOIN personal_data pd ON pd.user_id = u.id WHERE u.id = ?",
+    )
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await
+    {
+        Ok(u) => u,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    // a concurrent confirm of the same token finds it used and changes nothing
+    match sqlx::query("UPDATE email_changes SET used_at = NOW() WHERE id = ? AND used_at IS NULL")
+        .bind(change_id)
+        .execute(&mut *tx)
+        .await
+    {
+        Ok(r) if r.rows_affected() == 1 => {}
+        Ok(_) => return HttpResponse::BadRequest().json("Invalid or expired confirmation link"),
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    }
+
+    // the UNIQUE constraint on users.email rejects addresses taken meanwhile
+    if let Err(e) = sqlx::query("UPDATE users SET email = ? WHERE id = ?")
+        .bind(&new_email)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+    {
+        return match e.as_database_error() {
+            Some(db) if db.is_unique_violation() => HttpResponse::Conflict().json("Email already in use"),
+            _ => HttpResponse::InternalServerError().json(e.to_string()),
+        };
+    }
+
+    if let Err(e) = tx.commit().await {
+        return HttpResponse::InternalServerError().json(e.to_string());
+    }
+
+    send_email_changed_email(old_email.clone(), full_name.as_deref().unwrap_or(&old_email), &new_email).await;
+
+    HttpResponse::Ok().json("email updated")
+}
@@ -0,0 +1,185 @@
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use sqlx::mysql::MySqlPool;
+
+use crate::api_tokens::{check_endpoint_pattern, create_token, list_tokens, revoke_token};
+use crate::jwt::Claims;
+use crate::passwords::hash_password;
+use crate::sessions::generate_token;
+use crate::structs::{NewApiToken, NewServiceAccount, Role, ServiceAccount};
+
+// Managing service accounts takes `Claims` rather than `MySelf`, so an API
+// token can never be used to mint more tokens.
+
+#[post("/api/v1/service_accounts/")]
+pub async fn create_service_account(
+    claims: Claims,
+    pool: web::Data<MySqlPool>,
+    data: web::Json<NewServiceAccount>,
+) -> impl Responder {
+    if claims.user.role != Role::admin {
+        return HttpResponse::Unauthorized().finish();
+    }
+    if data.roles.is_empty() {
+        return HttpResponse::BadRequest().json("A service account needs at least one role");
+    }
+
+    // nobody knows this password, service accounts can only use API tokens
+    let hashed_pass = match hash_password(&generate_token()) {
+        Ok(h) => h,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    let mut tx = match pool.begin().await {
+        Ok(t) => t,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    let user_id = match sqlx::query("INSERT INTO users (email, password, is_service_account) VALUES (?, ?, TRUE)")
+        .bind(&data.email)
+        .bind(&hashed_pass)
+        .execute(&mut *tx)
+        .await
+    {
+        Ok(r) => r.last_insert_id(),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            return HttpResponse::Conflict().json("Email already in use");
+        }
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    for role in &data.roles {
+        if let Err(e) = sqlx::query("INSERT INTO roles (user_id, role) VALUES (?, ?)")
+            .bind(user_id)
+            .bind(role)
+            .execute(&mut *tx)
+            .await
+        {
+            return HttpResponse::InternalServerError().json(e.to_string());
+        }
+    }
+
+    if let Err(e) = tx.commit().await {
+        return HttpResponse::InternalServerError().json(e.to_string());
+    }
+
+    log::info!("Service account {} ({}) created by admin {}", user_id, data.email, claims.user.id);
+    HttpResponse::Created().json(ServiceAccount {
+        id: user_id,
+        email: data.email.clone(),
+    })
+}
+
+#[get("/api/v1/service_accounts/")]
+pub async fn get_service_accounts(
+    claims: Claims,
+    pool: web::Data<MySqlPool>,
+) -> impl Responder {
+    if claims.user.role != Role::admin {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    match sqlx::query_as::<_, ServiceAccount>("SELECT id, email FROM users WHERE is_service_account = TRUE")
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(accounts) => HttpResponse::Ok().json(accounts),
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
+
+#[post("/api/v1/api_tokens/")]
+pub async fn create_api_token(
+    claims: Claims,
+    pool: web::Data<MySqlPool>,
+    data: web::Json<NewApiToken>,
+) -> impl Responder {
+    if claims.user.role != Role::admin {
+        return HttpResponse::Unauthorized().finish();
+    }
+    if data.name.trim().is_empty() {
+        return HttpResponse::BadRequest().json("Token name is required");
+    }
+    if data.expires_in_days.is_some_and(|d| d <= 0) {
+        return HttpResponse::BadRequest().json("expires_in_days must be positive");
+    }
+    // an admin token that can reach everything is an admin password that never asks for 2FA
+    if data.role == Role::admin && data.allowed_endpoints.as_ref().is_none_or(|e| e.is_empty()) {
+        return HttpResponse::BadRequest().json("Admin tokens must list their allowed_endpoints");
+    }
+    if let Some(endpoints) = &data.allowed_endpoints {
+        for pattern in endpoints {
+            if let Err(e) = check_endpoint_pattern(pattern) {
+                return HttpResponse::BadRequest().json(e);
+            }
+        }
+    }
+
+    let allowed: bool = match sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM users u JOIN roles r ON r.user_id = u.id \
+         WHERE u.id = ? AND u.is_service_account = TRUE AND r.role = ?)",
+    )
+    .bind(data.user_id)
+    .bind(&data.role)
+    .fetch_one(pool.get_ref())
+    .await
+    {
+        Ok(a) => a,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+    if !allowed {
+        return HttpResponse::BadRequest().json("User is not a service account with that role");
+    }
+
+    match create_token(
+        pool.get_ref(),
+        data.user_id,
+        data.name.trim(),
+        &data.role,
+        data.allowed_endpoints.as_deref(),
+        data.expires_in_days,
+        claims.user.id,
+    )
+    .await
+    {
+        Ok((id, token)) => {
+            log::info!("API token {} for user {} created by admin {}", id, data.user_id, claims.user.id);
+            HttpResponse::Created().json(serde_json::json!({ "id": id, "token": token }))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
+
+#[get("/api/v1/api_tokens/")]
+pub async fn get_api_tokens(
+    claims: Claims,
+    pool: web::Data<MySqlPool>,
+) -> impl Responder {
+    if claims.user.role != Role::admin {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    match list_tokens(pool.get_ref()).await {
+        Ok(tokens) => HttpResponse::Ok().json(tokens),
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
+
+#[delete("/api/v1/api_tokens/{id}")]
+pub async fn delete_api_token(
+    claims: Claims,
+    pool: web::Data<MySqlPool>,
+    id: web::Path<u64>,
+) -> impl Responder {
+    if claims.user.role != Role::admin {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    match revoke_token(pool.get_ref(), *id).await {
+        Ok(true) => {
+            log::info!("API token {} revoked by admin {}", id, claims.user.id);
+            HttpResponse::Ok().json("token revoked")
+        }
+        Ok(false) => HttpResponse::NotFound().json("Token not found"),
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
@@ -0,0 +1,28 @@
+use actix_web::{get, post, web, HttpResponse, Responder};
+use sqlx::mysql::MySqlPool;
+
+use crate::filters::AssessmentWeightFilter;
+use crate::structs::{MySelf, NewAssessmentWeights};
+use crate::traits::{Get, Post};
+use crate::years::YearPool;
+
+#[get("/api/v1/assessment_weights/")]
+pub async fn get_assessment_weights(
+    pool: YearPool,
+    user: MySelf,
+    filter: web::Query<AssessmentWeightFilter>,
+) -> impl Responder {
+    match user.get_assessment_weights(&pool, filter.into_inner()).await {
+        Ok(weights) => HttpResponse::Ok().json(weights),
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
+
+#[post("/api/v1/assessment_weights/")]
+pub async fn post_assessment_weights(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    weights: web::Json<NewAssessmentWeights>,
+) -> impl Responder {
+    user.post_assessment_weights(&pool, weights.into_inner()).await
+}
@@ -0,0 +1,22 @@
+use actix_web::{get, web, HttpResponse, Responder};
+
+use crate::filters::AuditLogFilter;
+use crate::structs::{MySelf, Role};
+use crate::traits::Get;
+use crate::years::YearPool;
+
+#[get("/api/v1/audit_log/")]
+pub async fn get_audit_log(
+    pool: YearPool,
+    user: MySelf,
+    filter: web::Query<AuditLogFilter>,
+) -> impl Responder {
+    if user.role != Role::admin {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    match user.get_audit_log(&pool, filter.into_inner()).await {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
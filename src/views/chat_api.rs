@@ -1,28 +1,17 @@
-use actix_web::{get, post, put, delete, web, HttpRequest, HttpResponse, Responder};
+use actix_web::{get, post, put, delete, web, HttpResponse, Responder};
 use actix_multipart::Multipart;
 use sqlx::MySqlPool;
 
-use crate::jwt::validate;
-use crate::structs::{NewChatRequest, SendMessageRequest, Chat, ChatMessage};
+use crate::structs::{NewChatRequest, SendMessageRequest, Chat, ChatMessage, MySelf};
 use crate::parse_multipart::parse_multipart;
 
 /// GET /api/v1/chats/ - List user's chats with last message and unread count
 #[get("/api/v1/chats/")]
 pub async fn get_user_chats(
-    req: HttpRequest,
+    user: MySelf,
     pool: web::Data<MySqlPool>,
 ) -> impl Responder {
-    let cookie = match req.cookie("jwt") {
-        Some(c) => c,
-        None => return HttpResponse::Unauthorized().finish(),
-    };
-
-    let token = match validate(cookie.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().finish(),
-    };
-
-    let user_id = token.claims.user.id;
+    let user_id = user.id;
 
     // Get all chats for the user with last message and unread count
     let query = r#"
@@ -64,22 +53,10 @@ pub async fn get_user_chats(
 /// POST /api/v1/chats/ - Create new chat (direct or group)
 #[post("/api/v1/chats/")]
 pub async fn create_chat(
-    req: HttpRequest,
+    user: MySelf,
     pool: web::Data<MySqlPool>,
     data: web::Json<NewChatRequest>,
 ) -> impl Responder {
-    let cookie = match req.cookie("jwt") {
-        Some(c) => c,
-        None => return HttpResponse::Unauthorized().finish(),
-    };
-
-    let token = match validate(cookie.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().finish(),
-    };
-
-    let user = token.claims.user;
-
     // Validate chat type
     if data.chat_type != "direct" && data.chat_type != "group" {
         return HttpResponse::BadRequest().json("Invalid chat_type. Must be 'direct' or 'group'");
@@ -214,23 +191,11 @@ pub async fn create_chat(
 /// GET /api/v1/chats/{chat_id}/messages - Get messages with pagination
 #[get("/api/v1/chats/{chat_id}/messages")]
 pub async fn get_chat_messages(
-    req: HttpRequest,
+    user: MySelf,
     pool: web::Data<MySqlPool>,
     chat_id: web::Path<u64>,
     query: web::Query<MessageQueryParams>,
 ) -> impl Responder {
-    let cookie = match req.cookie("jwt") {
-        Some(c) => c,
-        None => return HttpResponse::Unauthorized().finish(),
-    };
-
-    let token = match validate(cookie.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().finish(),
-    };
-
-    let user = token.claims.user;
-
     // Verify user is participant
     match user.is_chat_participant(pool.get_ref(), *chat_id).await {
         Ok(true) => {},
@@ -283,23 +248,11 @@ pub struct MessageQueryParams {
 /// POST /api/v1/chats/{chat_id}/messages - Send message (HTTP fallback to WebSocket)
 #[post("/api/v1/chats/{chat_id}/messages")]
 pub async fn send_message(
-    req: HttpRequest,
+    user: MySelf,
     pool: web::Data<MySqlPool>,
     chat_id: web::Path<u64>,
     data: web::Json<SendMessageRequest>,
 ) -> impl Responder {
-    let cookie = match req.cookie("jwt") {
-        Some(c) => c,
-        None => return HttpResponse::Unauthorized().finish(),
-    };
-
-    let token = match validate(cookie.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().finish(),
-    };
-
-    let user = token.claims.user;
-
     // Verify user is participant
     match user.is_chat_participant(pool.get_ref(), *chat_id).await {
         Ok(true) => {},
@@ -340,23 +293,11 @@ pub async fn send_message(
 /// POST /api/v1/chats/{chat_id}/participants - Add participants to group chat
 #[post("/api/v1/chats/{chat_id}/participants")]
 pub async fn add_participants(
-    req: HttpRequest,
+    user: MySelf,
     pool: web::Data<MySqlPool>,
     chat_id: web::Path<u64>,
     data: web::Json<AddParticipantsRequest>,
 ) -> impl Responder {
-    let cookie = match req.cookie("jwt") {
-        Some(c) => c,
-        None => return HttpResponse::Unauthorized().finish(),
-    };
-
-    let token = match validate(cookie.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().finish(),
-    };
-
-    let user = token.claims.user;
-
     // Verify user is admin of the chat
     match user.is_chat_admin(pool.get_ref(), *chat_id).await {
         Ok(true) => {},
@@ -409,24 +350,12 @@ pub struct AddParticipantsRequest {
 /// DELETE /api/v1/chats/{chat_id}/participants/{user_id} - Remove participant from group chat
 #[delete("/api/v1/chats/{chat_id}/participants/{user_id}")]
 pub async fn remove_participant(
-    req: HttpRequest,
+    user: MySelf,
     pool: web::Data<MySqlPool>,
     path: web::Path<(u64, u64)>,
 ) -> impl Responder {
     let (chat_id, target_user_id) = path.into_inner();
 
-    let cookie = match req.cookie("jwt") {
-        Some(c) => c,
-        None => return HttpResponse::Unauthorized().finish(),
-    };
-
-    let token = match validate(cookie.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().finish(),
-    };
-
-    let user = token.claims.user;
-
     // User can remove themselves, or admins can remove others
     let can_remove = if user.id == target_user_id {
         // Users can leave chats
@@ -464,21 +393,9 @@ pub async fn remove_participant(
 /// GET /api/v1/chats/available-users - Get users available to chat with
 #[get("/api/v1/chats/available-users")]
 pub async fn get_available_users(
-    req: HttpRequest,
+    user: MySelf,
     pool: web::Data<MySqlPool>,
 ) -> impl Responder {
-    let cookie = match req.cookie("jwt") {
-        Some(c) => c,
-        None => return HttpResponse::Unauthorized().finish(),
-    };
-
-    let token = match validate(cookie.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().finish(),
-    };
-
-    let user = token.claims.user;
-
     match user.get_available_chat_users(pool.get_ref()).await {
         Ok(users) => HttpResponse::Ok().json(users),
         Err(e) => {
@@ -491,23 +408,11 @@ pub async fn get_available_users(
 /// POST /api/v1/chats/{chat_id}/upload - Upload file in chat
 #[post("/api/v1/chats/{chat_id}/upload")]
 pub async fn upload_chat_file(
-    req: HttpRequest,
+    user: MySelf,
     pool: web::Data<MySqlPool>,
     chat_id: web::Path<u64>,
     multipart: Multipart,
 ) -> impl Responder {
-    let cookie = match req.cookie("jwt") {
-        Some(c) => c,
-        None => return HttpResponse::Unauthorized().finish(),
-    };
-
-    let token = match validate(cookie.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().finish(),
-    };
-
-    let user = token.claims.user;
-
     // Verify user is participant
     match user.is_chat_participant(pool.get_ref(), *chat_id).await {
         Ok(true) => {},
@@ -531,7 +436,7 @@ pub async fn upload_chat_file(
             "application/zip",
             "text/plain",
         ]),
-        "./uploads/chat_files",
+        "chat_files",
     )
     .await
     {
@@ -587,22 +492,10 @@ pub async fn upload_chat_file(
 /// PUT /api/v1/chats/{chat_id}/read - Mark all messages in chat as read
 #[put("/api/v1/chats/{chat_id}/read")]
 pub async fn mark_chat_as_read(
-    req: HttpRequest,
+    user: MySelf,
     pool: web::Data<MySqlPool>,
     chat_id: web::Path<u64>,
 ) -> impl Responder {
-    let cookie = match req.cookie("jwt") {
-        Some(c) => c,
-        None => return HttpResponse::Unauthorized().finish(),
-    };
-
-    let token = match validate(cookie.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().finish(),
-    };
-
-    let user = token.claims.user;
-
     // Verify user is participant
     match user.is_chat_participant(pool.get_ref(), *chat_id).await {
         Ok(true) => {},
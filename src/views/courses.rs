@@ -1,26 +1,15 @@
-use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
 use sqlx::mysql::MySqlPool;
 
-use crate::jwt::validate;
-use crate::traits::Get;
+use crate::traits::{Get, Post, Update, Delete};
+use crate::structs::{MySelf, NewCourse, UpdateCourse};
+use crate::years::YearPool;
 
 #[get("/api/v1/courses/")]
 pub async fn get_courses(
-    pool: web::Data<MySqlPool>,
-    req: HttpRequest,
+    pool: YearPool,
+    user: MySelf,
 ) -> impl Responder {
-    let cookie = match req.cookie("jwt") {
-        Some(cookie) => cookie,
-        None => return HttpResponse::Unauthorized().json("Missing JWT cookie"),
-    };
-
-    let token = match validate(cookie.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().json("Invalid JWT token"),
-    };
-    
-    let user = token.claims.user;
-    
     let courses = match user.get_courses(&pool).await {
         Ok(a) => a,
         Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
@@ -28,3 +17,31 @@ pub async fn get_courses(
 
     HttpResponse::Ok().json(courses)
 }
+
+#[post("/api/v1/courses/")]
+pub async fn post_course(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    course: web::Json<NewCourse>,
+) -> impl Responder {
+    user.post_course(&pool, course.into_inner()).await
+}
+
+#[put("/api/v1/courses/{id}")]
+pub async fn update_course(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    id: web::Path<u64>,
+    data: web::Json<UpdateCourse>,
+) -> impl Responder {
+    user.update_course(pool.get_ref(), *id, data.into_inner()).await
+}
+
+#[delete("/api/v1/courses/{id}")]
+pub async fn delete_course(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    id: web::Path<u64>,
+) -> impl Responder {
+    user.delete_course(pool.get_ref(), *id).await
+}
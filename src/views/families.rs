@@ -0,0 +1,49 @@
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use sqlx::mysql::MySqlPool;
+
+use crate::filters::FamilyFilter;
+use crate::structs::{NewFamily, UpdateFamily, MySelf};
+use crate::traits::{Get, Post, Update, Delete};
+use crate::years::YearPool;
+
+#[get("/api/v1/families/")]
+pub async fn get_families(
+    pool: YearPool,
+    user: MySelf,
+    filter: web::Query<FamilyFilter>,
+) -> impl Responder {
+    let families = match user.get_families(&pool, filter.into_inner()).await {
+        Ok(f) => f,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    HttpResponse::Ok().json(families)
+}
+
+#[post("/api/v1/families/")]
+pub async fn post_family(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    family: web::Json<NewFamily>,
+) -> impl Responder {
+    user.post_family(&pool, family.into_inner()).await
+}
+
+#[put("/api/v1/families/{id}")]
+pub async fn update_family(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    id: web::Path<u64>,
+    data: web::Json<UpdateFamily>,
+) -> impl Responder {
+    user.update_family(pool.get_ref(), *id, data.into_inner()).await
+}
+
+#[delete("/api/v1/families/{id}")]
+pub async fn delete_family(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    id: web::Path<u64>,
+) -> impl Responder {
+    user.delete_family(pool.get_ref(), *id).await
+}
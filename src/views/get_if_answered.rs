@@ -1,9 +1,9 @@
-use actix_web::{HttpRequest, HttpResponse, Responder, post, web};
+use actix_web::{HttpResponse, Responder, post, web};
 use serde::Deserialize;
 use sqlx::mysql::MySqlPool;
 
-use crate::jwt::validate;
 use crate::traits::Post;
+use crate::structs::MySelf;
 
 #[derive(Deserialize)]
 pub struct HomeworkId {
@@ -18,21 +18,9 @@ pub struct SelfassessableId {
 #[post("/api/v1/get_if_homework_answered/")]
 pub async fn get_if_homework_answered(
     pool: web::Data<MySqlPool>,
-    req: HttpRequest,
+    user: MySelf,
     homework_id: web::Json<HomeworkId>,
 ) -> impl Responder {
-    let cookie = match req.cookie("jwt") {
-        Some(cookie) => cookie,
-        None => return HttpResponse::Unauthorized().json("Missing JWT cookie"),
-    };
-
-    let token = match validate(cookie.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().json("Invalid JWT token"),
-    };
-
-    let user = token.claims.user;
-
     let is_answered = match user
         .get_is_homework_answered(&pool, homework_id.homework_id)
         .await
@@ -47,21 +35,9 @@ pub async fn get_if_homework_answered(
 #[post("/api/v1/get_if_selfassessable_answered/")]
 pub async fn get_if_selfassessable_answered(
     pool: web::Data<MySqlPool>,
-    req: HttpRequest,
+    user: MySelf,
     selfassesssable_id: web::Json<SelfassessableId>,
 ) -> impl Responder {
-    let cookie = match req.cookie("jwt") {
-        Some(cookie) => cookie,
-        None => return HttpResponse::Unauthorized().json("Missing JWT cookie"),
-    };
-
-    let token = match validate(cookie.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().json("Invalid JWT token"),
-    };
-
-    let user = token.claims.user;
-
     let is_answered = match user
         .get_is_selfassessable_answered(&pool, selfassesssable_id.selfassessable_id)
         .await
@@ -1,93 +1,99 @@
-use actix_web::{get, web, HttpRequest, HttpResponse, Responder, post, put, delete};
+use actix_web::{get, web, HttpResponse, Responder, post, put, delete};
 use sqlx::mysql::MySqlPool;
 
-use crate::jwt::validate;
-use crate::structs::NewGrade;
-use crate::filters::GradeFilter;
+use crate::structs::{GradesWithAverages, NewGrade, MySelf};
+use crate::filters::{GradeAverageFilter, GradeFilter, GradeStatisticsFilter, SubjectStatisticsFilter};
 use crate::traits::{Get, Post, Update, Delete};
 use crate::structs::UpdateGrade;
+use crate::years::YearPool;
 
 
 #[get("/api/v1/grades/")]
 pub async fn get_grades(
-    pool: web::Data<MySqlPool>,
-    req: HttpRequest,
+    pool: YearPool,
+    my_self: MySelf,
     filter: web::Query<GradeFilter>,
 ) -> impl Responder {
-    let cookie = match req.cookie("jwt") {
-        Some(cookie) => cookie,
-        None => return HttpResponse::Unauthorized().json("Missing JWT cookie"),
-    };
+    let user = my_self;  
+    let filter = filter.into_inner();
+    let average_filter = GradeAverageFilter { subject_id: filter.subject_id, student_id: filter.student_id };
+    let with_averages = filter.with_averages.unwrap_or(false);
 
-    let token = match validate(cookie.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().json("Invalid JWT token"),
-    };
-    
-    let user = token.claims.user;  
-
-    let grades = match user.get_grades(&pool, filter.into_inner()).await { 
+    let grades = match user.get_grades(&pool, filter).await { 
         Ok(g) => g,
         Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
     };
+    if !with_averages {
+        return HttpResponse::Ok().json(grades);
+    }
+
+    match user.get_grade_averages(&pool, average_filter).await {
+        Ok(averages) => HttpResponse::Ok().json(GradesWithAverages { grades, averages }),
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
+
+#[get("/api/v1/grades/averages/")]
+pub async fn get_grade_averages(
+    pool: YearPool,
+    user: MySelf,
+    filter: web::Query<GradeAverageFilter>,
+) -> impl Responder {
+    match user.get_grade_averages(&pool, filter.into_inner()).await {
+        Ok(averages) => HttpResponse::Ok().json(averages),
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
+
+#[get("/api/v1/grades/statistics/")]
+pub async fn get_grade_statistics(
+    pool: YearPool,
+    user: MySelf,
+    filter: web::Query<GradeStatisticsFilter>,
+) -> impl Responder {
+    match user.get_grade_statistics(&pool, filter.into_inner()).await {
+        Ok(Some(statistics)) => HttpResponse::Ok().json(statistics),
+        Ok(None) => HttpResponse::Forbidden().json("Too few grades for anonymous statistics"),
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
 
-    HttpResponse::Ok().json(grades)
+#[get("/api/v1/grades/statistics/subjects/")]
+pub async fn get_subject_statistics(
+    pool: YearPool,
+    user: MySelf,
+    filter: web::Query<SubjectStatisticsFilter>,
+) -> impl Responder {
+    match user.get_subject_statistics(&pool, filter.into_inner()).await {
+        Ok(statistics) => HttpResponse::Ok().json(statistics),
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
 }
 
 #[post("/api/v1/grades/")]
 pub async fn post_grade(
-    req: HttpRequest,
+    user: MySelf,
     pool: web::Data<MySqlPool>,
     grade: web::Json<NewGrade>,
 ) -> impl Responder {
-    let jwt = match req.cookie("jwt") {
-        Some(c) => c,
-        None => return HttpResponse::Unauthorized().finish(),
-    };
-
-    let token = match validate(jwt.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().finish(),
-    };
-    
-    let user = token.claims.user;
-    
     user.post_grade(&pool, grade.into_inner()).await
 }
 
 #[put("/api/v1/grades/{id}")]
 pub async fn update_grade(
-    req: HttpRequest,
+    user: MySelf,
     pool: web::Data<MySqlPool>,
     id: web::Path<u64>,
     data: web::Json<UpdateGrade>,
 ) -> impl Responder {
-    let jwt = match req.cookie("jwt") {
-        Some(c) => c,
-        None => return HttpResponse::Unauthorized().finish(),
-    };
-    let token = match validate(jwt.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().finish(),
-    };
-    let user = token.claims.user;
     user.update_grade(pool.get_ref(), *id, data.into_inner()).await
 }
 
 #[delete("/api/v1/grades/{id}")]
 pub async fn delete_grade(
-    req: HttpRequest,
+    user: MySelf,
     pool: web::Data<MySqlPool>,
     id: web::Path<u64>,
 ) -> impl Responder {
-    let jwt = match req.cookie("jwt") {
-        Some(c) => c,
-        None => return HttpResponse::Unauthorized().finish(),
-    };
-    let token = match validate(jwt.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().finish(),
-    };
-    let user = token.claims.user;
     user.delete_grade(pool.get_ref(), *id).await
 }
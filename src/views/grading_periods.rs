@@ -0,0 +1,45 @@
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use sqlx::mysql::MySqlPool;
+
+use crate::traits::{Get, Post, Update, Delete};
+use crate::structs::{MySelf, NewGradingPeriod, UpdateGradingPeriod};
+use crate::years::YearPool;
+
+#[get("/api/v1/grading_periods/")]
+pub async fn get_grading_periods(
+    pool: YearPool,
+    user: MySelf,
+) -> impl Responder {
+    match user.get_grading_periods(&pool).await {
+        Ok(periods) => HttpResponse::Ok().json(periods),
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
+
+#[post("/api/v1/grading_periods/")]
+pub async fn post_grading_period(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    period: web::Json<NewGradingPeriod>,
+) -> impl Responder {
+    user.post_grading_period(&pool, period.into_inner()).await
+}
+
+#[put("/api/v1/grading_periods/{id}")]
+pub async fn update_grading_period(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    id: web::Path<u64>,
+    data: web::Json<UpdateGradingPeriod>,
+) -> impl Responder {
+    user.update_grading_period(pool.get_ref(), *id, data.into_inner()).await
+}
+
+#[delete("/api/v1/grading_periods/{id}")]
+pub async fn delete_grading_period(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    id: web::Path<u64>,
+) -> impl Responder {
+    user.delete_grading_period(pool.get_ref(), *id).await
+}
@@ -0,0 +1,55 @@
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use sqlx::mysql::MySqlPool;
+
+use crate::traits::{Get, Post, Update, Delete};
+use crate::structs::{GradeType, MySelf, NewGradeLabel, UpdateGradeLabel, UpdateGradingScale};
+use crate::years::YearPool;
+
+#[get("/api/v1/grading_scales/")]
+pub async fn get_grading_scales(
+    pool: YearPool,
+    user: MySelf,
+) -> impl Responder {
+    match user.get_grading_scales(&pool).await {
+        Ok(scales) => HttpResponse::Ok().json(scales),
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
+
+#[put("/api/v1/grading_scales/{grade_type}")]
+pub async fn update_grading_scale(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    grade_type: web::Path<GradeType>,
+    data: web::Json<UpdateGradingScale>,
+) -> impl Responder {
+    user.update_grading_scale(pool.get_ref(), *grade_type, data.into_inner()).await
+}
+
+#[post("/api/v1/grading_scales/labels/")]
+pub async fn post_grade_label(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    label: web::Json<NewGradeLabel>,
+) -> impl Responder {
+    user.post_grade_label(&pool, label.into_inner()).await
+}
+
+#[put("/api/v1/grading_scales/labels/{id}")]
+pub async fn update_grade_label(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    id: web::Path<u64>,
+    data: web::Json<UpdateGradeLabel>,
+) -> impl Responder {
+    user.update_grade_label(pool.get_ref(), *id, data.into_inner()).await
+}
+
+#[delete("/api/v1/grading_scales/labels/{id}")]
+pub async fn delete_grade_label(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    id: web::Path<u64>,
+) -> impl Responder {
+    user.delete_grade_label(pool.get_ref(), *id).await
+}
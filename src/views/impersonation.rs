@@ -0,0 +1,128 @@
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Responder};
+use sqlx::mysql::MySqlPool;
+
+use crate::audit;
+use crate::jwt::{sign, Claims, Impersonation};
+use crate::sessions::{access_cookie, create_impersonation_session, expired_cookie, revoke_session, ACCESS_COOKIE};
+use crate::structs::{AuditAction, ImpersonationRequest, MySelf, Role};
+use crate::traits::New;
+
+/// Replaces the access cookie of the admin with one acting as another user.
+/// The admin's refresh cookie is left alone, so `/api/v1/refresh/` returns
+/// to the admin's own session once the impersonation ends.
+#[post("/api/v1/impersonation/")]
+pub async fn start_impersonation(
+    claims: Claims,
+    req: HttpRequest,
+    pool: web::Data<MySqlPool>,
+    data: web::Json<ImpersonationRequest>,
+) -> impl Responder {
+    if claims.impersonation.is_some() {
+        return HttpResponse::Forbidden().json("Already impersonating a user");
+    }
+    if claims.user.role != Role::admin {
+        return HttpResponse::Unauthorized().finish();
+    }
+    if data.role == Role::admin {
+        return HttpResponse::Forbidden().json("Admins cannot be impersonated");
+    }
+
+    let role_existance: bool = match sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM roles r JOIN users u ON r.user_id = u.id \
+         WHERE r.user_id = ? AND r.role = ? AND u.is_service_account = FALSE)",
+    )
+    .bind(data.user_id)
+    .bind(&data.role)
+    .fetch_one(pool.get_ref())
+    .await
+    {
+        Ok(r) => r,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+    if !role_existance {
+        return HttpResponse::NotFound().json("Role not assigned to user");
+    }
+
+    let admin = &claims.user;
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+    let session_id = match create_impersonation_session(&mut tx, admin.id, data.user_id, &data.role, &req).await {
+        Ok(s) => s,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    let impersonation = Impersonation {
+        admin_id: admin.id,
+        read_only: !data.allow_writes,
+    };
+    let impersonated = Claims::impersonating(MySelf::new(data.user_id, data.role.clone()), session_id.clone(), impersonation);
+    let token = match sign(&impersonated) {
+        Ok(t) => t,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    let details = serde_json::json!({
+        "role": data.role,
+        "read_only": !data.allow_writes,
+        "session_id": session_id,
+    });
+    if let Err(e) = audit::record(&mut tx, admin, audit::IMPERSONATIONS, data.user_id, AuditAction::Create, None, Some(details.clone())).await {
+        return HttpResponse::InternalServerError().json(e.to_string());
+    }
+    if let Err(e) = tx.commit().await {
+        return HttpResponse::InternalServerError().json(e.to_string());
+    }
+    log::info!(
+        "Admin {} started impersonating user {} as {:?} (read only: {})",
+        admin.id,
+        data.user_id,
+        data.role,
+        !data.allow_writes
+    );
+
+    HttpResponse::Ok().cookie(access_cookie(token)).json(details)
+}
+
+/// Lets a client show who is being impersonated; `null` for a normal session.
+#[get("/api/v1/impersonation/")]
+pub async fn get_impersonation(claims: Claims) -> impl Responder {
+    match &claims.impersonation {
+        Some(impersonation) => HttpResponse::Ok().json(serde_json::json!({
+            "admin_id": impersonation.admin_id,
+            "read_only": impersonation.read_only,
+            "user_id": claims.user.id,
+            "role": claims.user.role,
+        })),
+        None => HttpResponse::Ok().json(serde_json::Value::Null),
+    }
+}
+
+#[delete("/api/v1/impersonation/")]
+pub async fn stop_impersonation(
+    claims: Claims,
+    pool: web::Data<MySqlPool>,
+) -> impl Responder {
+    let Some(impersonation) = &claims.impersonation else {
+        return HttpResponse::BadRequest().json("Not impersonating a user");
+    };
+
+    let admin = MySelf::new(impersonation.admin_id, Role::admin);
+    let details = serde_json::json!({ "role": claims.user.role, "session_id": claims.sid });
+    let res: Result<(), sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+        revoke_session(&mut *tx, &claims.sid).await?;
+        audit::record(&mut tx, &admin, audit::IMPERSONATIONS, claims.user.id, AuditAction::Delete, Some(details), None).await?;
+        tx.commit().await
+    }
+    .await;
+    if let Err(e) = res {
+        return HttpResponse::InternalServerError().json(e.to_string());
+    }
+    log::info!("Admin {} stopped impersonating user {}", admin.id, claims.user.id);
+
+    HttpResponse::Ok()
+        .cookie(expired_cookie(ACCESS_COOKIE))
+        .json("impersonation ended")
+}
@@ -0,0 +1,172 @@
+use actix_web::{post, web, HttpResponse, Responder};
+use sqlx::mysql::MySqlPool;
+
+use crate::email::send_invitation_email;
+use crate::schools;
+use crate::passwords::{check_password_strength, hash_password};
+use crate::sessions::{generate_token, hash_token};
+use crate::structs::{AcceptInvitation, MySelf, Role};
+
+const INVITATION_HOURS: i64 = 72;
+
+/// Replaces any pending invitation of the user with a new one and emails
+/// the link, so only the latest link is valid.
+pub async fn send_invitation(
+    pool: &MySqlPool,
+    user_id: u64,
+    email: &str,
+    invited_by: Option<u64>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE invitations SET revoked_at = NOW() WHERE user_id = ? AND accepted_at IS NULL AND revoked_at IS NULL")
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+    let token = generate_token();
+    sqlx::query(
+        "INSERT INTO invitations (user_id, token_hash, invited_by, expires_at) VALUES (?, ?, ?, DATE_ADD(NOW(), INTERVAL ? HOUR))",
+    )
+    .bind(user_id)
+    .bind(hash_token(&token))
+    .bind(invited_by)
+    .bind(INVITATION_HOURS)
+    .execute(pool)
+    .await?;
+
+    let frontend_url = schools::current().frontend_url();
+    let invitation_link = format!("{}accept_invitation?token={}", frontend_url, token);
+
+    send_invitation_email(email.to_string(), &invitation_link, INVITATION_HOURS).await;
+    Ok(())
+}
+
+#[post("/api/v1/invitations/accept/")]
+pub async fn accept_invitation(
+    pool: web::Data<MySqlPool>,
+    data: web::Json<AcceptInvitation>,
+) -> impl Responder {
+    if let Err(e) = check_password_strength(&data.password) {
+        return HttpResponse::BadRequest().json(e);
+    }
+
+    let hashed_pass = match hash_password(&data.password) {
+        Ok(h) => h,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    let invitation: Option<(u64, u64)> = match sqlx::query_as(
+        "SELECT id, user_id FROM invitations \
+         WHERE token_hash = ? AND accepted_at IS NULL AND revoked_at IS NULL AND expires_at > NOW() FOR UPDATE",
+    )
+    .bind(hash_token(&data.token))
+    .fetch_optional(&mut *tx)
+    .await
+    {
+        Ok(i) => i,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    let (invitation_id, user_id) = match invitation {
+        Some(i) => i,
+        None => return HttpResponse::BadRequest().json("Invalid or expired invitation"),
+    };
+
+    // a concurrent accept of the same token finds it accepted and changes nothing
+    match sqlx::query(
+        "UPDATE invitations SET accepted_at = NOW() \
+         WHERE id = ? AND accepted_at IS NULL AND revoked_at IS NULL AND expires_at > NOW()",
+    )
+    .bind(invitation_id)
+    .execute(&mut *tx)
+    .await
+    {
+        Ok(r) if r.rows_affected() == 1 => {}
+        Ok(_) => return HttpResponse::BadRequest().json("Invalid or expired invitation"),
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    }
+
+    if let Err(e) = sqlx::query("UPDATE users SET password = ? WHERE id = ?")
+        .bind(&hashed_pass)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+    {
+        return HttpResponse::InternalServerError().json(e.to_string());
+    }
+
+    // imported users already have personal data, the user confirms it here
+    let has_personal_data: bool = match sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM personal_data WHERE user_id = ?)")
+        .bind(user_id)
+        .fetch_one(&mut *tx)
+        .await
+    {
+        Ok(h) => h,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+    let personal_data_query = if has_personal_data {
+        "UPDATE personal_data SET full_name = ?, birth_date = ?, address = ?, phone_number = ? WHERE user_id = ?"
+    } else {
+        "INSERT INTO personal_data (full_name, birth_date, address, phone_number, user_id) VALUES (?, ?, ?, ?, ?)"
+    };
+    if let Err(e) = sqlx::query(personal_data_query)
+        .bind(&data.full_name)
+        .bind(data.birth_date)
+        .bind(&data.address)
+        .bind(&data.phone_number)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+    {
+        return HttpResponse::InternalServerError().json(e.to_string());
+    }
+
+    if let Err(e) = tx.commit().await {
+        return HttpResponse::InternalServerError().json(e.to_string());
+    }
+
+    log::info!("User {} accepted the invitation", user_id);
+    HttpResponse::Ok().json("account activated")
+}
+
+#[post("/api/v1/invitations/{user_id}/resend/")]
+pub async fn resend_invitation(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    user_id: web::Path<u64>,
+) -> impl Responder {
+    if user.role != Role::admin {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    // only users that were invited and never accepted
+    let email: Option<String> = match sqlx::query_scalar(
+        "SELECT u.email FROM users u WHERE u.id = ? \
+         AND EXISTS(SELECT 1 FROM invitations WHERE user_id = u.id) \
+         AND NOT EXISTS(SELECT 1 FROM invitations WHERE user_id = u.id AND accepted_at IS NOT NULL)",
+    )
+    .bind(*user_id)
+    .fetch_optional(pool.get_ref())
+    .await
+    {
+        Ok(e) => e,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    let email = match email {
+        Some(e) => e,
+        None => return HttpResponse::NotFound().json("No pending invitation for this user"),
+    };
+
+    match send_invitation(pool.get_ref(), *user_id, &email, Some(user.id)).await {
+        Ok(_) => {
+            log::info!("Invitation of user {} resent by admin {}", user_id, user.id);
+            HttpResponse::Ok().json("invitation sent")
+        }
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
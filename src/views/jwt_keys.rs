@@ -0,0 +1,45 @@
+use actix_web::{get, post, web, HttpResponse, Responder};
+
+use crate::keyring;
+use crate::jwt::Claims;
+use crate::structs::Role;
+
+#[get("/api/v1/jwt_keys/")]
+pub async fn get_jwt_keys(
+    claims: Claims,
+) -> impl Responder {
+    let user = claims.user;
+    if user.role != Role::admin {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    match keyring::key_ids() {
+        Ok((kids, signing_kid)) => HttpResponse::Ok().json(serde_json::json!({
+            "signing_kid": signing_kid,
+            "kids": kids,
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
+
+#[post("/api/v1/jwt_keys/rotate/")]
+pub async fn rotate_jwt_keys(
+    claims: Claims,
+) -> impl Responder {
+    let user = claims.user;
+    if user.role != Role::admin {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let rotated = match web::block(keyring::rotate).await {
+        Ok(r) => r,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+    match rotated {
+        Ok(kid) => {
+            log::info!("JWT signing key rotated to {} by admin {}", kid, user.id);
+            HttpResponse::Ok().json(serde_json::json!({ "signing_kid": kid }))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
@@ -1,37 +1,62 @@
-use actix_web::cookie::Cookie;
-use actix_web::{post, web, HttpResponse,  Responder};
+use actix_web::{post, web, HttpRequest, HttpResponse, HttpResponseBuilder, Responder};
 use sqlx::mysql::MySqlPool;
 use bcrypt::verify;
-use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
-use std::fs;
 
 use crate::Claims;
-use crate::structs::{CredentialsRole, MySelf};
+use crate::jwt::sign;
+use crate::login_guard::{client_ip, begin_attempt, record_failure, record_success};
+use crate::sessions::{access_cookie, create_session, refresh_cookie};
+use crate::structs::{CredentialsRole, MySelf, Role};
+use crate::totp::{create_challenge, requirement_for, TwoFactorRequirement};
 use crate::traits::New;
 
 #[post("/api/v1/login/")]
 pub async fn login(
+    req: HttpRequest,
     pool: web::Data<MySqlPool>,
     creds: web::Json<CredentialsRole>,
 ) -> impl Responder {
 
+    let ip = client_ip(&req);
 
-    let result: (u64,String) = match sqlx::query_as("SELECT id, password FROM users WHERE email = ?")
+    match begin_attempt(pool.get_ref(), &creds.email, &ip).await {
+        Ok(Some(seconds)) => {
+            return HttpResponse::TooManyRequests()
+                .insert_header(("Retry-After", seconds.to_string()))
+                .json("Too many failed attempts, try again later");
+        }
+        Ok(None) => {}
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    }
+
+    let result: (u64,String) = match sqlx::query_as("SELECT id, password FROM users WHERE email = ? AND is_service_account = FALSE")
         .bind(&creds.email)
         .fetch_one(pool.get_ref())
         .await
     {
         Ok(record) => record,
-        Err(_) => return HttpResponse::Unauthorized().json("Invalid credentials"),
+        Err(_) => {
+            if let Err(e) = record_failure(pool.get_ref(), &creds.email, &ip).await {
+                return HttpResponse::InternalServerError().body(e.to_string());
+            }
+            return HttpResponse::Unauthorized().json("Invalid credentials");
+        }
     }; 
 
     let hashed_pass = result.1;
     let valid = verify(&creds.password, &hashed_pass).unwrap_or(false);
 
     if !valid {
+        if let Err(e) = record_failure(pool.get_ref(), &creds.email, &ip).await {
+            return HttpResponse::InternalServerError().body(e.to_string());
+        }
         return HttpResponse::Unauthorized().json("Invalid credentials");
     }
 
+    if let Err(e) = record_success(pool.get_ref(), &creds.email).await {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+
     let user_id = result.0;
     
     let role_existance: bool = match sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM roles WHERE user_id = ? AND role = ?)")
@@ -46,43 +71,52 @@ pub async fn login(
         return HttpResponse::Unauthorized().finish();
     }
 
-
-    
-    let claims = Claims::new(MySelf::new(user_id as u64, creds.role.clone()));
-
-    let private_key_pem = match fs::read("/shared/ecc_private_key.pem") {
-        Ok(k) => k,
+    match requirement_for(pool.get_ref(), user_id, &creds.role).await {
+        Ok(TwoFactorRequirement::None) => {}
+        Ok(requirement) => {
+            // no session yet, the client has to finish the second step first
+            let challenge_id = match create_challenge(pool.get_ref(), user_id, &creds.role).await {
+                Ok(c) => c,
+                Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+            };
+            return HttpResponse::Ok().json(serde_json::json!({
+                "two_factor_required": true,
+                "enrollment_required": requirement == TwoFactorRequirement::Enroll,
+                "challenge_id": challenge_id,
+            }));
+        }
         Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
-    };
+    }
 
-    let encoding_key = match EncodingKey::from_ec_pem(&private_key_pem) {
-        Ok(k) => k,
-        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
-    };
+    match finish_login(pool.get_ref(), &req, user_id, &creds.role).await {
+        Ok(mut response) => response.json("login success"),
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
 
-    let token = match encode(
-        &Header::new(Algorithm::ES256),
-        &claims,
-        &encoding_key,
-    ) {
-        Ok(t) => t,
-        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
-    };
+/// Opens the session and sets the auth cookies once every login step passed.
+pub async fn finish_login(
+    pool: &MySqlPool,
+    req: &HttpRequest,
+    user_id: u64,
+    role: &Role,
+) -> Result<HttpResponseBuilder, String> {
+    let (session_id, refresh_token) = create_session(pool, user_id, role, req)
+        .await
+        .map_err(|e| e.to_string())?;
 
+    let claims = Claims::new(MySelf::new(user_id, role.clone()), session_id);
+    let token = sign(&claims).map_err(|e| e.to_string())?;
 
-    let _ = match sqlx::query("UPDATE users SET last_login = NOW() WHERE id = ?")
+    sqlx::query("UPDATE users SET last_login = NOW() WHERE id = ?")
         .bind(user_id)
-        .execute(pool.get_ref())
-        .await{
-        Ok(_) => 0,
-        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
-    };
-
-    let cookie = Cookie::build("jwt", token)
-        .path("/")
-        .http_only(true)
-        .secure(false)
-        .finish();
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
 
-    HttpResponse::Ok().cookie(cookie).json("login success")
+    let mut response = HttpResponse::Ok();
+    response
+        .cookie(access_cookie(token))
+        .cookie(refresh_cookie(refresh_token));
+    Ok(response)
 }
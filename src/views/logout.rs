@@ -1,16 +1,30 @@
-use actix_web::{post, HttpResponse, Responder};
-use actix_web::cookie::{Cookie, time::Duration};
+use actix_web::{post, web, HttpRequest, HttpResponse, Responder};
+use sqlx::mysql::MySqlPool;
+
+use crate::auth::access_token;
+use crate::jwt::validate;
+use crate::sessions::{expired_cookie, revoke_session, revoke_session_by_refresh_token, ACCESS_COOKIE, REFRESH_COOKIE};
 
 #[post("/api/v1/logout/")]
-pub async fn logout() -> impl Responder {
-    let expired_cookie = Cookie::build("jwt", "")
-        .path("/")
-        .http_only(true)
-        .secure(false)
-        .max_age(Duration::seconds(0))
-        .finish();
+pub async fn logout(
+    req: HttpRequest,
+    pool: web::Data<MySqlPool>,
+) -> impl Responder {
+    // the access token may already be expired, so fall back to the refresh token
+    if let Some(access_token) = access_token(&req)
+        && let Ok(token) = validate(&access_token, &pool).await
+        && let Err(e) = revoke_session(pool.get_ref(), &token.claims.sid).await
+    {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+    if let Some(cookie) = req.cookie(REFRESH_COOKIE)
+        && let Err(e) = revoke_session_by_refresh_token(pool.get_ref(), cookie.value()).await
+    {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
 
     HttpResponse::Ok()
-        .cookie(expired_cookie)
+        .cookie(expired_cookie(ACCESS_COOKIE))
+        .cookie(expired_cookie(REFRESH_COOKIE))
         .json("logout success")
 }
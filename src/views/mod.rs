@@ -22,3 +22,22 @@ pub mod assistance;
 pub mod disciplinary_sanctions;
 pub mod chat_api;
 pub mod health;
+pub mod refresh;
+pub mod sessions;
+pub mod password_reset;
+pub mod account;
+pub mod users;
+pub mod two_factor;
+pub mod jwt_keys;
+pub mod api_tokens;
+pub mod audit_log;
+pub mod impersonation;
+pub mod invitations;
+pub mod user_import;
+pub mod families;
+pub mod rollover;
+pub mod academic_years;
+pub mod grading_periods;
+pub mod report_cards;
+pub mod assessment_weights;
+pub mod grading_scales;
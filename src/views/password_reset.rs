@@ -0,0 +1,141 @@
+use actix_web::{post, web, HttpResponse, Responder};
+use sqlx::mysql::MySqlPool;
+
+use crate::email::send_password_reset_email;
+use crate::schools;
+use crate::passwords::{check_password_strength, hash_password};
+use crate::sessions::{generate_token, hash_token, revoke_user_sessions};
+use crate::structs::{PasswordResetConfirm, PasswordResetRequest};
+
+const RESET_TOKEN_MINUTES: i64 = 60;
+
+#[post("/api/v1/password_reset/")]
+pub async fn request_password_reset(
+    pool: web::Data<MySqlPool>,
+    data: web::Json<PasswordResetRequest>,
+) -> impl Responder {
+    // same answer whether the email exists or not, to avoid leaking accounts
+    let response = HttpResponse::Ok().json("If the email is registered, a reset link has been sent");
+
+    let user: Option<(u64, Option<String>)> = match sqlx::query_as(
+        "SELECT u.id, pd.full_name FROM users u LEFT JOIN personal_data pd ON pd.user_id = u.id WHERE u.email = ? AND u.is_service_account = FALSE",
+    )
+    .bind(&data.email)
+    .fetch_optional(pool.get_ref())
+    .await
+    {
+        Ok(u) => u,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    let (user_id, full_name) = match user {
+        Some(u) => u,
+        None => return response,
+    };
+
+    // only the latest link is valid
+    if let Err(e) = sqlx::query("UPDATE password_resets SET used_at = NOW() WHERE user_id = ? AND used_at IS NULL")
+        .bind(user_id)
+        .execute(pool.get_ref())
+        .await
+    {
+        return HttpResponse::InternalServerError().json(e.to_string());
+    }
+
+    let token = generate_token();
+    if let Err(e) = sqlx::query(
+        "INSERT INTO password_resets (user_id, token_hash, expires_at) VALUES (?, ?, DATE_ADD(NOW(), INTERVAL ? MINUTE))",
+    )
+    .bind(user_id)
+    .bind(hash_token(&token))
+    .bind(RESET_TOKEN_MINUTES)
+    .execute(pool.get_ref())
+    .await
+    {
+        return HttpResponse::InternalServerError().json(e.to_string());
+    }
+
+    let frontend_url = schools::current().frontend_url();
+    let reset_link = format!("{}reset_password?token={}", frontend_url, token);
+
+    // sent after answering, waiting for the mail server would tell registered emails apart
+    let email = data.into_inner().email;
+    actix_web::rt::spawn(schools::scope(schools::current(), async move {
+        send_password_reset_email(
+            email.clone(),
+            full_name.as_deref().unwrap_or(&email),
+            &reset_link,
+            RESET_TOKEN_MINUTES,
+        )
+        .await;
+    }));
+
+    response
+}
+
+#[post("/api/v1/password_reset/confirm/")]
+pub async fn confirm_password_reset(
+    pool: web::Data<MySqlPool>,
+    data: web::Json<PasswordResetConfirm>,
+) -> impl Responder {
+    if let Err(e) = check_password_strength(&data.new_password) {
+        return HttpResponse::BadRequest().json(e);
+    }
+
+    let hashed_pass = match hash_password(&data.new_password) {
+        Ok(h) => h,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    let reset: Option<(u64, u64)> = match sqlx::query_as(
+        "SELECT id, user_id FROM password_resets WHERE token_hash = ? AND used_at IS NULL AND expires_at > NOW() FOR UPDATE",
+    )
+    .bind(hash_token(&data.token))
+    .fetch_optional(&mut *tx)
+    .await
+    {
+        Ok(r) => r,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    let (reset_id, user_id) = match reset {
+        Some(r) => r,
+        None => return HttpResponse::BadRequest().json("Invalid or expired reset token"),
+    };
+
+    // a concurrent confirm of the same token finds it used and changes nothing
+    match sqlx::query("UPDATE password_resets SET used_at = NOW() WHERE id = ? AND used_at IS NULL")
+        .bind(reset_id)
+        .execute(&mut *tx)
+        .await
+    {
+        Ok(r) if r.rows_affected() == 1 => {}
+        Ok(_) => return HttpResponse::BadRequest().json("Invalid or expired reset token"),
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    }
+
+    if let Err(e) = sqlx::query("UPDATE users SET password = ? WHERE id = ?")
+        .bind(&hashed_pass)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+    {
+        return HttpResponse::InternalServerError().json(e.to_string());
+    }
+
+    if let Err(e) = tx.commit().await {
+        return HttpResponse::InternalServerError().json(e.to_string());
+    }
+
+    // whoever had the old password must log in again
+    if let Err(e) = revoke_user_sessions(pool.get_ref(), user_id, None).await {
+        return HttpResponse::InternalServerError().json(e.to_string());
+    }
+
+    HttpResponse::Ok().json("password updated")
+}
@@ -0,0 +1,35 @@
+use actix_web::{post, web, HttpRequest, HttpResponse, Responder};
+use sqlx::mysql::MySqlPool;
+
+use crate::Claims;
+use crate::jwt::sign;
+use crate::sessions::{access_cookie, refresh_cookie, rotate_refresh_token, REFRESH_COOKIE};
+
+#[post("/api/v1/refresh/")]
+pub async fn refresh(
+    req: HttpRequest,
+    pool: web::Data<MySqlPool>,
+) -> impl Responder {
+    let cookie = match req.cookie(REFRESH_COOKIE) {
+        Some(c) => c,
+        None => return HttpResponse::Unauthorized().json("Missing refresh token"),
+    };
+
+    let (session_id, user, refresh_token) = match rotate_refresh_token(pool.get_ref(), cookie.value()).await {
+        Ok(Some(s)) => s,
+        Ok(None) => return HttpResponse::Unauthorized().json("Invalid refresh token"),
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    let claims = Claims::new(user, session_id);
+
+    let token = match sign(&claims) {
+        Ok(t) => t,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    HttpResponse::Ok()
+        .cookie(access_cookie(token))
+        .cookie(refresh_cookie(refresh_token))
+        .json("token refreshed")
+}
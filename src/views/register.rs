@@ -1,64 +1,75 @@
-use actix_web::{post, web, HttpRequest, HttpResponse, Responder, get};
+use actix_web::{post, web, HttpResponse, Responder, get};
 use sqlx::mysql::MySqlPool;
 use bcrypt::{hash, DEFAULT_COST};
-use std::env;
 use tokio::time::{timeout, Duration};
 
-use crate::{jwt::validate, structs::NewUser, structs::Role};
+use crate::config::Config;
+use crate::passwords::hash_password;
+use crate::sessions::generate_token;
+use crate::views::invitations::send_invitation;
+use crate::jwt::Claims;
+use crate::{structs::NewUser, structs::Role};
 
+/// Creates the user without a usable password and emails an invitation,
+/// where the user picks a password and fills in their personal data.
 #[post("/api/v1/register/")]
 pub async fn register(
     pool: web::Data<MySqlPool>,
     user: web::Json<NewUser>,
-    req: HttpRequest,
+    claims: Option<Claims>,
+    config: web::Data<Config>,
 ) -> impl Responder {
-
-    let hashed_pass = match hash(&user.password, DEFAULT_COST) {
-        Ok(h) => h,
-        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
-    };
-
-
-    log::info!("Registering user: {}, pass {}", user.email, user.password);
-    
-    let debug = env::var("DEBUG").unwrap();
-
-    if debug != "true" {
-        let jwt = match req.cookie("jwt") {
-            Some(c) => c,
+    if !config.debug {
+        let role = match &claims {
+            Some(c) => &c.user.role,
             None => return HttpResponse::Unauthorized().finish(),
         };
-        let token = match validate(jwt.value()) {
-            Ok(t) => t,
-            Err(_) => return HttpResponse::Unauthorized().finish(),
-        };
-        
-        let role = token.claims.user.role;
         
-        if role != Role::admin {
+        if *role != Role::admin {
             return HttpResponse::Unauthorized().finish();
         }
     }
 
-    let _query = match sqlx::query("INSERT INTO users (password, email) VALUES (?, ?)")
+    log::info!("Registering user: {} as {:?}", user.email, user.role);
+
+    // nobody knows this password, it is replaced when the invitation is accepted
+    let hashed_pass = match hash_password(&generate_token()) {
+        Ok(h) => h,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    let user_id = match sqlx::query("INSERT INTO users (password, email, course_id) VALUES (?, ?, ?)")
             .bind(&hashed_pass)
             .bind(&user.email)
-        .execute(pool.get_ref())
+            .bind(user.course_id)
+        .execute(&mut *tx)
         .await {
-        Ok(g) => g,
+        Ok(g) => g.last_insert_id(),
         Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
     };
 
-    let _query = match sqlx::query("INSERT INTO roles (user_id, role) VALUES (?, ?)")
-            .bind(_query.last_insert_id())
+    if let Err(e) = sqlx::query("INSERT INTO roles (user_id, role) VALUES (?, ?)")
+            .bind(user_id)
             .bind(&user.role)
-        .execute(pool.get_ref())
+        .execute(&mut *tx)
         .await {
-        Ok(g) => g,
-        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
-    };
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
 
-    HttpResponse::Created().finish()
+    if let Err(e) = tx.commit().await {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+
+    if let Err(e) = send_invitation(pool.get_ref(), user_id, &user.email, claims.map(|c| c.user.id)).await {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+
+    HttpResponse::Created().json(user_id)
 }
 
 #[get("/api/v1/register_testing_users/")]
@@ -74,15 +85,11 @@ pub async fn register_testing_users(pool: web::Data<MySqlPool>) -> impl Responde
 
     //let debug = env::var("DEBUG").unwrap_or_default();
     //if debug != "true" {
-    //    let jwt = match req.cookie("jwt") {
-    //        Some(c) => c,
+    //    let role = match my_self {
+    //        Some(u) => u.role,
     //        None => return HttpResponse::Unauthorized().finish(),
     //    };
-    //    let token = match validate(jwt.value()) {
-    //        Ok(t) => t,
-    //        Err(_) => return HttpResponse::Unauthorized().finish(),
-    //    };
-    //    if token.claims.user.role != Role::admin {
+    //    if role != Role::admin {
     //        return HttpResponse::Unauthorized().finish();
     //    }
     //}
@@ -0,0 +1,46 @@
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{get, post, web, HttpResponse, Responder};
+use sqlx::mysql::MySqlPool;
+
+use crate::report_card;
+use crate::schools;
+use crate::structs::{MySelf, NewReportCardComment};
+use crate::traits::{Get, Post};
+use crate::years::YearPool;
+
+/// Report card of a student for a grading period, as a PDF.
+#[get("/api/v1/report_cards/{student_id}/{period_id}")]
+pub async fn get_report_card(
+    pool: YearPool,
+    user: MySelf,
+    path: web::Path<(u64, u64)>,
+) -> impl Responder {
+    let (student_id, period_id) = path.into_inner();
+    let card = match user.get_report_card(&pool, student_id, period_id).await {
+        Ok(Some(card)) => card,
+        Ok(None) => return HttpResponse::NotFound().json("Report card not found"),
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    let school = schools::current();
+    let pdf = report_card::render(&card, &school.name);
+    HttpResponse::Ok()
+        .content_type("application/pdf")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Inline,
+            parameters: vec![DispositionParam::Filename(format!(
+                "boletin_{}_{}.pdf",
+                student_id, period_id
+            ))],
+        })
+        .body(pdf)
+}
+
+#[post("/api/v1/report_cards/comments/")]
+pub async fn post_report_card_comment(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    comment: web::Json<NewReportCardComment>,
+) -> impl Responder {
+    user.post_report_card_comment(&pool, comment.into_inner()).await
+}
@@ -1,30 +1,61 @@
-use actix_web::{post, web, HttpResponse, Responder};
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
 use sqlx::mysql::MySqlPool;
 use bcrypt::verify;
 
-use crate::structs::{Role, Credentials};
+use crate::Claims;
+use crate::jwt::sign;
+use crate::login_guard::{client_ip, begin_attempt, record_failure, record_success};
+use crate::sessions::{access_cookie, create_session, refresh_cookie, revoke_session};
+use crate::structs::{Role, Credentials, MySelf, SwitchRole};
+use crate::totp::{requirement_for, TwoFactorRequirement};
+use crate::traits::New;
 
 #[post("/api/v1/roles/")]
 pub async fn get_roles(
+    req: HttpRequest,
     pool: web::Data<MySqlPool>,
     creds: web::Json<Credentials>,
 ) -> impl Responder {
-    let result: (u64,String) = match sqlx::query_as("SELECT id, password FROM users WHERE email = ?")
+    let ip = client_ip(&req);
+
+    match begin_attempt(pool.get_ref(), &creds.email, &ip).await {
+        Ok(Some(seconds)) => {
+            return HttpResponse::TooManyRequests()
+                .insert_header(("Retry-After", seconds.to_string()))
+                .json("Too many failed attempts, try again later");
+        }
+        Ok(None) => {}
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    }
+
+    let result: (u64,String) = match sqlx::query_as("SELECT id, password FROM users WHERE email = ? AND is_service_account = FALSE")
         .bind(&creds.email)
         .fetch_one(pool.get_ref())
         .await
     {
         Ok(record) => record,
-        Err(_) => return HttpResponse::Unauthorized().json("Invalid credentials"),
+        Err(_) => {
+            if let Err(e) = record_failure(pool.get_ref(), &creds.email, &ip).await {
+                return HttpResponse::InternalServerError().body(e.to_string());
+            }
+            return HttpResponse::Unauthorized().json("Invalid credentials");
+        }
     }; 
 
     let hashed_pass = result.1;
     let valid = verify(&creds.password, &hashed_pass).unwrap_or(false);
 
     if !valid {
+        if let Err(e) = record_failure(pool.get_ref(), &creds.email, &ip).await {
+            return HttpResponse::InternalServerError().body(e.to_string());
+        }
         return HttpResponse::Unauthorized().json("Invalid credentials");
     }
 
+    if let Err(e) = record_success(pool.get_ref(), &creds.email).await {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+
     let user_id = result.0;
 
     let roles:Vec<Role> = match sqlx::query_scalar::<_,Role>("SELECT role FROM roles WHERE user_id = ?")
@@ -37,3 +68,72 @@ pub async fn get_roles(
     
     HttpResponse::Ok().json(roles)   
 }
+
+#[get("/api/v1/roles/")]
+pub async fn get_my_roles(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+) -> impl Responder {
+    let roles: Vec<Role> = match sqlx::query_scalar::<_, Role>("SELECT role FROM roles WHERE user_id = ?")
+        .bind(user.id)
+        .fetch_all(pool.get_ref())
+        .await {
+        Ok(r) => r,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    HttpResponse::Ok().json(roles)
+}
+
+#[post("/api/v1/switch_role/")]
+pub async fn switch_role(
+    req: HttpRequest,
+    claims: Claims,
+    pool: web::Data<MySqlPool>,
+    data: web::Json<SwitchRole>,
+) -> impl Responder {
+    let user_id = claims.user.id;
+
+    let role_existance: bool = match sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM roles WHERE user_id = ? AND role = ?)")
+        .bind(user_id)
+        .bind(&data.role)
+        .fetch_one(pool.get_ref())
+        .await {
+        Ok(r) => r,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    if !role_existance {
+        return HttpResponse::Unauthorized().json("Role not assigned to user");
+    }
+
+    // an enrolled user already gave the second factor for this session, an
+    // unenrolled one has to log in again to go through enrollment
+    match requirement_for(pool.get_ref(), user_id, &data.role).await {
+        Ok(TwoFactorRequirement::Enroll) => {
+            return HttpResponse::Forbidden().json("Two-factor authentication required for this role");
+        }
+        Ok(_) => {}
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    }
+
+    // a fresh session per role, so tokens for the previous role stop working right away
+    let (session_id, refresh_token) = match create_session(pool.get_ref(), user_id, &data.role, &req).await {
+        Ok(s) => s,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    if let Err(e) = revoke_session(pool.get_ref(), &claims.sid).await {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+
+    let claims = Claims::new(MySelf::new(user_id, data.role.clone()), session_id);
+
+    let token = match sign(&claims) {
+        Ok(t) => t,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    HttpResponse::Ok()
+        .cookie(access_cookie(token))
+        .cookie(refresh_cookie(refresh_token))
+        .json(&data.role)
+}
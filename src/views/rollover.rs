@@ -0,0 +1,49 @@
+use actix_web::{post, web, HttpResponse, Responder};
+use sqlx::mysql::MySqlPool;
+
+use crate::rollover::rollover;
+use crate::structs::{MySelf, Role, RolloverRequest};
+use crate::years::{ActiveYear, YearPools, Years};
+
+/// Creates the database of the next academic year from the active one. The
+/// service keeps using the active year until it restarts in the new one.
+#[post("/api/v1/rollover/")]
+pub async fn post_rollover(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    years: web::Data<Years>,
+    year_pools: web::Data<YearPools>,
+    active_year: web::Data<ActiveYear>,
+    data: web::Json<RolloverRequest>,
+) -> impl Responder {
+    if user.role != Role::admin {
+        return HttpResponse::Unauthorized().finish();
+    }
+    // a missing target would leave its students in a course that does not exist
+    let course_ids: Vec<u64> = match sqlx::query_scalar("SELECT id FROM courses")
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(ids) => ids,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+    if let Some(target) = data.course_map.values().flatten().find(|id| !course_ids.contains(id)) {
+        return HttpResponse::BadRequest().json(format!("Course {} of course_map not found", target));
+    }
+
+    match rollover(&years, pool.get_ref(), active_year.0, &data).await {
+        Ok(report) => {
+            if !data.dry_run {
+                year_pools.refresh();
+                log::info!(
+                    "Academic year {} rolled over to {} by admin {}",
+                    report.from_year,
+                    report.to_year,
+                    user.id
+                );
+            }
+            HttpResponse::Ok().json(report)
+        }
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
@@ -0,0 +1,73 @@
+use actix_web::{delete, get, web, HttpResponse, Responder};
+use sqlx::mysql::MySqlPool;
+
+use crate::sessions::{revoke_session, revoke_user_sessions};
+use crate::jwt::Claims;
+use crate::structs::{Role, Session};
+
+#[get("/api/v1/sessions/")]
+pub async fn get_sessions(
+    claims: Claims,
+    pool: web::Data<MySqlPool>,
+) -> impl Responder {
+    let user = claims.user;
+    let sessions: Vec<Session> = match sqlx::query_as(
+        "SELECT id, role, created_at, last_used_at, expires_at, user_agent, ip_address, impersonator_id FROM sessions \
+         WHERE user_id = ? AND revoked_at IS NULL AND expires_at > NOW() ORDER BY last_used_at DESC",
+    )
+    .bind(user.id)
+    .fetch_all(pool.get_ref())
+    .await
+    {
+        Ok(s) => s,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    HttpResponse::Ok().json(sessions)
+}
+
+#[delete("/api/v1/sessions/{id}")]
+pub async fn delete_session(
+    claims: Claims,
+    pool: web::Data<MySqlPool>,
+    id: web::Path<String>,
+) -> impl Responder {
+    let user = claims.user;
+    if user.role != Role::admin {
+        let owns_session: bool = match sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM sessions WHERE id = ? AND user_id = ?)")
+            .bind(id.as_str())
+            .bind(user.id)
+            .fetch_one(pool.get_ref())
+            .await
+        {
+            Ok(o) => o,
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        };
+        if !owns_session {
+            return HttpResponse::Unauthorized().finish();
+        }
+    }
+
+    match revoke_session(pool.get_ref(), &id).await {
+        Ok(true) => HttpResponse::Ok().finish(),
+        Ok(false) => HttpResponse::NotFound().json("Session not found or already revoked"),
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
+
+#[delete("/api/v1/users/{user_id}/sessions/")]
+pub async fn delete_user_sessions(
+    claims: Claims,
+    pool: web::Data<MySqlPool>,
+    user_id: web::Path<u64>,
+) -> impl Responder {
+    let user = claims.user;
+    if user.role != Role::admin {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    match revoke_user_sessions(pool.get_ref(), *user_id, None).await {
+        Ok(revoked) => HttpResponse::Ok().json(serde_json::json!({ "revoked": revoked })),
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
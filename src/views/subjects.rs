@@ -1,28 +1,17 @@
-use actix_web::{HttpRequest, HttpResponse, Responder, get, web};
+use actix_web::{HttpResponse, Responder, delete, get, post, put, web};
 use sqlx::mysql::MySqlPool;
 
 use crate::filters::SubjectFilter;
-use crate::jwt::validate;
-use crate::traits::Get;
+use crate::traits::{Get, Post, Update, Delete};
+use crate::structs::{MySelf, NewSubject, UpdateSubject};
+use crate::years::YearPool;
 
 #[get("/api/v1/subjects/")]
 pub async fn get_subjects(
-    pool: web::Data<MySqlPool>,
-    req: HttpRequest,
+    pool: YearPool,
+    user: MySelf,
     filter: web::Query<SubjectFilter>,
 ) -> impl Responder {
-    let cookie = match req.cookie("jwt") {
-        Some(cookie) => cookie,
-        None => return HttpResponse::Unauthorized().json("Missing JWT cookie"),
-    };
-
-    let token = match validate(cookie.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().json("Invalid JWT token"),
-    };
-    
-    let user = token.claims.user;
-    
     let subjects = match user.get_subjects(&pool, filter.into_inner()).await {
         Ok(a) => a,
         Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
@@ -32,3 +21,31 @@ pub async fn get_subjects(
 
     HttpResponse::Ok().json(subjects)
 }
+
+#[post("/api/v1/subjects/")]
+pub async fn post_subject(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    subject: web::Json<NewSubject>,
+) -> impl Responder {
+    user.post_subject(&pool, subject.into_inner()).await
+}
+
+#[put("/api/v1/subjects/{id}")]
+pub async fn update_subject(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    id: web::Path<u64>,
+    data: web::Json<UpdateSubject>,
+) -> impl Responder {
+    user.update_subject(pool.get_ref(), *id, data.into_inner()).await
+}
+
+#[delete("/api/v1/subjects/{id}")]
+pub async fn delete_subject(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    id: web::Path<u64>,
+) -> impl Responder {
+    user.delete_subject(pool.get_ref(), *id).await
+}
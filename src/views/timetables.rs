@@ -1,29 +1,17 @@
-use actix_web::{HttpRequest, HttpResponse, Responder, get, post, web, put, delete};
+use actix_web::{HttpResponse, Responder, get, post, web, put, delete};
 use sqlx::mysql::MySqlPool;
 
-use crate::jwt::validate;
 use crate::traits::{Delete, Get, Post, Update};
 use crate::filters::TimetableFilter;
-use crate::structs::{NewTimetable, UpdateTimetable};
+use crate::structs::{NewTimetable, UpdateTimetable, MySelf};
+use crate::years::YearPool;
 
 #[get("/api/v1/timetables/")]
 pub async fn get_timetable(
-    pool: web::Data<MySqlPool>,
+    pool: YearPool,
     filter: web::Query<TimetableFilter>,
-    req: HttpRequest,
+    user: MySelf,
 ) -> impl Responder {
-    let cookie = match req.cookie("jwt") {
-        Some(cookie) => cookie,
-        None => return HttpResponse::Unauthorized().json("Missing JWT cookie"),
-    };
-
-    let token = match validate(cookie.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().json("Invalid JWT token"),
-    };
-    
-    let user = token.claims.user;
-    
     let timetables = match user.get_timetables(&pool, filter.into_inner()).await {
         Ok(a) => a,
         Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
@@ -36,20 +24,8 @@ pub async fn get_timetable(
 pub async fn post_timetable(
     pool: web::Data<MySqlPool>,
     timetable: web::Json<NewTimetable>,
-    req: HttpRequest
+    user: MySelf
 ) -> impl Responder {
-    let cookie = match req.cookie("jwt") {
-        Some(cookie) => cookie,
-        None => return HttpResponse::Unauthorized().json("Missing JWT cookie")
-    };
-
-    let token = match validate(cookie.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().json("Invalid JWT cookie")
-    };
-    
-    let user = token.claims.user;
-
     user.post_timetable(&pool, timetable.into_inner()).await
 }
 
@@ -58,21 +34,8 @@ pub async fn update_timetable(
     pool: web::Data<MySqlPool>,
     timetable: web::Json<UpdateTimetable>,
     timetable_id: web::Path<u64>,
-    req: HttpRequest
+    user: MySelf
 ) -> impl Responder {
-
-    let cookie = match req.cookie("jwt") {
-        Some(cookie) => cookie,
-        None => return HttpResponse::Unauthorized().json("Missing JWT cookie")
-    };
-
-    let token = match validate(cookie.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().json("Invalid JWT cookie")
-    };
-
-    let user = token.claims.user;
-
     user.update_timetable(&pool, timetable_id.into_inner(), timetable.into_inner()).await
 }
 
@@ -80,20 +43,7 @@ pub async fn update_timetable(
 pub async fn delete_timetable(
     pool: web::Data<MySqlPool>,
     timetable_id: web::Path<u64>,
-    req: HttpRequest
+    user: MySelf
 ) -> impl Responder {
-
-    let cookie = match req.cookie("jwt") {
-        Some(cookie) => cookie,
-        None => return HttpResponse::Unauthorized().json("Missing JWT cookie")
-    };
-
-    let token = match validate(cookie.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().json("Invalid JWT cookie")
-    };
-
-    let user = token.claims.user;
-
     user.delete_timetable(&pool, timetable_id.into_inner()).await
 }
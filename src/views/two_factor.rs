@@ -0,0 +1,284 @@
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse, Responder};
+use sqlx::mysql::MySqlPool;
+
+use crate::passwords::verify_current_password;
+use crate::jwt::Claims;
+use crate::structs::{DisableTwoFactor, Role, TwoFactorChallenge, TwoFactorCode, TwoFactorLogin, TwoFactorPolicy};
+use crate::totp::{
+    check_second_factor, check_totp, consume_challenge, disable, enable, is_enabled, otpauth_url, peek_challenge,
+    regenerate_recovery_codes, required_by_policy, start_enrollment, take_challenge_attempt,
+};
+use crate::views::login::finish_login;
+
+/// Creates (or returns the pending) secret and the matching otpauth:// url
+/// to be shown as a QR code.
+async fn setup_response(pool: &MySqlPool, user_id: u64) -> HttpResponse {
+    let email: String = match sqlx::query_scalar("SELECT email FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+    {
+        Ok(e) => e,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    match start_enrollment(pool, user_id).await {
+        Ok(secret) => HttpResponse::Ok().json(serde_json::json!({
+            "otpauth_url": otpauth_url(&secret, &email),
+            "secret": secret,
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
+
+#[post("/api/v1/account/2fa/setup/")]
+pub async fn setup_two_factor(
+    claims: Claims,
+    pool: web::Data<MySqlPool>,
+) -> impl Responder {
+    let user = claims.user;
+    match is_enabled(pool.get_ref(), user.id).await {
+        Ok(true) => return HttpResponse::Conflict().json("Two-factor authentication already enabled"),
+        Ok(false) => {}
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    }
+
+    setup_response(pool.get_ref(), user.id).await
+}
+
+#[post("/api/v1/account/2fa/enable/")]
+pub async fn enable_two_factor(
+    claims: Claims,
+    pool: web::Data<MySqlPool>,
+    data: web::Json<TwoFactorCode>,
+) -> impl Responder {
+    let user = claims.user;
+    match is_enabled(pool.get_ref(), user.id).await {
+        Ok(true) => return HttpResponse::Conflict().json("Two-factor authentication already enabled"),
+        Ok(false) => {}
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    }
+
+    match check_totp(pool.get_ref(), user.id, &data.code).await {
+        Ok(true) => {}
+        Ok(false) => return HttpResponse::BadRequest().json("Invalid code"),
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    }
+
+    match enable(pool.get_ref(), user.id).await {
+        Ok(codes) => {
+            log::info!("User {} enabled two-factor authentication", user.id);
+            HttpResponse::Ok().json(serde_json::json!({ "recovery_codes": codes }))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
+
+#[post("/api/v1/account/2fa/disable/")]
+pub async fn disable_two_factor(
+    claims: Claims,
+    pool: web::Data<MySqlPool>,
+    data: web::Json<DisableTwoFactor>,
+) -> impl Responder {
+    let user = claims.user;
+    match required_by_policy(pool.get_ref(), user.id).await {
+        Ok(true) => return HttpResponse::Forbidden().json("Two-factor authentication is required for your roles"),
+        Ok(false) => {}
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    }
+
+    match verify_current_password(pool.get_ref(), user.id, &data.current_password).await {
+        Ok(true) => {}
+        Ok(false) => return HttpResponse::Unauthorized().json("Invalid credentials"),
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    }
+
+    match check_second_factor(pool.get_ref(), user.id, &data.code).await {
+        Ok(true) => {}
+        Ok(false) => return HttpResponse::BadRequest().json("Invalid code"),
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    }
+
+    match disable(pool.get_ref(), user.id).await {
+        Ok(_) => {
+            log::info!("User {} disabled two-factor authentication", user.id);
+            HttpResponse::Ok().json("two-factor authentication disabled")
+        }
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
+
+#[post("/api/v1/account/2fa/recovery_codes/")]
+pub async fn regenerate_two_factor_recovery_codes(
+    claims: Claims,
+    pool: web::Data<MySqlPool>,
+    data: web::Json<TwoFactorCode>,
+) -> impl Responder {
+    let user = claims.user;
+    match is_enabled(pool.get_ref(), user.id).await {
+        Ok(true) => {}
+        Ok(false) => return HttpResponse::BadRequest().json("Two-factor authentication not enabled"),
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    }
+
+    // a recovery code is not accepted here, regenerating needs the device
+    match check_totp(pool.get_ref(), user.id, &data.code).await {
+        Ok(true) => {}
+        Ok(false) => return HttpResponse::BadRequest().json("Invalid code"),
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    }
+
+    match regenerate_recovery_codes(pool.get_ref(), user.id).await {
+        Ok(codes) => HttpResponse::Ok().json(serde_json::json!({ "recovery_codes": codes })),
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
+
+#[post("/api/v1/login/2fa/setup/")]
+pub async fn login_two_factor_setup(
+    pool: web::Data<MySqlPool>,
+    data: web::Json<TwoFactorChallenge>,
+) -> impl Responder {
+    let (user_id, _) = match peek_challenge(pool.get_ref(), &data.challenge_id).await {
+        Ok(Some(c)) => c,
+        Ok(None) => return HttpResponse::Unauthorized().json("Invalid or expired challenge"),
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    match is_enabled(pool.get_ref(), user_id).await {
+        Ok(true) => return HttpResponse::Conflict().json("Two-factor authentication already enabled"),
+        Ok(false) => {}
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    }
+
+    setup_response(pool.get_ref(), user_id).await
+}
+
+#[post("/api/v1/login/2fa/")]
+pub async fn login_two_factor(
+    req: HttpRequest,
+    pool: web::Data<MySqlPool>,
+    data: web::Json<TwoFactorLogin>,
+) -> impl Responder {
+    let (user_id, role) = match take_challenge_attempt(pool.get_ref(), &data.challenge_id).await {
+        Ok(Some(c)) => c,
+        Ok(None) => return HttpResponse::Unauthorized().json("Invalid or expired challenge"),
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    let enabled = match is_enabled(pool.get_ref(), user_id).await {
+        Ok(e) => e,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    // enrolling during login only accepts a code from the new secret
+    let valid = if enabled {
+        check_second_factor(pool.get_ref(), user_id, &data.code).await
+    } else {
+        check_totp(pool.get_ref(), user_id, &data.code).await
+    };
+    match valid {
+        Ok(true) => {}
+        Ok(false) => return HttpResponse::Unauthorized().json("Invalid code"),
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    }
+
+    // failed codes were counted by take_challenge_attempt, a good one ends the challenge
+    match consume_challenge(pool.get_ref(), &data.challenge_id).await {
+        Ok(true) => {}
+        Ok(false) => return HttpResponse::Unauthorized().json("Invalid or expired challenge"),
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    }
+
+    let recovery_codes = if enabled {
+        None
+    } else {
+        match enable(pool.get_ref(), user_id).await {
+            Ok(codes) => Some(codes),
+            Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+        }
+    };
+
+    match finish_login(pool.get_ref(), &req, user_id, &role).await {
+        Ok(mut response) => match recovery_codes {
+            Some(codes) => response.json(serde_json::json!({ "recovery_codes": codes })),
+            None => response.json("login success"),
+        },
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
+
+#[get("/api/v1/2fa/policies/")]
+pub async fn get_two_factor_policies(
+    claims: Claims,
+    pool: web::Data<MySqlPool>,
+) -> impl Responder {
+    let user = claims.user;
+    if user.role != Role::admin {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    match sqlx::query_as::<_, TwoFactorPolicy>("SELECT role, required FROM two_factor_policies")
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(policies) => HttpResponse::Ok().json(policies),
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
+
+#[put("/api/v1/2fa/policies/")]
+pub async fn update_two_factor_policy(
+    claims: Claims,
+    pool: web::Data<MySqlPool>,
+    data: web::Json<TwoFactorPolicy>,
+) -> impl Responder {
+    let user = claims.user;
+    if user.role != Role::admin {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let res = sqlx::query(
+        "INSERT INTO two_factor_policies (role, required) VALUES (?, ?) \
+         ON DUPLICATE KEY UPDATE required = VALUES(required)",
+    )
+    .bind(&data.role)
+    .bind(data.required)
+    .execute(pool.get_ref())
+    .await;
+
+    match res {
+        Ok(_) => {
+            log::info!(
+                "Two-factor policy for {:?} set to {} by admin {}",
+                data.role,
+                data.required,
+                user.id
+            );
+            HttpResponse::Ok().json(data.into_inner())
+        }
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
+
+#[delete("/api/v1/users/{user_id}/2fa/")]
+pub async fn reset_user_two_factor(
+    claims: Claims,
+    pool: web::Data<MySqlPool>,
+    user_id: web::Path<u64>,
+) -> impl Responder {
+    let user = claims.user;
+    if user.role != Role::admin {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    // the user enrolls again on next login if a policy still applies
+    match disable(pool.get_ref(), *user_id).await {
+        Ok(true) => {
+            log::info!("Two-factor authentication of user {} reset by admin {}", user_id, user.id);
+            HttpResponse::Ok().json("two-factor authentication reset")
+        }
+        Ok(false) => HttpResponse::NotFound().json("Two-factor authentication not configured"),
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
@@ -0,0 +1,61 @@
+use actix_web::{post, web, HttpResponse, Responder};
+use sqlx::mysql::MySqlPool;
+
+use crate::schools;
+use crate::structs::{ImportUsersQuery, MySelf, Role};
+use crate::user_import::{import, validate};
+use crate::views::invitations::send_invitation;
+
+/// Imports users from a CSV body. Nothing is written unless every row is
+/// valid; with `dry_run` only the validation report is returned.
+#[post("/api/v1/users/import/")]
+pub async fn import_users(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    query: web::Query<ImportUsersQuery>,
+    body: web::Bytes,
+) -> impl Responder {
+    if user.role != Role::admin {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let validated = match validate(pool.get_ref(), &body).await {
+        Ok(v) => v,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+
+    let validated = match validated {
+        Ok(v) => v,
+        Err(errors) if query.dry_run => {
+            return HttpResponse::Ok().json(serde_json::json!({ "valid": false, "errors": errors }));
+        }
+        Err(errors) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({ "valid": false, "errors": errors }));
+        }
+    };
+
+    if query.dry_run {
+        return HttpResponse::Ok().json(serde_json::json!({ "valid": true, "users": validated.users.len() }));
+    }
+
+    let created = match import(pool.get_ref(), validated).await {
+        Ok(c) => c,
+        Err(e) => return HttpResponse::InternalServerError().json(e.to_string()),
+    };
+    log::info!("{} users imported by admin {}", created.len(), user.id);
+
+    let user_ids: Vec<u64> = created.iter().map(|(id, _)| *id).collect();
+
+    // hundreds of emails would hold the response for minutes
+    let pool = pool.into_inner();
+    let admin_id = user.id;
+    actix_web::rt::spawn(schools::scope(schools::current(), async move {
+        for (user_id, email) in created {
+            if let Err(e) = send_invitation(&pool, user_id, &email, Some(admin_id)).await {
+                log::error!("Failed to invite imported user {}: {}", user_id, e);
+            }
+        }
+    }));
+
+    HttpResponse::Created().json(serde_json::json!({ "created": user_ids.len(), "user_ids": user_ids }))
+}
@@ -0,0 +1,25 @@
+use actix_web::{post, web, HttpResponse, Responder};
+use sqlx::mysql::MySqlPool;
+
+use crate::login_guard::unlock_account;
+use crate::structs::{Role, MySelf};
+
+#[post("/api/v1/users/{user_id}/unlock/")]
+pub async fn unlock_user(
+    user: MySelf,
+    pool: web::Data<MySqlPool>,
+    user_id: web::Path<u64>,
+) -> impl Responder {
+    if user.role != Role::admin {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    match unlock_account(pool.get_ref(), *user_id).await {
+        Ok(true) => {
+            log::info!("User {} unlocked by admin {}", user_id, user.id);
+            HttpResponse::Ok().json("account unlocked")
+        }
+        Ok(false) => HttpResponse::NotFound().json("User not found"),
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
@@ -1,20 +1,10 @@
-use actix_web::{get, HttpRequest, HttpResponse, Responder};
+use actix_web::{get, HttpResponse, Responder};
 
-use crate::jwt::validate;
+use crate::structs::MySelf;
 
 #[get("/api/v1/verify_token/")]
 pub async fn verify_token(
-    req: HttpRequest,
+    _user: MySelf,
 ) -> impl Responder {
-    
-    let jwt = match req.cookie("jwt") {
-        Some(c) => c,
-        None => return HttpResponse::Unauthorized().finish(),
-    };
-    let _token = match validate(jwt.value()) {
-        Ok(t) => t,
-        Err(_) => return HttpResponse::Unauthorized().finish(),
-    };
-
     HttpResponse::Ok().json("json web token is valid")
 }
@@ -4,8 +4,7 @@ use futures_util::StreamExt;
 use sqlx::MySqlPool;
 use chrono::Utc;
 
-use crate::jwt::validate;
-use crate::structs::{ChatMessage, PubUser};
+use crate::structs::{ChatMessage, MySelf, PubUser};
 use super::manager::ChatConnectionManager;
 use super::protocol::{ClientMessage, ServerMessage};
 use sqlx::FromRow;
@@ -38,6 +37,7 @@ struct MessageWithSender {
 #[get("/api/v1/ws/chat/")]
 pub async fn chat_websocket(
     req: HttpRequest,
+    user: MySelf,
     stream: web::Payload,
     manager: web::Data<ChatConnectionManager>,
     pool: web::Data<MySqlPool>,
@@ -45,32 +45,8 @@ pub async fn chat_websocket(
     log::info!("[WS DEBUG] New WebSocket connection request from {:?}", req.peer_addr());
     log::debug!("[WS DEBUG] Request headers: {:?}", req.headers());
 
-    // Extract and validate JWT from cookie
-    let cookie = match req.cookie("jwt") {
-        Some(c) => {
-            log::debug!("[WS DEBUG] JWT cookie found");
-            c
-        },
-        None => {
-            log::warn!("[WS DEBUG] WebSocket rejected: No JWT cookie found");
-            log::debug!("[WS DEBUG] Available cookies: {:?}", req.cookies());
-            return Ok(HttpResponse::Unauthorized().body("Authentication required"));
-        }
-    };
-
-    let token = match validate(cookie.value()) {
-        Ok(t) => {
-            log::debug!("[WS DEBUG] JWT validated successfully");
-            t
-        },
-        Err(e) => {
-            log::warn!("[WS DEBUG] WebSocket rejected: Invalid JWT - {}", e);
-            return Ok(HttpResponse::Unauthorized().body("Invalid authentication token"));
-        }
-    };
-
-    let user_id = token.claims.user.id;
-    let user_role = token.claims.user.role.clone();
+    let user_id = user.id;
+    let user_role = user.role.clone();
 
     log::info!("[WS DEBUG] WebSocket: User {} ({:?}) authenticated successfully", user_id, user_role);
 
@@ -0,0 +1,295 @@
+use std::ops::Deref;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::error::{ErrorInternalServerError, InternalError};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, FromRequest, HttpRequest, HttpResponse};
+use chrono::Datelike;
+use dashmap::DashMap;
+use futures::future::LocalBoxFuture;
+use serde::Deserialize;
+use sqlx::mysql::{MySqlConnectOptions, MySqlPool, MySqlPoolOptions};
+use sqlx::Executor;
+
+use crate::config;
+
+/// Every academic year lives in its own database, named after the database
+/// of `DATABASE_URL` followed by the year (`colegio_` -> `colegio_2025`).
+#[derive(Clone)]
+pub struct Years {
+    options: MySqlConnectOptions,
+    prefix: String,
+}
+
+/// Year of the database the service is connected to.
+#[derive(Clone, Copy)]
+pub struct ActiveYear(pub i32);
+
+impl Years {
+    pub fn from_url(url: &str) -> anyhow::Result<Years> {
+        let options = MySqlConnectOptions::from_str(url)?;
+        let prefix = options.get_database().unwrap_or_default().to_string();
+        // the name ends up in statements that cannot bind it
+        if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            anyhow::bail!("The database URL must end with a database name prefix made of letters, digits and '_'");
+        }
+        Ok(Years { options, prefix })
+    }
+
+    /// Same server, with the school id added to the prefix.
+    pub fn for_school(&self, school_id: &str) -> Years {
+        Years {
+            options: self.options.clone(),
+            prefix: format!("{}{}_", self.prefix, school_id),
+        }
+    }
+
+    pub fn database_name(&self, year: i32) -> String {
+        format!("{}{}", self.prefix, year)
+    }
+
+    pub fn connect_options(&self, year: i32) -> MySqlConnectOptions {
+        self.options.clone().database(&self.database_name(year))
+    }
+
+    /// Years with a database on the server, oldest first.
+    pub async fn existing(&self) -> Result<Vec<i32>, sqlx::Error> {
+        let pool = MySqlPool::connect_with(self.options.clone().database("information_schema")).await?;
+        let names: Vec<String> = sqlx::query_scalar("SELECT SCHEMA_NAME FROM SCHEMATA WHERE SCHEMA_NAME LIKE ?")
+            .bind(format!("{}%", self.prefix.replace('_', "\\_")))
+            .fetch_all(&pool)
+            .await?;
+        pool.close().await;
+
+        let mut years: Vec<i32> = names
+            .iter()
+            .filter_map(|n| n.strip_prefix(&self.prefix)?.parse().ok())
+            .collect();
+        years.sort();
+        Ok(years)
+    }
+
+    /// `ACADEMIC_YEAR` if set, otherwise the newest year that already has a
+    /// database and has started. A new year only starts being used once its
+    /// database was created by a rollover.
+    pub async fn active(&self) -> Result<i32, sqlx::Error> {
+        if let Some(year) = config::get().academic_year {
+            return Ok(year);
+        }
+
+        let calendar_year = chrono::Utc::now().year();
+        let year = self
+            .existing()
+            .await?
+            .into_iter()
+            .rfind(|y| *y <= calendar_year)
+            .unwrap_or(calendar_year);
+        if year < calendar_year {
+            log::warn!(
+                "No database for {} yet, still using {}. Run the academic year rollover.",
+                calendar_year,
+                year
+            );
+        }
+        Ok(year)
+    }
+}
+
+pub const YEAR_HEADER: &str = "X-Academic-Year";
+
+/// Pools of past academic years, opened on first use. Their connections are
+/// read-only, history is never changed from the service.
+pub struct YearPools {
+    years: Years,
+    active: i32,
+    pools: DashMap<i32, MySqlPool>,
+    // looked up once, so asking for arbitrary years costs no database work
+    known: RwLock<Option<KnownYears>>,
+}
+
+#[derive(Clone)]
+struct KnownYears {
+    existing: Vec<i32>,
+    /// Past years reached from the active one through rollovers only.
+    linked: Vec<i32>,
+}
+
+enum PastYear {
+    Open(MySqlPool),
+    Missing,
+    /// Its user ids may belong to other people than in the active year.
+    Unlinked,
+}
+
+impl YearPools {
+    pub fn new(years: Years, active: i32) -> YearPools {
+        YearPools { years, active, pools: DashMap::new(), known: RwLock::new(None) }
+    }
+
+    /// Drops the known years, after a rollover created a new one.
+    pub fn refresh(&self) {
+        *self.known.write().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Years with a database and the past ones whose user ids match the
+    /// active year: every year after them up to the active one was created by
+    /// a rollover of the year before, as recorded in `year_rollovers`.
+    async fn known(&self, active_pool: &MySqlPool) -> Result<KnownYears, sqlx::Error> {
+        if let Some(known) = self.known.read().unwrap_or_else(|e| e.into_inner()).clone() {
+            return Ok(known);
+        }
+
+        let existing = self.years.existing().await?;
+        let rollovers: Vec<i32> = sqlx::query_scalar("SELECT from_year FROM year_rollovers WHERE from_year < ?")
+            .bind(self.active)
+            .fetch_all(active_pool)
+            .await?;
+        let mut linked = Vec::new();
+        let mut year = self.active - 1;
+        while rollovers.contains(&year) {
+            if existing.contains(&year) {
+                linked.push(year);
+            }
+            year -= 1;
+        }
+
+        let known = KnownYears { existing, linked };
+        *self.known.write().unwrap_or_else(|e| e.into_inner()) = Some(known.clone());
+        Ok(known)
+    }
+
+    /// Pool of a year before the active one, if it can be read.
+    async fn past(&self, active_pool: &MySqlPool, year: i32) -> Result<PastYear, sqlx::Error> {
+        if year >= self.active {
+            return Ok(PastYear::Missing);
+        }
+        if let Some(pool) = self.pools.get(&year) {
+            return Ok(PastYear::Open(pool.clone()));
+        }
+        let known = self.known(active_pool).await?;
+        if !known.existing.contains(&year) {
+            return Ok(PastYear::Missing);
+        }
+        if !known.linked.contains(&year) {
+            return Ok(PastYear::Unlinked);
+        }
+
+        let pool = MySqlPoolOptions::new()
+            .max_connections(2)
+            .after_connect(|conn, _| {
+                Box::pin(async move {
+                    conn.execute("SET SESSION TRANSACTION READ ONLY").await?;
+                    Ok(())
+                })
+            })
+            .connect_with(self.years.connect_options(year))
+            .await?;
+        Ok(PastYear::Open(self.pools.entry(year).or_insert(pool).clone()))
+    }
+}
+
+#[derive(Deserialize)]
+struct YearSelector {
+    academic_year: Option<i32>,
+}
+
+fn bad_request(message: &'static str) -> Error {
+    InternalError::from_response(message, HttpResponse::BadRequest().json(message)).into()
+}
+
+/// Year asked for with the `academic_year` query parameter or the
+/// `X-Academic-Year` header, the parameter taking precedence.
+fn selected_year(req: &HttpRequest) -> Result<Option<i32>, Error> {
+    let query = web::Query::<YearSelector>::from_query(req.query_string())
+        .map_err(|_| bad_request("Invalid academic_year"))?;
+    if let Some(year) = query.academic_year {
+        return Ok(Some(year));
+    }
+
+    match req.headers().get(YEAR_HEADER) {
+        Some(h) => h
+            .to_str()
+            .ok()
+            .and_then(|y| y.trim().parse().ok())
+            .map(Some)
+            .ok_or_else(|| bad_request("Invalid X-Academic-Year header")),
+        None => Ok(None),
+    }
+}
+
+/// Database of the selected academic year, the active one by default. Only
+/// read endpoints take it, past years cannot be written.
+pub struct YearPool(web::Data<MySqlPool>);
+
+impl YearPool {
+    pub fn into_inner(self) -> web::Data<MySqlPool> {
+        self.0
+    }
+}
+
+impl Deref for YearPool {
+    type Target = web::Data<MySqlPool>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromRequest for YearPool {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let active_pool = req.app_data::<web::Data<MySqlPool>>().cloned();
+        let year_pools = req.app_data::<web::Data<YearPools>>().cloned();
+        let selected = selected_year(req);
+
+        Box::pin(async move {
+            let active_pool = active_pool.ok_or_else(|| ErrorInternalServerError("Database pool not configured"))?;
+            let year_pools = year_pools.ok_or_else(|| ErrorInternalServerError("Year pools not configured"))?;
+
+            let year = match selected? {
+                Some(year) if year != year_pools.active => year,
+                _ => return Ok(YearPool(active_pool)),
+            };
+            match year_pools.past(&active_pool, year).await {
+                Ok(PastYear::Open(pool)) => Ok(YearPool(web::Data::new(pool))),
+                Ok(PastYear::Unlinked) => Err(InternalError::from_response(
+                    "Academic year not reached by rollovers, its users do not match",
+                    HttpResponse::Conflict().json("Academic year not reached by rollovers, its users do not match"),
+                )
+                .into()),
+                Ok(PastYear::Missing) => Err(InternalError::from_response(
+                    "Unknown academic year",
+                    HttpResponse::NotFound().json("Unknown academic year"),
+                )
+                .into()),
+                Err(e) => Err(ErrorInternalServerError(e.to_string())),
+            }
+        })
+    }
+}
+
+/// Writes always go to the active year; refusing them when another year is
+/// selected keeps a client from changing the wrong year by mistake.
+pub async fn reject_past_year_writes(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if !matches!(*req.method(), Method::GET | Method::HEAD)
+        && let Some(year_pools) = req.app_data::<web::Data<YearPools>>()
+        && let Some(year) = selected_year(req.request())?
+        && year != year_pools.active
+    {
+        return Err(InternalError::from_response(
+            "Past academic years are read-only",
+            HttpResponse::Forbidden().json("Past academic years are read-only"),
+        )
+        .into());
+    }
+    next.call(req).await
+}